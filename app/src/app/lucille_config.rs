@@ -2,6 +2,10 @@ use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
+use lucille_core::{
+    clean_sub::{CleanupRule, SubtitleCleaner},
+    subtitle_format::{SubtitleFormat, DEFAULT_SIDECAR_PRIORITY},
+};
 
 const QUALIFIER: &str = "io";
 const ORGANIZATION: &str = "vauntware";
@@ -22,6 +26,11 @@ const GIF_UPLOAD_S3_BUCKET_KEY: &str = "gif_upload_s3_bucket";
 
 const FFMPEG_CMD_KEY: &str = "ffmpeg";
 const MEDIA_VIEW_KEY: &str = "media_view_priority";
+const SUBTITLE_SIDECAR_KEY: &str = "subtitle_sidecar_priority";
+const SUBTITLE_LANGUAGE_KEY: &str = "subtitle_language_priority";
+const MEDIA_EXTENSIONS_KEY: &str = "media_extensions";
+const INDEX_CLEANUP_KEY: &str = "subtitle_index_cleanup";
+const RENDER_CLEANUP_KEY: &str = "subtitle_render_cleanup";
 
 const DEFAULT_CONFIG_FILE: &str = "lucille.toml";
 
@@ -211,16 +220,93 @@ impl LucilleConfig {
             Err(e) => panic!("{}", e),
         }
     }
-    pub fn media_view_priority(&self) -> Vec<String> {
-        if let Ok(v) = self.inner.get_string(MEDIA_VIEW_KEY) {
-            return vec![v];
+    /// A list of strings, which may be configured as a single string.
+    /// `None` if it is not configured.
+    fn get_string_list(&self, key: &str) -> Option<Vec<String>> {
+        if let Ok(v) = self.inner.get_string(key) {
+            return Some(vec![v]);
         }
         self.inner
-            .get_array(MEDIA_VIEW_KEY)
-            .unwrap_or_else(|_| Vec::new())
+            .get_array(key)
+            .ok()?
             .into_iter()
             .map(|v| v.into_string())
             .collect::<Result<Vec<_>, _>>()
-            .unwrap_or_else(|_| Vec::new())
+            .ok()
+    }
+    /// Extensions of the media files to ingest, without the dot
+    pub fn media_extensions(&self) -> Vec<String> {
+        self.get_string_list(MEDIA_EXTENSIONS_KEY)
+            .map(|exts| {
+                exts.into_iter()
+                    .map(|ext| ext.trim_start_matches('.').to_string())
+                    .collect()
+            })
+            .unwrap_or_else(|| {
+                crate::ingest::DEFAULT_MEDIA_EXTENSIONS
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect()
+            })
+    }
+    pub fn media_view_priority(&self) -> Vec<String> {
+        self.get_string_list(MEDIA_VIEW_KEY).unwrap_or_default()
+    }
+    /// Subtitle file extensions to look for next to media, most preferred first
+    pub fn subtitle_sidecar_priority(&self) -> Vec<String> {
+        let configured = match self.get_string_list(SUBTITLE_SIDECAR_KEY) {
+            Some(configured) => configured,
+            None => {
+                return DEFAULT_SIDECAR_PRIORITY
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect()
+            }
+        };
+        configured
+            .into_iter()
+            .filter(|ext| {
+                let known = SubtitleFormat::from_extension(ext).is_some();
+                if !known {
+                    log::warn!("ignoring unknown subtitle sidecar extension {:?}", ext);
+                }
+                known
+            })
+            .collect()
+    }
+    /// Languages of embedded subtitle streams to extract when media has no
+    /// sidecar, most preferred first. Codes such as `en` or `eng`, or names.
+    /// When empty, the corpus' language is preferred.
+    pub fn subtitle_language_priority(&self) -> Vec<String> {
+        self.get_string_list(SUBTITLE_LANGUAGE_KEY)
+            .unwrap_or_default()
+    }
+    /// How subtitles are cleaned before they are indexed and shown in search
+    /// results. Updating an index cleaned differently rebuilds it.
+    pub fn subtitle_index_cleanup(&self) -> SubtitleCleaner {
+        self.get_cleanup_rules(INDEX_CLEANUP_KEY)
+            .map(|rules| SubtitleCleaner::new(&rules))
+            .unwrap_or_else(SubtitleCleaner::index_default)
+    }
+    /// How subtitles are cleaned before they are burned into a clip
+    pub fn subtitle_render_cleanup(&self) -> SubtitleCleaner {
+        self.get_cleanup_rules(RENDER_CLEANUP_KEY)
+            .map(|rules| SubtitleCleaner::new(&rules))
+            .unwrap_or_else(SubtitleCleaner::render_default)
+    }
+    fn get_cleanup_rules(&self, key: &str) -> Option<Vec<CleanupRule>> {
+        let names = self.get_string_list(key)?;
+        Some(
+            names
+                .iter()
+                .filter_map(|name| match name.parse() {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        log::warn!("ignoring {} in {}", e, key);
+                        None
+                    }
+                })
+                .collect(),
+        )
     }
 }
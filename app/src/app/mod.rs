@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use anyhow::Context;
 use database::Database;
@@ -6,7 +6,9 @@ use lucille_core::uuid::Uuid;
 use search::SearchIndex;
 
 use crate::{
-    hashfs::HashFS, search_manager::SearchService, storage::backend::CascadingMediaBackend,
+    hashfs::HashFS,
+    search_manager::{FederatedSearchService, SearchService},
+    storage::backend::CascadingMediaBackend,
 };
 
 mod lucille_config;
@@ -139,11 +141,117 @@ impl LucilleApp {
         }
     }
 
-    pub fn search_service(&self, index_uuid: Uuid) -> anyhow::Result<SearchService> {
+    fn open_search_index(&self, index_uuid: Uuid) -> anyhow::Result<(SearchIndex, Option<String>)> {
         let index_dir = self.config.index_root().join(index_uuid.to_string());
         log::debug!("loading search index from: {:?}", index_dir.as_path());
         let index = SearchIndex::open_in_dir(index_uuid, index_dir)?;
-        Ok(SearchService { index })
+        let outdated = search::outdated_reason(index.info()?.as_ref());
+        Ok((index, outdated))
+    }
+
+    fn new_search_service(&self, index: SearchIndex) -> SearchService {
+        SearchService::new(index, Arc::new(self.db.clone()))
+            .with_media_view_priority(self.config.media_view_priority())
+            .with_subtitle_cleanup(self.config.subtitle_index_cleanup())
+    }
+
+    /// Load a search index, failing if it was built by an incompatible version.
+    ///
+    /// See [`LucilleApp::search_service_or_rebuild`] to replace outdated indexes.
+    pub fn search_service(&self, index_uuid: Uuid) -> anyhow::Result<SearchService> {
+        let (index, outdated) = self.open_search_index(index_uuid)?;
+        if let Some(reason) = outdated {
+            anyhow::bail!(
+                "search index {} is out of date and must be rebuilt: {}",
+                index_uuid,
+                reason
+            );
+        }
+        Ok(self.new_search_service(index))
+    }
+
+    /// Load a search index from a bundle made by [`SearchIndex::to_bundle`].
+    ///
+    /// The index is kept in memory, so this works without an index root.
+    pub fn search_service_from_bundle(&self, bundle: &[u8]) -> anyhow::Result<SearchService> {
+        let index = SearchIndex::from_bundle(bundle).context("could not load search index")?;
+        if let Some(reason) = search::outdated_reason(index.info()?.as_ref()) {
+            anyhow::bail!(
+                "search index {} is out of date and must be rebuilt: {}",
+                index.uuid(),
+                reason
+            );
+        }
+        Ok(self.new_search_service(index))
+    }
+
+    /// Load a search index, building a new one if it is out of date
+    pub async fn search_service_or_rebuild(
+        &self,
+        index_uuid: Uuid,
+    ) -> anyhow::Result<SearchService> {
+        self.warn_if_stale(index_uuid).await?;
+        let (index, outdated) = self.open_search_index(index_uuid)?;
+        let index = match outdated {
+            None => index,
+            Some(reason) => {
+                log::warn!("search index {} must be rebuilt: {}", index_uuid, reason);
+                crate::rebuild_index(self, index_uuid).await?
+            }
+        };
+        Ok(self.new_search_service(index))
+    }
+
+    /// Search across several indexes at once, labeling results with their corpus
+    pub async fn federated_search_service(
+        &self,
+        index_uuids: &[Uuid],
+        rebuild_outdated: bool,
+    ) -> anyhow::Result<FederatedSearchService> {
+        let mut services = Vec::with_capacity(index_uuids.len());
+        for index_uuid in index_uuids {
+            let service = if rebuild_outdated {
+                self.search_service_or_rebuild(*index_uuid).await
+            } else {
+                self.warn_if_stale(*index_uuid).await?;
+                self.search_service(*index_uuid)
+            };
+            let mut service =
+                service.with_context(|| format!("could not load search index {}", index_uuid))?;
+            if let Some(corpus) = self.db.get_corpus_for_search_index(*index_uuid).await? {
+                service = service.with_corpus(corpus.title);
+            }
+            services.push(service);
+        }
+        Ok(FederatedSearchService::new(services))
+    }
+
+    /// A stale index still works, but may find subtitles which were rolled back
+    async fn warn_if_stale(&self, index_uuid: Uuid) -> anyhow::Result<()> {
+        if self.db.is_search_index_stale(index_uuid).await? {
+            log::warn!(
+                "search index {} is stale, subtitles it covers were rolled back since it was updated",
+                index_uuid
+            );
+        }
+        Ok(())
+    }
+
+    /// The most recent search index in the language of every corpus which has one
+    pub async fn latest_search_indexes(&self) -> anyhow::Result<Vec<Uuid>> {
+        let mut indexes = Vec::new();
+        for corpus in self.db.list_corpus().await? {
+            let corpus_id = corpus.id.expect("corpus from db has id");
+            let language = self.db.get_corpus_language(corpus_id).await?;
+            if let Some(index) = self
+                .db
+                .get_latest_search_index_for_corpus_language(corpus_id, language)
+                .await?
+            {
+                indexes.push(index);
+            }
+        }
+        Ok(indexes)
     }
 }
 
@@ -87,6 +87,21 @@ impl FFMpegBinary {
             std::path::Path::new("ffmpeg")
         }
     }
+    /// ffprobe, from the same directory as ffmpeg when ffmpeg has a path
+    pub(crate) fn ffprobe(&self) -> FFMpegBinary {
+        let path = match &self.path {
+            Some(ffmpeg) => {
+                let mut name = OsString::from("ffprobe");
+                if let Some(ext) = ffmpeg.extension() {
+                    name.push(".");
+                    name.push(ext);
+                }
+                ffmpeg.with_file_name(name)
+            }
+            None => PathBuf::from("ffprobe"),
+        };
+        FFMpegBinary { path: Some(path) }
+    }
     pub(crate) fn build_command(self) -> FFmpegCommand {
         FFmpegCommand {
             bin: self,
@@ -103,6 +118,8 @@ pub(crate) struct FFmpegCommand {
     pub(crate) cwd: Option<FFmpegArg>,
     pub(crate) stdin: Option<StdIo>,
     pub(crate) stdout: Option<StdIo>,
+    /// Discarded by default, unless debugging
+    pub(crate) stderr: Option<StdIo>,
     debug: bool,
 }
 
@@ -144,7 +161,9 @@ impl FFmpegCommand {
             st.stdout(StdIo::Null.into_exec());
         }
 
-        if self.debug || cfg!(feature = "ffmpeg-debug") {
+        if let Some(stderr) = self.stderr {
+            st.stderr(stderr.into_exec());
+        } else if self.debug || cfg!(feature = "ffmpeg-debug") {
             st.stderr(StdIo::Inherit.into_exec());
         } else {
             st.stderr(StdIo::Null.into_exec());
@@ -1,7 +1,7 @@
 use std::{path::PathBuf, time::Duration};
 
 use anyhow::Context;
-use lucille_core::Subtitle;
+use lucille_core::{clean_sub::SubtitleCleaner, Subtitle};
 use tokio::io::{AsyncRead, AsyncWriteExt};
 
 use super::{
@@ -53,6 +53,8 @@ pub struct GifSettings {
     pub quality: QualitySettings,
     pub font_size: u32,
     pub cut_selection: GifTimeSelection,
+    /// How the subtitles are cleaned before they are burned in
+    pub subtitle_cleanup: SubtitleCleaner,
 }
 
 impl Default for GifSettings {
@@ -62,6 +64,7 @@ impl Default for GifSettings {
             quality: Default::default(),
             font_size: GIF_DEFAULT_FONT,
             cut_selection: Default::default(),
+            subtitle_cleanup: SubtitleCleaner::render_default(),
         }
     }
 }
@@ -135,7 +138,9 @@ impl FFMpegGifTranscoder {
             .await
             .with_context(|| format!("could not create srt file {:?}", srt_path))?;
 
-        for sub in offset_subs(sub_offset_start, subs) {
+        // the clip is cut around all of `subs`, even those with nothing left to show
+        let shown_subs = settings.subtitle_cleanup.clean_subtitles(subs);
+        for sub in offset_subs(sub_offset_start, &shown_subs) {
             let s = format!("{}", sub);
             f.write_all(s.as_bytes())
                 .await
@@ -1,6 +1,7 @@
 mod cmd;
 pub mod gif;
 pub mod split;
+pub mod subtitles;
 
 pub use cmd::FFMpegBinary;
 use cmd::{FFmpegArg, FFmpegCommand};
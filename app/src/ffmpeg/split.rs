@@ -167,14 +167,14 @@ mod tests {
     fn ffmpeg_split_command() {
         let split = FFMpegMediaSplit::new(
             &FFMpegBinary::default(),
-            "video.mkv",
+            "video.mp4",
             Duration::from_secs(30),
         )
         .unwrap();
         let actual = format!("{:?}", split.cmd.test_display());
         assert_eq!(
             actual,
-            r##"FFMpegTestFormat { bin: "ffmpeg", args: ["-i", "video.mkv", "-y", "-f", "segment", "-segment_time", "30", "-segment_list", "split_records.csv", "out%06d.mkv"], cwd: Some("output_dir"), stdin: None, stdout: None }"##,
+            r##"FFMpegTestFormat { bin: "ffmpeg", args: ["-i", "video.mp4", "-y", "-f", "segment", "-segment_time", "30", "-segment_list", "split_records.csv", "out%06d.mkv"], cwd: Some("output_dir"), stdin: None, stdout: None }"##,
         )
     }
 }
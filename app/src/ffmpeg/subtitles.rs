@@ -0,0 +1,302 @@
+use std::{ffi::OsString, str::FromStr};
+
+use lucille_core::{language::Language, Subtitle};
+use serde::Deserialize;
+
+use super::{cmd::StdIo, FFMpegBinary, FFmpegArg, FFmpegCommand};
+
+/// Subtitle codecs which are drawn as images, they have no text to extract
+const BITMAP_CODECS: &[&str] = &[
+    "hdmv_pgs_subtitle",
+    "dvd_subtitle",
+    "dvb_subtitle",
+    "dvb_teletext",
+    "xsub",
+];
+
+/// A subtitle stream of a media container, as ffprobe lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleStream {
+    /// The position of the stream among the subtitle streams, for `-map 0:s:N`
+    pub index: usize,
+    /// The language tag of the stream, usually an ISO 639-2 code such as `eng`
+    pub language: Option<String>,
+    pub codec: String,
+    pub default: bool,
+    /// Forced subtitles only cover foreign dialogue and signs
+    pub forced: bool,
+}
+
+impl SubtitleStream {
+    pub fn is_text(&self) -> bool {
+        !BITMAP_CODECS.contains(&self.codec.as_str())
+    }
+
+    /// The language of the stream, if its tag is a language lucille knows
+    pub fn tagged_language(&self) -> Option<Language> {
+        self.language.as_deref()?.parse().ok()
+    }
+
+    /// Does the stream's language tag match `preference`, a language code or name
+    fn has_language(&self, preference: &str) -> bool {
+        let language = match &self.language {
+            Some(language) => language,
+            None => return false,
+        };
+        if language.eq_ignore_ascii_case(preference) {
+            return true;
+        }
+        match (Language::from_str(preference), Language::from_str(language)) {
+            (Ok(preferred), Ok(tagged)) => preferred == tagged,
+            _ => false,
+        }
+    }
+}
+
+/// The output of `ffprobe -show_streams -of json`
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    #[serde(default)]
+    disposition: ProbeDisposition,
+    #[serde(default)]
+    tags: ProbeTags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProbeDisposition {
+    #[serde(default)]
+    default: u8,
+    #[serde(default)]
+    forced: u8,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProbeTags {
+    language: Option<String>,
+}
+
+/// Parse the subtitle streams from ffprobe's JSON description of a file
+fn parse_subtitle_streams(ffprobe_output: &str) -> anyhow::Result<Vec<SubtitleStream>> {
+    let output: ProbeOutput = serde_json::from_str(ffprobe_output)?;
+    Ok(output
+        .streams
+        .into_iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("subtitle"))
+        .enumerate()
+        .map(|(index, stream)| SubtitleStream {
+            index,
+            language: stream
+                .tags
+                .language
+                .filter(|language| !language.is_empty() && language != "und"),
+            codec: stream.codec_name.unwrap_or_default(),
+            default: stream.disposition.default != 0,
+            forced: stream.disposition.forced != 0,
+        })
+        .collect())
+}
+
+/// The text stream to extract: the earliest match in `language_priority`, then
+/// complete subtitles over forced ones, then the default stream, then the first
+pub fn best_subtitle_stream<'s>(
+    streams: &'s [SubtitleStream],
+    language_priority: &[String],
+) -> Option<&'s SubtitleStream> {
+    streams.iter().filter(|s| s.is_text()).min_by_key(|s| {
+        let language_rank = language_priority
+            .iter()
+            .position(|preference| s.has_language(preference))
+            .unwrap_or(language_priority.len());
+        (language_rank, s.forced, !s.default, s.index)
+    })
+}
+
+/// Lists the subtitle streams of a media file with ffprobe, see [`FFMpegBinary::ffprobe`]
+#[derive(Debug)]
+pub struct FFMpegSubtitleProbe {
+    cmd: FFmpegCommand,
+}
+
+impl FFMpegSubtitleProbe {
+    pub fn new<P: Into<OsString>>(bin: &FFMpegBinary, src: P) -> FFMpegSubtitleProbe {
+        let mut cmd = bin.ffprobe().build_command();
+        for arg in [
+            "-v",
+            "error",
+            "-select_streams",
+            "s",
+            "-show_streams",
+            "-of",
+            "json",
+        ] {
+            cmd.args.push(FFmpegArg::plain(arg));
+        }
+        cmd.args.push(FFmpegArg::plain(src));
+        cmd.stdout = Some(StdIo::Piped);
+        FFMpegSubtitleProbe { cmd }
+    }
+
+    pub async fn run(self) -> anyhow::Result<Vec<SubtitleStream>> {
+        let output = self.cmd.spawn().await?.wait_with_output().await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "ffprobe could not read the streams, exit {:?}",
+                output.status.code()
+            );
+        }
+        parse_subtitle_streams(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Converts one subtitle stream of a media file to SRT
+#[derive(Debug)]
+pub struct FFMpegSubtitleExtract {
+    cmd: FFmpegCommand,
+}
+
+impl FFMpegSubtitleExtract {
+    pub fn new<P: Into<OsString>>(
+        bin: &FFMpegBinary,
+        src: P,
+        stream: &SubtitleStream,
+    ) -> FFMpegSubtitleExtract {
+        let mut cmd = bin.clone().build_command();
+        cmd.args.push(FFmpegArg::plain("-i"));
+        cmd.args.push(FFmpegArg::plain(src));
+        cmd.args.push(FFmpegArg::plain("-map"));
+        cmd.args
+            .push(FFmpegArg::plain(format!("0:s:{}", stream.index)));
+        cmd.args.push(FFmpegArg::plain("-c:s"));
+        cmd.args.push(FFmpegArg::plain("srt"));
+        cmd.args.push(FFmpegArg::plain("-f"));
+        cmd.args.push(FFmpegArg::plain("srt"));
+        cmd.args.push(FFmpegArg::plain("pipe:"));
+        cmd.stdout = Some(StdIo::Piped);
+        FFMpegSubtitleExtract { cmd }
+    }
+
+    pub async fn run(self) -> anyhow::Result<Vec<Subtitle>> {
+        let output = self.cmd.spawn().await?.wait_with_output().await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "subtitle extraction error, ffmpeg exit {:?}",
+                output.status.code()
+            );
+        }
+        let srt = String::from_utf8_lossy(&output.stdout);
+        subrip::parse(&srt).map_err(|e| anyhow::anyhow!("ffmpeg produced invalid SRT: {:?}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FFPROBE_OUTPUT: &str = r#"{
+    "streams": [
+        {
+            "index": 2,
+            "codec_name": "hdmv_pgs_subtitle",
+            "codec_type": "subtitle",
+            "disposition": { "default": 1, "forced": 0 },
+            "tags": { "language": "eng" }
+        },
+        {
+            "index": 3,
+            "codec_name": "subrip",
+            "codec_type": "subtitle",
+            "disposition": { "default": 0, "forced": 1 },
+            "tags": { "language": "eng" }
+        },
+        {
+            "index": 4,
+            "codec_name": "ass",
+            "codec_type": "subtitle",
+            "disposition": { "default": 0, "forced": 0 },
+            "tags": { "language": "spa" }
+        },
+        {
+            "index": 5,
+            "codec_name": "subrip",
+            "codec_type": "subtitle",
+            "disposition": { "default": 0, "forced": 0 },
+            "tags": { "language": "eng", "title": "English SDH" }
+        },
+        {
+            "index": 6,
+            "codec_name": "mov_text",
+            "codec_type": "subtitle",
+            "tags": { "language": "und" }
+        }
+    ]
+}"#;
+
+    #[test]
+    fn parse_streams() {
+        let streams = parse_subtitle_streams(FFPROBE_OUTPUT).unwrap();
+        assert_eq!(streams.len(), 5);
+        assert_eq!(
+            streams[1],
+            SubtitleStream {
+                index: 1,
+                language: Some("eng".to_string()),
+                codec: "subrip".to_string(),
+                default: false,
+                forced: true,
+            }
+        );
+        assert!(!streams[0].is_text());
+        assert_eq!(streams[4].language, None);
+        assert_eq!(streams[1].tagged_language(), Some(Language::English));
+        assert_eq!(streams[4].tagged_language(), None);
+        assert_eq!(streams[4].codec, "mov_text");
+        assert!(!streams[4].default);
+
+        assert!(parse_subtitle_streams("not json").is_err());
+        assert_eq!(parse_subtitle_streams("{}").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn choose_stream_by_language() {
+        let streams = parse_subtitle_streams(FFPROBE_OUTPUT).unwrap();
+        let best = |languages: &[&str]| {
+            let languages = languages.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+            best_subtitle_stream(&streams, &languages).map(|s| s.index)
+        };
+        // complete english subtitles over forced ones and images
+        assert_eq!(best(&["en"]), Some(3));
+        assert_eq!(best(&["Spanish", "eng"]), Some(2));
+        assert_eq!(best(&["spa"]), Some(2));
+        // the first text stream, when nothing matches
+        assert_eq!(best(&["fr"]), Some(2));
+        assert_eq!(best_subtitle_stream(&streams[..1], &[]), None);
+    }
+
+    #[test]
+    fn ffmpeg_subtitle_commands() {
+        let probe = FFMpegSubtitleProbe::new(&FFMpegBinary::default(), "video.mkv");
+        assert_eq!(
+            format!("{:?}", probe.cmd.test_display()),
+            r##"FFMpegTestFormat { bin: "ffprobe", args: ["-v", "error", "-select_streams", "s", "-show_streams", "-of", "json", "video.mkv"], cwd: None, stdin: None, stdout: Some(Piped) }"##,
+        );
+        let probe = FFMpegSubtitleProbe::new(&FFMpegBinary::new("/opt/ffmpeg/bin/ffmpeg"), "v.mkv");
+        assert!(format!("{:?}", probe.cmd.test_display())
+            .starts_with(r#"FFMpegTestFormat { bin: "/opt/ffmpeg/bin/ffprobe""#));
+
+        let streams = parse_subtitle_streams(FFPROBE_OUTPUT).unwrap();
+        let extract =
+            FFMpegSubtitleExtract::new(&FFMpegBinary::default(), "video.mkv", &streams[3]);
+        assert_eq!(
+            format!("{:?}", extract.cmd.test_display()),
+            r##"FFMpegTestFormat { bin: "ffmpeg", args: ["-i", "video.mkv", "-map", "0:s:3", "-c:s", "srt", "-f", "srt", "pipe:"], cwd: None, stdin: None, stdout: Some(Piped) }"##,
+        );
+    }
+}
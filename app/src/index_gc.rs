@@ -0,0 +1,188 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use lucille_core::{identifiers::CorpusId, language::Language, uuid::Uuid};
+
+use crate::app::LucilleApp;
+
+/// Search indexes which are no longer needed
+#[derive(Debug, Default)]
+pub struct StaleIndexes {
+    /// Indexes older than the newest few of their corpus and language, or which
+    /// cover no corpus
+    pub superseded: Vec<Uuid>,
+    /// Index directories in `index_root` which the database does not know about
+    pub orphaned: Vec<PathBuf>,
+}
+
+impl StaleIndexes {
+    pub fn is_empty(&self) -> bool {
+        self.superseded.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Find the indexes which [`remove_stale_indexes`] would delete, keeping
+/// the newest `keep` indexes of every language of every corpus.
+pub async fn find_stale_indexes(app: &LucilleApp, keep: usize) -> anyhow::Result<StaleIndexes> {
+    if keep == 0 {
+        anyhow::bail!("must keep at least one search index per corpus");
+    }
+
+    let indexes = app.db.get_search_indexes_with_corpus().await?;
+    let known = indexes
+        .iter()
+        .map(|(uuid, _)| *uuid)
+        .collect::<HashSet<_>>();
+
+    // indexes are listed newest first
+    let mut kept: HashMap<(CorpusId, Option<Language>), usize> = HashMap::new();
+    let mut superseded = Vec::new();
+    for (uuid, corpus_id) in indexes {
+        match corpus_id {
+            Some(corpus_id) => {
+                let language = app.db.get_search_index_language(uuid).await?;
+                let count = kept.entry((corpus_id, language)).or_default();
+                if *count < keep {
+                    *count += 1;
+                } else {
+                    superseded.push(uuid);
+                }
+            }
+            None => superseded.push(uuid),
+        }
+    }
+
+    let mut orphaned = Vec::new();
+    let index_root = app.config.index_root();
+    if index_root.exists() {
+        let mut entries = tokio::fs::read_dir(&index_root)
+            .await
+            .with_context(|| format!("could not read index root {:?}", index_root))?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            match name.to_str().map(Uuid::from_str) {
+                Some(Ok(uuid)) if known.contains(&uuid) => {}
+                Some(Ok(_)) => orphaned.push(entry.path()),
+                _ => log::debug!("ignoring unknown directory in index root: {:?}", name),
+            }
+        }
+    }
+    orphaned.sort();
+
+    Ok(StaleIndexes {
+        superseded,
+        orphaned,
+    })
+}
+
+/// Delete the directories and database records of stale indexes
+pub async fn remove_stale_indexes(app: &LucilleApp, stale: &StaleIndexes) -> anyhow::Result<()> {
+    let mut errs = 0;
+    for uuid in &stale.superseded {
+        let index_dir = app.config.index_root().join(uuid.to_string());
+        if let Err(e) = remove_index_dir(index_dir).await {
+            errs += 1;
+            log::error!("{:#}", e);
+            // keep the record while the directory is still around
+            continue;
+        }
+        app.db.delete_search_index(*uuid).await?;
+    }
+    for index_dir in &stale.orphaned {
+        if let Err(e) = remove_index_dir(index_dir.clone()).await {
+            errs += 1;
+            log::error!("{:#}", e);
+        }
+    }
+    if errs != 0 {
+        anyhow::bail!("could not remove {} search index directories", errs);
+    }
+    Ok(())
+}
+
+async fn remove_index_dir(index_dir: PathBuf) -> anyhow::Result<()> {
+    match tokio::fs::remove_dir_all(&index_dir).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("could not remove {:?}", index_dir)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lucille_core::{metadata::MediaHash, test_util::generate_subtitle};
+
+    use super::*;
+    use crate::app::tests::lucille_test_app;
+
+    #[tokio::test]
+    async fn remove_old_and_orphaned_indexes() {
+        let tapp = lucille_test_app().await;
+        let app = &tapp.app;
+        let corpus_id = app.db.add_corpus("show").await.unwrap().id.unwrap();
+        let chapter_id = app
+            .db
+            .define_chapter(
+                corpus_id,
+                "episode",
+                Some(1),
+                Some(1),
+                MediaHash::from_bytes(b"episode"),
+            )
+            .await
+            .unwrap();
+        app.db
+            .add_subtitles(chapter_id, &generate_subtitle(&["there's always money"]))
+            .await
+            .unwrap();
+
+        app.db
+            .add_subtitle_track(
+                chapter_id,
+                Some(Language::Spanish),
+                &generate_subtitle(&["siempre hay dinero"]),
+            )
+            .await
+            .unwrap();
+
+        let mut built = Vec::new();
+        for _ in 0..3 {
+            let index = crate::index_subtitles(app, corpus_id, None).await.unwrap();
+            built.push(index.uuid());
+        }
+        // the only index of another language is never superseded by these
+        let spanish = crate::index_subtitles_for_language(app, corpus_id, Language::Spanish, None)
+            .await
+            .unwrap();
+        let orphan = app.config.index_root().join(Uuid::generate().to_string());
+        std::fs::create_dir_all(&orphan).unwrap();
+        let unrelated = app.config.index_root().join("notes");
+        std::fs::create_dir_all(&unrelated).unwrap();
+
+        let stale = find_stale_indexes(app, 2).await.unwrap();
+        assert_eq!(stale.superseded, vec![built[0]]);
+        assert_eq!(stale.orphaned, vec![orphan.clone()]);
+
+        remove_stale_indexes(app, &stale).await.unwrap();
+        let index_dir = |uuid: Uuid| app.config.index_root().join(uuid.to_string());
+        assert!(!index_dir(built[0]).exists());
+        assert!(index_dir(built[1]).exists());
+        assert!(index_dir(built[2]).exists());
+        assert!(!orphan.exists());
+        assert!(unrelated.exists());
+        assert_eq!(
+            app.db.get_search_indexes().await.unwrap(),
+            vec![built[1], built[2], spanish.uuid()]
+        );
+
+        assert!(find_stale_indexes(app, 2).await.unwrap().is_empty());
+        assert!(find_stale_indexes(app, 0).await.is_err());
+    }
+}
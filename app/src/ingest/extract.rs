@@ -1,18 +1,27 @@
-use std::{io::Read, path};
+use std::{collections::BTreeMap, io::Read, path};
 
 use database::Database;
+use lucille_core::{
+    language::Language, metadata::MediaHash, subtitle_format::SubtitleFormat, Subtitle,
+};
 
-use super::{ScannedData, ScannedSubtitles};
-use crate::hashfs::compute_hash;
+use super::{MediaProcessor, ScannedData, ScannedSubtitles};
+use crate::{
+    ffmpeg::{
+        subtitles::{best_subtitle_stream, FFMpegSubtitleExtract, FFMpegSubtitleProbe},
+        FFMpegBinary,
+    },
+    hashfs::compute_hash,
+};
 
 pub(crate) async fn read_media_from_path(
-    db: &Database,
+    processor: &MediaProcessor,
     media_path: &path::Path,
-    trust_hashes: bool,
 ) -> anyhow::Result<ScannedData> {
-    let subtitles = extract_subtitles(media_path)?;
-    let media_hash = if trust_hashes {
-        match db.get_storage_by_path(media_path).await {
+    let mut subtitles = extract_subtitles(media_path, &processor.sidecar_priority)?;
+    let mut language_tracks = extract_language_sidecars(media_path, &processor.sidecar_priority)?;
+    let media_hash = if processor.trust_hashes {
+        match processor.db.get_storage_by_path(media_path).await {
             Ok(media_opt) => media_opt.map(|m| Ok(m.hash)),
             Err(e) => {
                 log::error!(
@@ -32,24 +41,196 @@ pub(crate) async fn read_media_from_path(
         compute_hash(media_path).await
     }?;
 
+    if subtitles == ScannedSubtitles::NotFound && language_tracks.is_empty() {
+        subtitles = if has_subtitles(&processor.db, media_hash).await {
+            // extracted by an earlier scan, the media can't have changed
+            ScannedSubtitles::Known
+        } else {
+            match extract_embedded_subtitles(
+                &processor.ffmpeg,
+                media_path,
+                &processor.subtitle_language_priority,
+            )
+            .await
+            {
+                Some((Some(language), subs)) => {
+                    language_tracks.push((language, subs));
+                    ScannedSubtitles::NotFound
+                }
+                Some((None, subs)) => ScannedSubtitles::Subtitles(subs),
+                None => ScannedSubtitles::NotFound,
+            }
+        };
+    }
+
     Ok(ScannedData {
         path: media_path.to_path_buf(),
         subs: subtitles,
+        language_tracks,
         hash: media_hash,
     })
 }
 
-/// find/extract subtitles for a given piece of media
-fn extract_subtitles(media_path: &path::Path) -> anyhow::Result<ScannedSubtitles> {
-    let srt_path = media_path.with_extension("srt");
-    if !srt_path.exists() {
-        return Ok(ScannedSubtitles::NotFound);
+/// find/extract subtitles for a given piece of media, from the first sidecar
+/// file with an extension in `sidecar_priority`
+pub(super) fn extract_subtitles(
+    media_path: &path::Path,
+    sidecar_priority: &[String],
+) -> anyhow::Result<ScannedSubtitles> {
+    for ext in sidecar_priority {
+        let format = match SubtitleFormat::from_extension(ext) {
+            Some(format) => format,
+            None => continue,
+        };
+        let sidecar_path = media_path.with_extension(ext);
+        if !sidecar_path.exists() {
+            continue;
+        }
+        let contents = read_path_to_string(sidecar_path.as_path())?;
+        return Ok(match format.parse(&contents) {
+            Ok(s) => ScannedSubtitles::Subtitles(s),
+            Err(e) => ScannedSubtitles::Error(e),
+        });
+    }
+    Ok(ScannedSubtitles::NotFound)
+}
+
+/// find the sidecars tagged with a language, such as `episode.es.srt` next to
+/// `episode.mkv`. Every language uses its first sidecar in `sidecar_priority`.
+pub(super) fn extract_language_sidecars(
+    media_path: &path::Path,
+    sidecar_priority: &[String],
+) -> anyhow::Result<Vec<(Language, Vec<Subtitle>)>> {
+    let (dir, stem) = match (
+        media_path.parent(),
+        media_path.file_stem().and_then(|s| s.to_str()),
+    ) {
+        (Some(dir), Some(stem)) => (dir, stem),
+        _ => return Ok(vec![]),
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        path::Path::new(".")
+    } else {
+        dir
+    };
+
+    // the most preferred sidecar of each language
+    let mut sidecars = BTreeMap::<usize, (usize, Language, SubtitleFormat, path::PathBuf)>::new();
+    for entry in std::fs::read_dir(dir)? {
+        let sidecar_path = entry?.path();
+        let (rank, tag, format) = match sidecar_tag(&sidecar_path, stem, sidecar_priority) {
+            Some(found) => found,
+            None => continue,
+        };
+        let language = match tag.parse::<Language>() {
+            Ok(language) => language,
+            Err(_) => {
+                log::debug!("ignoring sidecar with unknown language: {:?}", sidecar_path);
+                continue;
+            }
+        };
+        let order = Language::ALL
+            .iter()
+            .position(|l| *l == language)
+            .expect("every language is listed");
+        let preferred = match sidecars.get(&order) {
+            Some((best, ..)) => rank < *best,
+            None => true,
+        };
+        if preferred {
+            sidecars.insert(order, (rank, language, format, sidecar_path));
+        }
+    }
+
+    let mut tracks = vec![];
+    for (_, (_, language, format, sidecar_path)) in sidecars {
+        let contents = read_path_to_string(sidecar_path.as_path())?;
+        match format.parse(&contents) {
+            Ok(subs) => tracks.push((language, subs)),
+            Err(e) => log::error!("could not parse subtitles {:?}: {}", sidecar_path, e),
+        }
+    }
+    Ok(tracks)
+}
+
+/// The rank in `sidecar_priority`, language tag and format of a sidecar named
+/// like `{stem}.{tag}.{ext}`
+fn sidecar_tag<'a>(
+    sidecar_path: &'a path::Path,
+    stem: &str,
+    sidecar_priority: &[String],
+) -> Option<(usize, &'a str, SubtitleFormat)> {
+    let tagged = sidecar_path
+        .file_name()?
+        .to_str()?
+        .strip_prefix(stem)?
+        .strip_prefix('.')?;
+    let (tag, ext) = tagged.split_once('.')?;
+    let rank = sidecar_priority
+        .iter()
+        .position(|p| p.eq_ignore_ascii_case(ext))?;
+    let format = SubtitleFormat::from_extension(ext)?;
+    Some((rank, tag, format))
+}
+
+/// Does a chapter with the media `hash` already have subtitles
+async fn has_subtitles(db: &Database, hash: MediaHash) -> bool {
+    let chapter = match db.get_chapter_by_hash(hash).await {
+        Ok(Some(chapter)) => chapter,
+        Ok(None) => return false,
+        Err(e) => {
+            log::error!("could not look up chapter for {}: {}", hash, e);
+            return false;
+        }
+    };
+    match db.lookup_latest_sub_for_chapter(chapter.id).await {
+        Ok(subs) => subs.is_some(),
+        Err(e) => {
+            log::error!(
+                "could not look up subtitles of chapter {}: {}",
+                chapter.id,
+                e
+            );
+            false
+        }
+    }
+}
+
+/// extract the best embedded text subtitle stream of the media, see
+/// [`best_subtitle_stream`], with the language the stream is tagged with
+async fn extract_embedded_subtitles(
+    ffmpeg: &FFMpegBinary,
+    media_path: &path::Path,
+    language_priority: &[String],
+) -> Option<(Option<Language>, Vec<Subtitle>)> {
+    let streams = match FFMpegSubtitleProbe::new(ffmpeg, media_path).run().await {
+        Ok(streams) => streams,
+        Err(e) => {
+            log::warn!("could not list subtitle streams of {:?}: {}", media_path, e);
+            return None;
+        }
+    };
+    let stream = best_subtitle_stream(&streams, language_priority)?;
+    log::info!(
+        "extracting embedded subtitles from {:?}: {:?}",
+        media_path,
+        stream
+    );
+    match FFMpegSubtitleExtract::new(ffmpeg, media_path, stream)
+        .run()
+        .await
+    {
+        Ok(subs) => Some((stream.tagged_language(), subs)),
+        Err(e) => {
+            log::warn!(
+                "could not extract subtitle stream {} of {:?}: {}",
+                stream.index,
+                media_path,
+                e
+            );
+            None
+        }
     }
-    let srt_contents = read_path_to_string(srt_path.as_path())?;
-    Ok(match subrip::parse(&srt_contents) {
-        Ok(s) => ScannedSubtitles::Subtitles(s),
-        Err(e) => ScannedSubtitles::Error(e),
-    })
 }
 
 fn read_path_to_string<P: AsRef<path::Path>>(tpath: P) -> anyhow::Result<String> {
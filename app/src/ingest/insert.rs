@@ -36,21 +36,18 @@ pub(crate) async fn add_scanned_media_to_db(
     media: &ScannedMedia,
 ) -> anyhow::Result<ChapterId> {
     log::trace!("insert media into db: {:?}", media);
-    let (title, season, episode) = match &media.metadata {
-        lucille_core::metadata::MediaMetadata::Episode(e) => (
-            e.title.as_str(),
-            Some(e.season as i64),
-            Some(e.episode as i64),
-        ),
-        lucille_core::metadata::MediaMetadata::Unknown(u) => (u.as_str(), None, None),
-    };
     let chapter_id = db
-        .define_chapter(corpus_id, title, season, episode, media.hash)
+        .define_media_chapter(corpus_id, &media.metadata, media.hash)
         .await?;
+    let untagged = matches!(media.subs, ScannedSubtitles::Subtitles(_));
     match &media.subs {
+        ScannedSubtitles::NotFound if !media.language_tracks.is_empty() => {}
         ScannedSubtitles::NotFound => {
             log::error!("not adding subtitles for {:?}: None Found", media);
         }
+        ScannedSubtitles::Known => {
+            log::debug!("keeping the subtitles of {:?}", media.path);
+        }
         ScannedSubtitles::Error(e) => {
             log::error!("not adding subtitles for {:?}: {:?}", media, e);
         }
@@ -58,6 +55,21 @@ pub(crate) async fn add_scanned_media_to_db(
             let _uuid = db.add_subtitles(chapter_id, subs).await?;
         }
     }
+    let corpus_language = db.get_corpus_language(corpus_id).await?;
+    for (language, subs) in &media.language_tracks {
+        // the untagged subtitles already are the corpus' language track
+        if untagged && *language == corpus_language {
+            log::warn!(
+                "not adding {} subtitles for {:?}: untagged subtitles are in the corpus' language",
+                language,
+                media.path
+            );
+            continue;
+        }
+        let _uuid = db
+            .add_subtitle_track(chapter_id, Some(*language), subs)
+            .await?;
+    }
     if !check_if_hash_is_chapter_original(db, chapter_id, media.hash).await? {
         let media_view_id = db.add_media_view(chapter_id, ORIGINAL_MEDIA_VIEW).await?;
         db.add_media_segment(media_view_id.id, 0, media.hash, Duration::default(), None)
@@ -114,11 +126,8 @@ mod tests {
         let fname = std::path::PathBuf::from("/path/to/file");
         let subs = generate_subtitle(&["line1"]);
         let hash = MediaHash::from_bytes(b"data");
-        let metadata = lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata {
-            season: 3,
-            episode: 12,
-            title: "ep title".to_owned(),
-        });
+        let metadata =
+            lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata::new(3, 12, "ep title"));
 
         let chapter_id = add_scanned_media_to_db(
             &tapp.app.db,
@@ -126,6 +135,10 @@ mod tests {
             &ScannedMedia {
                 path: fname.clone(),
                 subs: ScannedSubtitles::Subtitles(subs),
+                language_tracks: vec![(
+                    lucille_core::language::Language::Spanish,
+                    generate_subtitle(&["línea 1"]),
+                )],
                 hash,
                 metadata: metadata.clone(),
             },
@@ -133,6 +146,18 @@ mod tests {
         .await
         .expect("failure adding show to db");
 
+        let spanish = tapp
+            .app
+            .db
+            .lookup_latest_sub_for_chapter_language(
+                chapter_id,
+                lucille_core::language::Language::Spanish,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spanish.subs, generate_subtitle(&["línea 1"]));
+
         let storage = tapp
             .app
             .db
@@ -180,11 +205,8 @@ mod tests {
         let fname = std::path::PathBuf::from("/path/to/file");
         let subs = generate_subtitle(&["line1"]);
         let hash = MediaHash::from_bytes(b"data");
-        let metadata = lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata {
-            season: 3,
-            episode: 12,
-            title: "ep title".to_owned(),
-        });
+        let metadata =
+            lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata::new(3, 12, "ep title"));
 
         let _c1 = add_scanned_media_to_db(
             &tapp.app.db,
@@ -192,6 +214,7 @@ mod tests {
             &ScannedMedia {
                 path: fname.clone(),
                 subs: ScannedSubtitles::Subtitles(subs.clone()),
+                language_tracks: vec![],
                 hash,
                 metadata: metadata.clone(),
             },
@@ -205,6 +228,7 @@ mod tests {
             &ScannedMedia {
                 path: fname.clone(),
                 subs: ScannedSubtitles::Subtitles(subs),
+                language_tracks: vec![],
                 hash,
                 metadata: metadata.clone(),
             },
@@ -223,11 +247,8 @@ mod tests {
         let subs = generate_subtitle(&["line1"]);
         let hash = MediaHash::from_bytes(b"data");
         let hash2 = MediaHash::from_bytes(b"data2");
-        let metadata = lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata {
-            season: 3,
-            episode: 12,
-            title: "ep title".to_owned(),
-        });
+        let metadata =
+            lucille_core::metadata::MediaMetadata::Episode(EpisodeMetadata::new(3, 12, "ep title"));
 
         let _c1 = add_scanned_media_to_db(
             &tapp.app.db,
@@ -235,6 +256,7 @@ mod tests {
             &ScannedMedia {
                 path: fname.clone(),
                 subs: ScannedSubtitles::Subtitles(subs.clone()),
+                language_tracks: vec![],
                 hash,
                 metadata: metadata.clone(),
             },
@@ -249,6 +271,7 @@ mod tests {
             &ScannedMedia {
                 path: fname.clone(),
                 subs: ScannedSubtitles::Subtitles(subs),
+                language_tracks: vec![],
                 hash: hash2,
                 metadata: metadata.clone(),
             },
@@ -1,56 +1,325 @@
 use std::path;
 
 use lucille_core::metadata::{EpisodeMetadata, MediaMetadata};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
 
 use super::{ScannedData, ScannedMedia};
 
+/// Multi-episode ranges longer than this are not believed, e.g. `S01E01-99`
+const MAX_EPISODE_RANGE: u32 = 10;
+
+fn regex(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("invalid metadata regex")
+}
+
+/// `S01E02`, with more episodes `S01E02E03`, `S01E02-E04` or `S01E02-04`
+static SEASON_EPISODE: Lazy<Regex> =
+    Lazy::new(|| regex(r"(?i)\bS(\d{1,2}) ?E(\d{1,4})((?:-?E\d{1,4}|-\d{1,4})*)\b"));
+/// `1x02`, with more episodes `1x02x03`, `1x02-03` or `1x02-1x03`
+static CROSS_EPISODE: Lazy<Regex> =
+    Lazy::new(|| regex(r"(?i)\b(\d{1,2})x(\d{2,3})((?:-\d{1,2}x\d{2,3}|[-x]\d{2,3})*)\b"));
+/// `Season 1 Episode 2`
+static LONG_EPISODE: Lazy<Regex> =
+    Lazy::new(|| regex(r"(?i)\bseason ?(\d{1,2})\W{0,3}episode ?(\d{1,4})\b"));
+/// `E12`, `Ep 12` or `Episode 12` without a season
+static EPISODE_ONLY: Lazy<Regex> =
+    Lazy::new(|| regex(r"(?i)\b(?:e|ep ?|episode ?)(\d{1,4})((?:-e?\d{1,4})*)\b"));
+/// `Show - 012`, as anime is usually numbered
+static DASH_EPISODE: Lazy<Regex> = Lazy::new(|| regex(r"(?i)(?:^|\s)-\s(\d{1,4})(?:v\d)?\b"));
+/// `02 - Title`, in a season directory
+static LEADING_EPISODE: Lazy<Regex> =
+    Lazy::new(|| regex(r"(?i)^(\d{1,4})(?:v\d)?(?:\s*-\s*|\s+|$)"));
+/// The episodes after the first, in the third group of the episode patterns
+static EPISODE_TAIL: Lazy<Regex> = Lazy::new(|| regex(r"(?i)([-x])?(?:\d{1,2}x|e)?(\d{1,4})"));
+
+static PAREN_YEAR: Lazy<Regex> = Lazy::new(|| regex(r"\(((?:19|20)\d{2})\)"));
+static BARE_YEAR: Lazy<Regex> = Lazy::new(|| regex(r"\b((?:19|20)\d{2})\b"));
+static TRAILING_YEAR: Lazy<Regex> = Lazy::new(|| regex(r"\s*\(?\b(?:19|20)\d{2}\)?$"));
+
+static SEASON_DIR: Lazy<Regex> = Lazy::new(|| regex(r"(?i)^(?:season|series|s)\s*(\d{1,2})$"));
+static SPECIALS_DIR: Lazy<Regex> = Lazy::new(|| regex(r"(?i)^specials?$"));
+
+/// Everything after the first of these in a name is about the release, not the media
+static RELEASE_TAG: Lazy<Regex> = Lazy::new(|| {
+    regex(
+        r"(?i)\b(?:480p|576p|720p|1080p|2160p|4k|hdtv|web-?dl|web ?dl|webrip|blu-?ray|bdrip|brrip|dvdrip|x ?26[45]|h ?26[45]|hevc|xvid|aac(?:2 0)?|ac3|ddp?5 1|dts|proper|repack|remux|10bit|internal)\b",
+    )
+});
+static CHECKSUM: Lazy<Regex> = Lazy::new(|| regex(r"^[0-9A-Fa-f]{8}$"));
+static BRACKETS: Lazy<Regex> = Lazy::new(|| regex(r"\[[^\]]*\]"));
+static PARENS: Lazy<Regex> = Lazy::new(|| regex(r"\(([^)]*)\)"));
+
+/// What the path of a media file says it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedName {
+    Episode {
+        show: Option<String>,
+        /// `None` for absolute numbering, counted from the first episode of the show
+        season: Option<u32>,
+        /// Every episode in the file, more than one for multi-episode files
+        episodes: Vec<u32>,
+        /// The episode's title, or the whole name if it has none
+        title: String,
+    },
+    Movie {
+        title: String,
+        year: u32,
+    },
+    Unknown(String),
+}
+
+impl ParsedName {
+    /// Absolute numbers are kept without a season, they are not episodes of season 1
+    pub fn into_metadata(self) -> MediaMetadata {
+        match self {
+            ParsedName::Episode {
+                show,
+                season,
+                mut episodes,
+                title,
+            } => {
+                let episode = episodes.remove(0);
+                MediaMetadata::Episode(EpisodeMetadata {
+                    show,
+                    season,
+                    episode,
+                    more_episodes: episodes,
+                    title,
+                })
+            }
+            ParsedName::Movie { title, year } => MediaMetadata::Movie { title, year },
+            ParsedName::Unknown(name) => MediaMetadata::Unknown(name),
+        }
+    }
+}
+
 impl ScannedData {
     pub(crate) fn extract_metadata(self) -> ScannedMedia {
         let metadata = extract_metadata_from_path(self.path.as_path());
         ScannedMedia {
             path: self.path,
             subs: self.subs,
+            language_tracks: self.language_tracks,
             hash: self.hash,
             metadata,
         }
     }
 }
 
-fn extract_metadata_from_path(fname: &path::Path) -> MediaMetadata {
-    let file_name = fname.file_name().expect("path did not contain filename");
-    let title = file_name.to_string_lossy().to_string();
-    extract_metadata(&title)
+fn extract_metadata_from_path(path: &path::Path) -> MediaMetadata {
+    let parsed = parse_media_path(path);
+    log::debug!("{:?} is {:?}", path, parsed);
+    parsed.into_metadata()
 }
 
-/// Get rich-ish metadata from a media's title
-fn extract_metadata(title: &str) -> MediaMetadata {
-    match torrent_name_parser::Metadata::from(title) {
-        Ok(m) => {
-            if let Some((s, e)) = m.season().zip(m.episode()) {
-                MediaMetadata::Episode(EpisodeMetadata {
-                    season: s as u32,
-                    episode: e as u32,
-                    title: hacky_extract_episode_name(title),
-                })
-            } else {
-                MediaMetadata::Unknown(m.title().to_string())
-            }
+/// Read the show, season, episodes and title of a media file from its name,
+/// and from `Show (2004)/Season 01/` style directories above it.
+///
+/// Understands `Show.S01E02.Title`, `Show - 1x02 - Title`, `Season 1 Episode 2`,
+/// multi-episode files like `S01E01E02`, absolute numbering like `Show - 012`,
+/// and movies with a year, `Movie (2004)`.
+pub fn parse_media_path(path: &path::Path) -> ParsedName {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = normalize(&stem);
+    let (dir_show, dir_season) = directory_hints(path);
+    let whole_name = tidy(strip_release_tags(&name))
+        .map(str::to_string)
+        .unwrap_or(stem);
+
+    let found = match find_episode(&name, dir_season) {
+        Some(found) => found,
+        None => {
+            return match find_movie(&name) {
+                Some((title, year)) => ParsedName::Movie { title, year },
+                None => ParsedName::Unknown(whole_name),
+            };
+        }
+    };
+    let show = show_name(&name[..found.start]).or(dir_show);
+    let title = tidy(strip_release_tags(&name[found.end..]))
+        .map(str::to_string)
+        .unwrap_or(whole_name);
+    ParsedName::Episode {
+        show,
+        season: found.season.or(dir_season),
+        episodes: found.episodes,
+        title,
+    }
+}
+
+/// Where the episode numbers are in a name, and what they are
+struct EpisodeMatch {
+    start: usize,
+    end: usize,
+    season: Option<u32>,
+    episodes: Vec<u32>,
+}
+
+impl EpisodeMatch {
+    fn new(caps: &Captures, season: Option<u32>, episodes: Vec<u32>) -> EpisodeMatch {
+        let whole = caps.get(0).expect("regex match has group 0");
+        EpisodeMatch {
+            start: whole.start(),
+            end: whole.end(),
+            season,
+            episodes,
+        }
+    }
+}
+
+fn number(caps: &Captures, group: usize) -> Option<u32> {
+    caps.get(group)?.as_str().parse().ok()
+}
+
+/// The first episode, and the rest listed in `tail`, where a dash is a range
+fn episode_list(first: u32, tail: &str) -> Vec<u32> {
+    let mut episodes = vec![first];
+    for caps in EPISODE_TAIL.captures_iter(tail) {
+        let episode = match number(&caps, 2) {
+            Some(episode) => episode,
+            None => continue,
+        };
+        let last = *episodes.last().expect("episodes are never empty");
+        if episode <= last {
+            continue;
+        }
+        let range = matches!(caps.get(1), Some(sep) if sep.as_str() == "-");
+        if range && episode - last <= MAX_EPISODE_RANGE {
+            episodes.extend(last + 1..=episode);
+        } else {
+            episodes.push(episode);
+        }
+    }
+    episodes
+}
+
+fn find_episode(name: &str, dir_season: Option<u32>) -> Option<EpisodeMatch> {
+    for pattern in [&SEASON_EPISODE, &CROSS_EPISODE] {
+        if let Some(caps) = pattern.captures(name) {
+            let (season, first) = (number(&caps, 1)?, number(&caps, 2)?);
+            let tail = caps.get(3).map_or("", |m| m.as_str());
+            return Some(EpisodeMatch::new(
+                &caps,
+                Some(season),
+                episode_list(first, tail),
+            ));
+        }
+    }
+    if let Some(caps) = LONG_EPISODE.captures(name) {
+        let (season, episode) = (number(&caps, 1)?, number(&caps, 2)?);
+        return Some(EpisodeMatch::new(&caps, Some(season), vec![episode]));
+    }
+    if let Some(caps) = EPISODE_ONLY.captures(name) {
+        let first = number(&caps, 1)?;
+        let tail = caps.get(2).map_or("", |m| m.as_str());
+        return Some(EpisodeMatch::new(&caps, None, episode_list(first, tail)));
+    }
+    // a year is more likely than an episode number, `Show - 2004` is a movie
+    if find_movie(name).is_some() {
+        return None;
+    }
+    if let Some(caps) = DASH_EPISODE.captures(name) {
+        return Some(EpisodeMatch::new(&caps, None, vec![number(&caps, 1)?]));
+    }
+    // `12 Angry Men` is not an episode, but `12 - Title` or `12 Title` in a season is
+    let caps = LEADING_EPISODE.captures(name)?;
+    if dir_season.is_some() || caps[0].contains('-') {
+        return Some(EpisodeMatch::new(&caps, None, vec![number(&caps, 1)?]));
+    }
+    None
+}
+
+/// The title and year of a movie, the year in parentheses or the last one
+/// which has a title before it
+fn find_movie(name: &str) -> Option<(String, u32)> {
+    if let Some(caps) = PAREN_YEAR.captures(name) {
+        let start = caps.get(0).expect("regex match has group 0").start();
+        if let Some(title) = tidy(strip_release_tags(&name[..start])) {
+            return Some((title.to_string(), number(&caps, 1)?));
+        }
+    }
+    BARE_YEAR
+        .captures_iter(name)
+        .filter_map(|caps| {
+            let start = caps.get(0)?.start();
+            let title = tidy(strip_release_tags(&name[..start]))?;
+            Some((title.to_string(), number(&caps, 1)?))
+        })
+        .last()
+}
+
+/// The show and season of the directories a media file is in, when it is
+/// in a season directory such as `Season 01` or `Specials`
+fn directory_hints(path: &path::Path) -> (Option<String>, Option<u32>) {
+    let dir_name = |p: Option<&path::Path>| {
+        p.and_then(|p| p.file_name())
+            .map(|n| normalize(&n.to_string_lossy()))
+    };
+    let parent = path.parent();
+    let season_dir = match dir_name(parent) {
+        Some(season_dir) => season_dir,
+        None => return (None, None),
+    };
+    let season = if SPECIALS_DIR.is_match(&season_dir) {
+        0
+    } else {
+        match SEASON_DIR.captures(&season_dir).and_then(|c| number(&c, 1)) {
+            Some(season) => season,
+            None => return (None, None),
+        }
+    };
+    let show = dir_name(parent.and_then(|p| p.parent())).and_then(|show| show_name(&show));
+    (show, Some(season))
+}
+
+/// Remove `[group]` and `(1080p)` style tags, and turn the `.` and `_` used
+/// between words into spaces. A `.` before a space ends a word, `Mr. Robot`.
+fn normalize(name: &str) -> String {
+    let name = BRACKETS.replace_all(name, " ");
+    let name = PARENS.replace_all(&name, |caps: &Captures| {
+        let inner = caps[1].trim();
+        if RELEASE_TAG.is_match(inner) || CHECKSUM.is_match(inner) {
+            " ".to_string()
+        } else {
+            caps[0].to_string()
         }
-        Err(e) => {
-            log::warn!("could not parse metadata from `{:?}`: {}", title, e);
-            MediaMetadata::Unknown(title.to_string())
+    });
+    let mut spaced = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '_' => spaced.push(' '),
+            '.' if matches!(chars.peek(), Some(next) if !next.is_whitespace()) => spaced.push(' '),
+            c => spaced.push(c),
         }
     }
+    spaced.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-// TODO: this only happens to work because of my data
-fn hacky_extract_episode_name(filename: &str) -> String {
-    let segments = filename.split('.').collect::<Vec<_>>();
-    let r = segments[2..segments.len() - 1].join(".");
-    if r.is_empty() {
-        filename.to_string()
+/// A show's name without release tags or the year it started, `Show (2004)`
+fn show_name(part: &str) -> Option<String> {
+    let part = tidy(strip_release_tags(part))?;
+    tidy(&TRAILING_YEAR.replace(part, "")).map(str::to_string)
+}
+
+fn strip_release_tags(name: &str) -> &str {
+    match RELEASE_TAG.find(name) {
+        Some(tag) => &name[..tag.start()],
+        None => name,
+    }
+}
+
+/// Trim the separators around a part of a name, `None` if nothing is left
+fn tidy(part: &str) -> Option<&str> {
+    let part = part.trim_matches(|c: char| c.is_whitespace() || "-:,.".contains(c));
+    if part.is_empty() {
+        None
     } else {
-        r
+        Some(part)
     }
 }
 
@@ -58,17 +327,198 @@ fn hacky_extract_episode_name(filename: &str) -> String {
 mod tests {
     use super::*;
 
+    fn episode(
+        show: Option<&str>,
+        season: Option<u32>,
+        episodes: &[u32],
+        title: &str,
+    ) -> ParsedName {
+        ParsedName::Episode {
+            show: show.map(str::to_string),
+            season,
+            episodes: episodes.to_vec(),
+            title: title.to_string(),
+        }
+    }
+
     #[test]
-    fn extract_packed_show_path() {
-        let p = path::Path::new("./path/dir/Show Name.S03E12.Episode Title.mkv");
-        let metadata = extract_metadata_from_path(p);
-        assert_eq!(
-            metadata,
-            MediaMetadata::Episode(EpisodeMetadata {
-                season: 3,
-                episode: 12,
-                title: "Episode Title".to_owned(),
-            })
-        )
+    fn parse_media_paths() {
+        let cases = [
+            (
+                "./path/dir/Show Name.S03E12.Episode Title.mkv",
+                episode(Some("Show Name"), Some(3), &[12], "Episode Title"),
+            ),
+            (
+                "Arrested.Development.S01E02.Top.Banana.720p.WEB-DL.x264-GRP.mkv",
+                episode(Some("Arrested Development"), Some(1), &[2], "Top Banana"),
+            ),
+            (
+                "Arrested Development - 1x02 - Top Banana.mkv",
+                episode(Some("Arrested Development"), Some(1), &[2], "Top Banana"),
+            ),
+            (
+                "Arrested_Development_Season_1_Episode_2_Top_Banana.avi",
+                episode(Some("Arrested Development"), Some(1), &[2], "Top Banana"),
+            ),
+            (
+                "Arrested Development (2003)/Season 01/Arrested Development (2003) - S01E02 - Top Banana.mkv",
+                episode(Some("Arrested Development"), Some(1), &[2], "Top Banana"),
+            ),
+            (
+                "Arrested Development (2003)/Season 02/02 - The One Where They Build a House.mkv",
+                episode(
+                    Some("Arrested Development"),
+                    Some(2),
+                    &[2],
+                    "The One Where They Build a House",
+                ),
+            ),
+            (
+                "Show/Specials/E01 - Behind the Scenes.mkv",
+                episode(Some("Show"), Some(0), &[1], "Behind the Scenes"),
+            ),
+            (
+                "Show (2004)/Season 1/Show.S01E03.mkv",
+                episode(Some("Show"), Some(1), &[3], "Show S01E03"),
+            ),
+            (
+                "Mr. Robot S01E01.mkv",
+                episode(Some("Mr. Robot"), Some(1), &[1], "Mr. Robot S01E01"),
+            ),
+            // multi-episode files
+            (
+                "Show.S01E01E02.Pilot.mkv",
+                episode(Some("Show"), Some(1), &[1, 2], "Pilot"),
+            ),
+            (
+                "Show - S02E03-E05 - Finale.mkv",
+                episode(Some("Show"), Some(2), &[3, 4, 5], "Finale"),
+            ),
+            (
+                "Show 1x01-02 Double.mkv",
+                episode(Some("Show"), Some(1), &[1, 2], "Double"),
+            ),
+            // absolute numbering
+            (
+                "[SubsPlease] Some Anime - 012 (1080p) [ABCDEF12].mkv",
+                episode(Some("Some Anime"), None, &[12], "Some Anime - 012"),
+            ),
+            (
+                "One Piece - 1015 - The Supernova.mkv",
+                episode(Some("One Piece"), None, &[1015], "The Supernova"),
+            ),
+            (
+                "Show Ep 105.mp4",
+                episode(Some("Show"), None, &[105], "Show Ep 105"),
+            ),
+            (
+                "01 - Pilot.mkv",
+                episode(None, None, &[1], "Pilot"),
+            ),
+            // movies
+            (
+                "The Movie (2004).mkv",
+                ParsedName::Movie {
+                    title: "The Movie".to_string(),
+                    year: 2004,
+                },
+            ),
+            (
+                "Blade.Runner.2049.2017.1080p.BluRay.x264.mkv",
+                ParsedName::Movie {
+                    title: "Blade Runner 2049".to_string(),
+                    year: 2017,
+                },
+            ),
+            (
+                "2001 A Space Odyssey (1968).mkv",
+                ParsedName::Movie {
+                    title: "2001 A Space Odyssey".to_string(),
+                    year: 1968,
+                },
+            ),
+            // nothing to go on
+            (
+                "12 Angry Men.mkv",
+                ParsedName::Unknown("12 Angry Men".to_string()),
+            ),
+            (
+                "home_video_1920x1080.mkv",
+                ParsedName::Unknown("home video 1920x1080".to_string()),
+            ),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                parse_media_path(path::Path::new(path)),
+                expected,
+                "parsing {:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn media_metadata_from_parsed_names() {
+        let cases = [
+            (
+                "./path/dir/Show Name.S03E12.Episode Title.mkv",
+                MediaMetadata::Episode(EpisodeMetadata {
+                    show: Some("Show Name".to_owned()),
+                    season: Some(3),
+                    episode: 12,
+                    more_episodes: vec![],
+                    title: "Episode Title".to_owned(),
+                }),
+            ),
+            (
+                "Show - S02E03-E05 - Finale.mkv",
+                MediaMetadata::Episode(EpisodeMetadata {
+                    show: Some("Show".to_owned()),
+                    season: Some(2),
+                    episode: 3,
+                    more_episodes: vec![4, 5],
+                    title: "Finale".to_owned(),
+                }),
+            ),
+            (
+                "One Piece - 1015 - The Supernova.mkv",
+                MediaMetadata::Episode(EpisodeMetadata {
+                    show: Some("One Piece".to_owned()),
+                    season: None,
+                    episode: 1015,
+                    more_episodes: vec![],
+                    title: "The Supernova".to_owned(),
+                }),
+            ),
+            (
+                "01 - Pilot.mkv",
+                MediaMetadata::Episode(EpisodeMetadata {
+                    show: None,
+                    season: None,
+                    episode: 1,
+                    more_episodes: vec![],
+                    title: "Pilot".to_owned(),
+                }),
+            ),
+            (
+                "The.Movie.2004.720p.mkv",
+                MediaMetadata::Movie {
+                    title: "The Movie".to_owned(),
+                    year: 2004,
+                },
+            ),
+            (
+                "12 Angry Men.mkv",
+                MediaMetadata::Unknown("12 Angry Men".to_owned()),
+            ),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                extract_metadata_from_path(path::Path::new(path)),
+                expected,
+                "parsing {:?}",
+                path
+            );
+        }
     }
 }
@@ -1,7 +1,9 @@
 use database::Database;
 use lucille_core::{
+    language::Language,
     metadata::{MediaHash, MediaMetadata},
-    Corpus,
+    subtitle_format::SubtitleParseError,
+    Corpus, Subtitle,
 };
 
 mod extract;
@@ -9,32 +11,21 @@ mod insert;
 mod metadata;
 mod scan;
 
+#[derive(PartialEq)]
 pub enum ScannedSubtitles {
     NotFound,
-    Error(subrip::Error),
+    /// The media has no sidecar, and was given subtitles by an earlier scan.
+    /// Its embedded subtitles are not extracted again.
+    Known,
+    Error(SubtitleParseError),
     Subtitles(Vec<subrip::Subtitle>),
 }
 
-impl PartialEq for ScannedSubtitles {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Error(l0), Self::Error(r0)) => match (l0, r0) {
-                (
-                    subrip::Error::ParseError(lsubs, lerr),
-                    subrip::Error::ParseError(rsubs, rerr),
-                ) => lsubs == rsubs && lerr == rerr,
-                _ => false,
-            },
-            (Self::Subtitles(l0), Self::Subtitles(r0)) => l0 == r0,
-            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
-        }
-    }
-}
-
 impl std::fmt::Debug for ScannedSubtitles {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NotFound => write!(f, "NotFound"),
+            Self::Known => write!(f, "Known"),
             Self::Error(arg0) => f.debug_tuple("Error").field(arg0).finish(),
             Self::Subtitles(arg0) => f.debug_tuple("Subtitles").field(&arg0.len()).finish(),
         }
@@ -45,13 +36,17 @@ impl std::fmt::Debug for ScannedSubtitles {
 pub(crate) struct ScannedData {
     pub path: std::path::PathBuf,
     pub subs: ScannedSubtitles,
+    pub language_tracks: Vec<(Language, Vec<Subtitle>)>,
     pub hash: MediaHash,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ScannedMedia {
     pub path: std::path::PathBuf,
+    /// Subtitles in the language of the corpus
     pub subs: ScannedSubtitles,
+    /// Subtitles from sidecars tagged with their language, such as `episode.es.srt`
+    pub language_tracks: Vec<(Language, Vec<Subtitle>)>,
     pub hash: MediaHash,
     pub metadata: MediaMetadata,
 }
@@ -61,6 +56,10 @@ impl LucilleApp {
         MediaProcessor {
             db: self.db.clone(),
             trust_hashes,
+            media_extensions: self.config.media_extensions(),
+            sidecar_priority: self.config.subtitle_sidecar_priority(),
+            ffmpeg: self.config.ffmpeg(),
+            subtitle_language_priority: self.config.subtitle_language_priority(),
         }
     }
 }
@@ -69,12 +68,22 @@ impl LucilleApp {
 pub struct MediaProcessor {
     pub db: Database,
     pub trust_hashes: bool,
+    /// Extensions of the media files to ingest
+    pub media_extensions: Vec<String>,
+    /// Subtitle file extensions to look for next to media, the first one found is used
+    pub sidecar_priority: Vec<String>,
+    /// Extracts embedded subtitles from media without a sidecar
+    pub ffmpeg: FFMpegBinary,
+    /// Languages of embedded subtitles to extract, most preferred first, the
+    /// corpus' language when empty
+    pub subtitle_language_priority: Vec<String>,
 }
 
 pub use insert::add_content_to_corpus;
-pub use scan::scan_media_paths;
+pub use metadata::{parse_media_path, ParsedName};
+pub use scan::{scan_media_paths, DEFAULT_MEDIA_EXTENSIONS};
 
-use crate::app::LucilleApp;
+use crate::{app::LucilleApp, ffmpeg::FFMpegBinary};
 
 impl MediaProcessor {
     pub async fn ingest<P: AsRef<std::path::Path>>(
@@ -82,15 +91,30 @@ impl MediaProcessor {
         root: P,
         corpus: Option<&Corpus>,
     ) -> anyhow::Result<()> {
-        let content = self.scan_and_process(root).await?;
+        let content = self
+            .for_corpus(corpus)
+            .await?
+            .scan_and_process(root)
+            .await?;
 
         add_content_to_corpus(&self.db, corpus, content).await
     }
+    /// Without a configured subtitle language priority, extract embedded
+    /// subtitles in the corpus' language
+    async fn for_corpus(&self, corpus: Option<&Corpus>) -> anyhow::Result<MediaProcessor> {
+        let mut processor = self.clone();
+        let corpus_id = corpus.and_then(|c| c.id);
+        if let Some(corpus_id) = corpus_id.filter(|_| self.subtitle_language_priority.is_empty()) {
+            let language = self.db.get_corpus_language(corpus_id).await?;
+            processor.subtitle_language_priority = vec![language.code().to_string()];
+        }
+        Ok(processor)
+    }
     async fn scan_and_process<P: AsRef<std::path::Path>>(
         &self,
         root: P,
     ) -> anyhow::Result<Vec<ScannedMedia>> {
-        let media_paths = scan::scan_media_paths(root)?;
+        let media_paths = scan::scan_media_paths(root, &self.media_extensions)?;
         Ok(self.process_all_media(&media_paths).await)
     }
     pub async fn process_all_media(&self, paths: &[std::path::PathBuf]) -> Vec<ScannedMedia> {
@@ -120,7 +144,7 @@ impl MediaProcessor {
         &self,
         media_path: &std::path::Path,
     ) -> anyhow::Result<ScannedMedia> {
-        extract::read_media_from_path(&self.db, media_path, self.trust_hashes)
+        extract::read_media_from_path(self, media_path)
             .await
             .map(|data| data.extract_metadata())
     }
@@ -176,11 +200,15 @@ mod tests {
                 let expected = ScannedMedia {
                     path: video_path.clone(),
                     subs: ScannedSubtitles::Subtitles(srt_data),
+                    language_tracks: vec![],
                     hash,
                     metadata: MediaMetadata::Episode(lucille_core::metadata::EpisodeMetadata {
-                        season: s,
-                        episode: e,
-                        title: ep_title,
+                        show: Some(show_name.to_string()),
+                        ..lucille_core::metadata::EpisodeMetadata::new(
+                            s,
+                            e,
+                            ep_title.replace('_', " "),
+                        )
                     }),
                 };
                 media.insert(video_path, expected);
@@ -280,4 +308,190 @@ mod tests {
             assert_eq!(m.hash, garbage_hash)
         }
     }
+
+    #[tokio::test]
+    async fn extract_the_corpus_language_by_default() {
+        let test_app = lucille_test_app().await;
+        let db = &test_app.app.db;
+        let corpus = db.add_corpus("show").await.unwrap();
+        db.set_corpus_language(corpus.id.unwrap(), Language::Spanish)
+            .await
+            .unwrap();
+        let scanner = test_app.app.media_scanner(false);
+        assert!(scanner.subtitle_language_priority.is_empty());
+
+        let processor = scanner.for_corpus(Some(&corpus)).await.unwrap();
+        assert_eq!(processor.subtitle_language_priority, vec!["es"]);
+        let processor = scanner.for_corpus(None).await.unwrap();
+        assert!(processor.subtitle_language_priority.is_empty());
+
+        let configured = MediaProcessor {
+            subtitle_language_priority: vec!["fre".to_string()],
+            ..scanner
+        };
+        let processor = configured.for_corpus(Some(&corpus)).await.unwrap();
+        assert_eq!(processor.subtitle_language_priority, vec!["fre"]);
+    }
+
+    #[test]
+    fn extract_sidecar_by_priority() {
+        let root = tempfile::tempdir().unwrap();
+        let media_path = root.path().join("episode.mkv");
+        std::fs::write(
+            root.path().join("episode.vtt"),
+            "WEBVTT\n\n00:01.000 --> 00:02.000\n<i>no touching</i>\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.path().join("episode.ass"),
+            "[Events]\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\b1}there's always money\n",
+        )
+        .unwrap();
+        let text = |subs: ScannedSubtitles| match subs {
+            ScannedSubtitles::Subtitles(subs) => subs[0].text.clone(),
+            other => panic!("expected subtitles, found {:?}", other),
+        };
+        let priority = |exts: &[&str]| exts.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        let default_priority = priority(lucille_core::subtitle_format::DEFAULT_SIDECAR_PRIORITY);
+        let subs = extract::extract_subtitles(&media_path, &default_priority).unwrap();
+        assert_eq!(text(subs), "no touching\n");
+
+        let subs =
+            extract::extract_subtitles(&media_path, &priority(&["ssa", "ass", "vtt"])).unwrap();
+        assert_eq!(text(subs), "there's always money\n");
+
+        let subs = extract::extract_subtitles(&media_path, &priority(&["srt"])).unwrap();
+        assert_eq!(subs, ScannedSubtitles::NotFound);
+
+        std::fs::write(root.path().join("episode.srt"), "not\nan srt").unwrap();
+        let subs = extract::extract_subtitles(&media_path, &default_priority).unwrap();
+        assert!(matches!(subs, ScannedSubtitles::Error(_)));
+    }
+
+    #[test]
+    fn extract_language_tagged_sidecars() {
+        let root = tempfile::tempdir().unwrap();
+        let media_path = root.path().join("show.S01E01.mkv");
+        let write_srt = |name: &str, line: &str| {
+            let srt = generate_subtitle(&[line])
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<String>();
+            std::fs::write(root.path().join(name), srt).unwrap();
+        };
+        write_srt("show.S01E01.srt", "no touching");
+        write_srt("show.S01E01.es.srt", "no tocar");
+        write_srt("show.S01E01.en.srt", "no touching!");
+        write_srt("show.S01E01.forced.srt", "signs");
+        write_srt("show.S01E02.fr.srt", "another episode");
+        std::fs::write(
+            root.path().join("show.S01E01.spa.vtt"),
+            "WEBVTT\n\n00:01.000 --> 00:02.000\nsiempre hay dinero\n",
+        )
+        .unwrap();
+
+        let default_priority = lucille_core::subtitle_format::DEFAULT_SIDECAR_PRIORITY
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        let tracks = extract::extract_language_sidecars(&media_path, &default_priority)
+            .unwrap()
+            .into_iter()
+            .map(|(language, subs)| (language, subs[0].text.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tracks,
+            vec![
+                (Language::English, "no touching!\n".to_string()),
+                (Language::Spanish, "no tocar\n".to_string()),
+            ]
+        );
+
+        let vtt_first = ["vtt".to_string(), "srt".to_string()];
+        let tracks = extract::extract_language_sidecars(&media_path, &vtt_first).unwrap();
+        assert_eq!(tracks[1].0, Language::Spanish);
+        assert_eq!(tracks[1].1[0].text, "siempre hay dinero\n");
+    }
+
+    #[tokio::test]
+    async fn rescan_keeps_untagged_track() {
+        let test_app = lucille_test_app().await;
+        let root = tempfile::tempdir().unwrap();
+        let media_path = root.path().join("show.S01E01.mkv");
+        std::fs::write(&media_path, b"episode").unwrap();
+        let write_srt = |name: &str, line: &str| {
+            let srt = generate_subtitle(&[line])
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<String>();
+            std::fs::write(root.path().join(name), srt).unwrap();
+        };
+        write_srt("show.S01E01.srt", "no touching");
+        write_srt("show.S01E01.en.srt", "no touching!");
+        write_srt("show.S01E01.es.srt", "no tocar");
+
+        let db = &test_app.app.db;
+        let corpus = db.add_corpus("show").await.unwrap();
+        let scanner = test_app.app.media_scanner(false);
+        for _ in 0..2 {
+            let media = scanner
+                .process_all_media(std::slice::from_ref(&media_path))
+                .await;
+            add_content_to_corpus(db, Some(&corpus), media)
+                .await
+                .unwrap();
+        }
+
+        let chapter = db
+            .get_chapter_by_hash(MediaHash::from_bytes(b"episode"))
+            .await
+            .unwrap()
+            .unwrap();
+        let revisions = db.get_subtitle_revisions(chapter.id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        let english = db
+            .lookup_latest_sub_for_chapter_language(chapter.id, Language::English)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(english.subs[0].text, "no touching\n");
+    }
+
+    #[tokio::test]
+    async fn skip_embedded_subtitles_of_known_media() {
+        let test_app = lucille_test_app().await;
+        let root = tempfile::tempdir().unwrap();
+        let media_path = root.path().join("show.S01E01.mkv");
+        std::fs::write(&media_path, b"episode").unwrap();
+
+        let mut scanner = test_app.app.media_scanner(false);
+        // probing would fail, and find nothing
+        scanner.ffmpeg = FFMpegBinary::new(root.path().join("no-ffmpeg"));
+        let media = scanner
+            .process_all_media(std::slice::from_ref(&media_path))
+            .await;
+        assert_eq!(media[0].subs, ScannedSubtitles::NotFound);
+
+        let corpus = test_app.app.db.add_corpus("show").await.unwrap();
+        add_content_to_corpus(&test_app.app.db, Some(&corpus), media)
+            .await
+            .unwrap();
+        let chapter = test_app
+            .app
+            .db
+            .get_chapter_by_hash(MediaHash::from_bytes(b"episode"))
+            .await
+            .unwrap()
+            .unwrap();
+        test_app
+            .app
+            .db
+            .add_subtitles(chapter.id, &generate_subtitle(&["no touching"]))
+            .await
+            .unwrap();
+
+        let media = scanner.process_all_media(&[media_path]).await;
+        assert_eq!(media[0].subs, ScannedSubtitles::Known);
+    }
 }
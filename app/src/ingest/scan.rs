@@ -1,14 +1,21 @@
 use std::path;
-const MEDIA_FILES: &[&str] = &["mkv"];
 
-/// Get the list of paths
-pub fn scan_media_paths<P: AsRef<path::Path>>(root: P) -> std::io::Result<Vec<path::PathBuf>> {
+/// Media file extensions which are ingested unless configured otherwise,
+/// see [`crate::app::LucilleConfig::media_extensions`]
+pub const DEFAULT_MEDIA_EXTENSIONS: &[&str] = &["mkv", "mp4", "m4v", "avi", "webm"];
+
+/// Get the list of paths of media with one of `extensions`
+pub fn scan_media_paths<P: AsRef<path::Path>>(
+    root: P,
+    extensions: &[String],
+) -> std::io::Result<Vec<path::PathBuf>> {
     let root = root.as_ref();
     let mut content = Vec::new();
-    for dir in walkdir::WalkDir::new(root)
-        .into_iter()
-        .filter(|de| de.as_ref().map(|de| is_media(de.path())).unwrap_or(true))
-    {
+    for dir in walkdir::WalkDir::new(root).into_iter().filter(|de| {
+        de.as_ref()
+            .map(|de| is_media(de.path(), extensions))
+            .unwrap_or(true)
+    }) {
         let dir = dir?;
         log::trace!("scanned: {:?}", dir.path());
         content.push(dir.path().to_owned());
@@ -17,10 +24,10 @@ pub fn scan_media_paths<P: AsRef<path::Path>>(root: P) -> std::io::Result<Vec<pa
     Ok(content)
 }
 /// is a path media we care about?
-fn is_media(p: &path::Path) -> bool {
+fn is_media(p: &path::Path, extensions: &[String]) -> bool {
     let oext = p.extension();
     oext.and_then(|ext| ext.to_str())
-        .map(|ext| MEDIA_FILES.contains(&ext))
+        .map(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
         .unwrap_or(false)
 }
 
@@ -28,18 +35,46 @@ fn is_media(p: &path::Path) -> bool {
 mod tests {
     use super::*;
 
+    fn default_extensions() -> Vec<String> {
+        DEFAULT_MEDIA_EXTENSIONS
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
     #[test]
     fn check_srt_is_not_media() {
-        assert!(!is_media(path::Path::new("my/file.srt")))
+        assert!(!is_media(
+            path::Path::new("my/file.srt"),
+            &default_extensions()
+        ))
     }
 
     #[test]
     fn check_mkv_is_media() {
-        assert!(is_media(path::Path::new("my/file.mkv")))
+        assert!(is_media(
+            path::Path::new("my/file.mkv"),
+            &default_extensions()
+        ))
+    }
+
+    #[test]
+    fn check_other_containers_are_media() {
+        for file in ["my/file.mp4", "my/file.M4V", "my/file.avi", "my/file.webm"] {
+            assert!(
+                is_media(path::Path::new(file), &default_extensions()),
+                "{}",
+                file
+            );
+        }
+        assert!(!is_media(
+            path::Path::new("my/file.mp4"),
+            &["mkv".to_string()]
+        ));
     }
 
     #[test]
     fn check_no_extension_is_not_media() {
-        assert!(!is_media(path::Path::new("my/file")))
+        assert!(!is_media(path::Path::new("my/file"), &default_extensions()))
     }
 }
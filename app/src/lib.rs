@@ -1,8 +1,11 @@
 #![allow(clippy::uninlined_format_args)]
+use std::collections::HashSet;
+
 use anyhow::Context;
 use lucille_core::{
     export::{CorpusExport, MediaExport, ViewOptions},
     identifiers::CorpusId,
+    language::Language,
     metadata::MediaHash,
     uuid::Uuid,
     ContentData,
@@ -14,7 +17,9 @@ pub mod app;
 pub mod encryption;
 pub mod ffmpeg;
 pub mod hashfs;
+pub mod index_gc;
 pub mod ingest;
+pub mod lookup;
 pub mod media_view;
 pub mod prepare;
 pub mod search_manager;
@@ -52,7 +57,11 @@ pub async fn import_corpus_packet(
     app: &LucilleApp,
     packet: &CorpusExport,
 ) -> anyhow::Result<CorpusId> {
-    let CorpusExport { title, content } = packet;
+    let CorpusExport {
+        title,
+        content,
+        language,
+    } = packet;
 
     let corpus = app
         .db
@@ -60,6 +69,9 @@ pub async fn import_corpus_packet(
         .await
         .context("creating corpus")?;
     let corpus_id = corpus.id.unwrap();
+    if let Some(language) = language {
+        app.db.set_corpus_language(corpus_id, *language).await?;
+    }
 
     for chapter in content {
         let MediaExport {
@@ -70,25 +82,21 @@ pub async fn import_corpus_packet(
                     hash,
                     subtitle,
                 },
+            language,
         } = chapter;
 
-        let (title, season, episode) = match &metadata {
-            lucille_core::metadata::MediaMetadata::Episode(e) => (
-                e.title.as_str(),
-                Some(e.season as i64),
-                Some(e.episode as i64),
-            ),
-            lucille_core::metadata::MediaMetadata::Unknown(u) => (u.as_str(), None, None),
-        };
         let chapter_id = app
             .db
-            .define_chapter(corpus_id, title, season, episode, *hash)
+            .define_media_chapter(corpus_id, metadata, *hash)
             .await?;
         app.db
-            .import_subtitles(chapter_id, subtitle.uuid, &subtitle.subs)
+            .import_subtitles(chapter_id, *language, subtitle.uuid, &subtitle.subs)
             .await?;
+        // every track of the chapter lists its views
         for name in views {
-            app.db.add_media_view(chapter_id, name).await?;
+            if app.db.lookup_media_view(chapter_id, name).await?.is_none() {
+                app.db.add_media_view(chapter_id, name).await?;
+            }
         }
     }
 
@@ -100,56 +108,340 @@ pub async fn export_corpus_packet(
     corpus_id: CorpusId,
 ) -> anyhow::Result<CorpusExport> {
     let title = app.db.get_corpus(corpus_id).await?.title;
-    let (_, content) = app.db.get_all_subs_for_corpus(corpus_id).await?;
-    //
-    let mut export = Vec::with_capacity(content.len());
+    let mut export = Vec::new();
 
-    for c in content {
-        let views = app
+    for language in app.db.get_subtitle_languages_for_corpus(corpus_id).await? {
+        let (_, content) = app
             .db
-            .get_media_views_for_srt(c.subtitle.uuid)
-            .await?
-            .into_iter()
-            .map(|view| view.name)
-            .collect();
-        export.push(MediaExport {
-            views: ViewOptions { views },
-            data: c,
-        });
+            .get_all_subs_for_corpus_language(corpus_id, language)
+            .await?;
+        for c in content {
+            let views = app
+                .db
+                .get_media_views_for_srt(c.subtitle.uuid)
+                .await?
+                .into_iter()
+                .map(|view| view.name)
+                .collect();
+            export.push(MediaExport {
+                views: ViewOptions { views },
+                data: c,
+                language: Some(language),
+            });
+        }
     }
 
     Ok(CorpusExport {
         title,
         content: export,
+        language: Some(app.db.get_corpus_language(corpus_id).await?),
     })
 }
 
+async fn current_subs_for_corpus(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    language: Language,
+) -> anyhow::Result<(HashSet<i64>, Vec<ContentData>)> {
+    let (srts, all_subs) = app
+        .db
+        .get_all_subs_for_corpus_language(corpus_id, language)
+        .await?;
+    log::trace!("ALL SUBS: {:#?}", all_subs);
+    Ok((srts, all_subs))
+}
+
+/// Index the subtitles of a corpus in its own language
 pub async fn index_subtitles(
     app: &LucilleApp,
     corpus_id: CorpusId,
     max_window: Option<usize>,
 ) -> anyhow::Result<search::SearchIndex> {
-    log::info!("performing index for {}", corpus_id);
+    let language = app.db.get_corpus_language(corpus_id).await?;
+    index_subtitles_for_language(app, corpus_id, language, max_window).await
+}
 
-    let (srts, all_subs) = app.db.get_all_subs_for_corpus(corpus_id).await?;
+/// Index the subtitle track of a corpus in `language`, every language of a
+/// corpus has its own search index
+pub async fn index_subtitles_for_language(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    language: Language,
+    max_window: Option<usize>,
+) -> anyhow::Result<search::SearchIndex> {
+    log::info!("performing index for {} in {}", corpus_id, language);
 
-    log::trace!("ALL SUBS: {:#?}", all_subs);
+    let (srts, all_subs) = current_subs_for_corpus(app, corpus_id, language).await?;
+    let max_window = max_window.unwrap_or(DEFAULT_INDEX_WINDOW_SIZE);
 
     let index_uuid = Uuid::generate();
     let index_path = app.config.index_root().join(index_uuid.to_string());
     std::fs::create_dir_all(&index_path)?;
-    let index = search::build_index(
+    let cleanup = app.config.subtitle_index_cleanup();
+    let index = match search::build_index(
         index_uuid,
         &index_path,
-        all_subs.into_iter(),
-        max_window.unwrap_or(DEFAULT_INDEX_WINDOW_SIZE),
-    )?;
+        all_subs
+            .into_iter()
+            .map(|c| search::IndexableEpisode::new(Some(corpus_id), c, &cleanup)),
+        max_window,
+        language,
+        &cleanup,
+    ) {
+        Ok(index) => index,
+        Err(e) => {
+            discard_index_dir(&index_path);
+            return Err(e.into());
+        }
+    };
 
-    app.db.assoc_index_with_srts(index_uuid, srts).await?;
+    if let Err(e) = app
+        .db
+        .assoc_index_with_srts(
+            index_uuid,
+            srts,
+            &search::index_info(max_window, language, &cleanup),
+        )
+        .await
+    {
+        drop(index);
+        discard_index_dir(&index_path);
+        return Err(e.into());
+    }
 
     Ok(index)
 }
 
+/// Remove the directory of a search index which the database does not know about
+fn discard_index_dir(index_path: &std::path::Path) {
+    if let Err(e) = std::fs::remove_dir_all(index_path) {
+        log::warn!(
+            "could not remove unrecorded search index {:?}: {}",
+            index_path,
+            e
+        );
+    }
+}
+
+/// Index the subtitles of a corpus without writing anything, neither to
+/// the index root nor the database.
+///
+/// The index is lost when dropped unless saved with [`search::SearchIndex::to_bundle`].
+pub async fn index_subtitles_in_memory(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    max_window: Option<usize>,
+) -> anyhow::Result<search::SearchIndex> {
+    let language = app.db.get_corpus_language(corpus_id).await?;
+    let (_, all_subs) = current_subs_for_corpus(app, corpus_id, language).await?;
+    let max_window = max_window.unwrap_or(DEFAULT_INDEX_WINDOW_SIZE);
+
+    let cleanup = app.config.subtitle_index_cleanup();
+    let index = search::build_index_in_ram(
+        Uuid::generate(),
+        all_subs
+            .into_iter()
+            .map(|c| search::IndexableEpisode::new(Some(corpus_id), c, &cleanup)),
+        max_window,
+        language,
+        &cleanup,
+    )?;
+    Ok(index)
+}
+
+/// Build a replacement for a search index which can no longer be used,
+/// covering the same corpus and subtitle language with the same `max_window`.
+pub async fn rebuild_index(
+    app: &LucilleApp,
+    index_uuid: Uuid,
+) -> anyhow::Result<search::SearchIndex> {
+    let corpus = app
+        .db
+        .get_corpus_for_search_index(index_uuid)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("search index {} does not cover a corpus", index_uuid))?;
+    let corpus_id = corpus.id.expect("corpus from db has id");
+    let max_window = app
+        .db
+        .get_search_index_info(index_uuid)
+        .await?
+        .map(|info| info.max_window);
+    let language = match app.db.get_search_index_language(index_uuid).await? {
+        Some(language) => language,
+        None => app.db.get_corpus_language(corpus_id).await?,
+    };
+    log::info!(
+        "rebuilding search index {} for {:?} in {}",
+        index_uuid,
+        corpus.title,
+        language
+    );
+    index_subtitles_for_language(app, corpus_id, language, max_window).await
+}
+
+#[derive(Debug)]
+pub struct IndexUpdate {
+    pub index: search::SearchIndex,
+    /// srt ids which were newly indexed
+    pub added: HashSet<i64>,
+    /// srt ids which were superseded and removed from the index
+    pub removed: HashSet<i64>,
+    /// A new index was built, instead of updating the existing one
+    pub created: bool,
+}
+
+/// Bring the most recent search index for a corpus' own language up to date,
+/// see [`update_index_for_language`]
+pub async fn update_index(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    max_window: Option<usize>,
+) -> anyhow::Result<IndexUpdate> {
+    let language = app.db.get_corpus_language(corpus_id).await?;
+    update_index_for_language(app, corpus_id, language, max_window).await
+}
+
+/// Bring the most recent search index for the subtitles of a corpus in
+/// `language` up to date, without rebuilding it from scratch.
+///
+/// If they have never been indexed, or the existing index is out of date
+/// or was built with a different tokenizer, a new index is built. Clips are
+/// put together from lines when searching, so a different `max_window` is
+/// only recorded.
+pub async fn update_index_for_language(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    language: Language,
+    max_window: Option<usize>,
+) -> anyhow::Result<IndexUpdate> {
+    let index_uuid = match app
+        .db
+        .get_latest_search_index_for_corpus_language(corpus_id, language)
+        .await?
+    {
+        Some(uuid) => uuid,
+        None => {
+            log::info!(
+                "corpus {} has no search index in {}, creating one",
+                corpus_id,
+                language
+            );
+            return create_index(app, corpus_id, language, max_window).await;
+        }
+    };
+
+    let index_path = app.config.index_root().join(index_uuid.to_string());
+    let index = search::SearchIndex::open_in_dir(index_uuid, &index_path)
+        .with_context(|| format!("could not open search index {}", index_uuid))?;
+
+    let info = index.info()?;
+    if let Some(reason) = search::outdated_reason(info.as_ref()) {
+        log::warn!("search index {} must be rebuilt: {}", index_uuid, reason);
+        return create_index(app, corpus_id, language, max_window).await;
+    }
+    let info = info.expect("current index has info");
+    if info.tokenizer != search::tokenizer_name(language) {
+        log::info!(
+            "search index {} uses tokenizer {}, rebuilding for language {}",
+            index_uuid,
+            info.tokenizer,
+            language
+        );
+        return create_index(
+            app,
+            corpus_id,
+            language,
+            Some(max_window.unwrap_or(info.max_window)),
+        )
+        .await;
+    }
+    let cleanup = app.config.subtitle_index_cleanup();
+    if info.cleanup != cleanup.rules() {
+        log::info!(
+            "search index {} was cleaned with {:?}, rebuilding with {:?}",
+            index_uuid,
+            info.cleanup,
+            cleanup.rules()
+        );
+        return create_index(
+            app,
+            corpus_id,
+            language,
+            Some(max_window.unwrap_or(info.max_window)),
+        )
+        .await;
+    }
+    let max_window = max_window.unwrap_or(info.max_window);
+    if max_window != info.max_window {
+        log::info!(
+            "search index {} has max_window {}, changing it to {}",
+            index_uuid,
+            info.max_window,
+            max_window
+        );
+    }
+    log::info!("updating index {} for {}", index_uuid, corpus_id);
+
+    let (srts, all_subs) = current_subs_for_corpus(app, corpus_id, language).await?;
+    let indexed = app.db.get_srts_for_search_index(index_uuid).await?;
+
+    let added = srts.difference(&indexed).copied().collect::<HashSet<_>>();
+    let removed = indexed.difference(&srts).copied().collect::<HashSet<_>>();
+
+    if added.is_empty() && removed.is_empty() && max_window == info.max_window {
+        log::info!("search index {} is already up to date", index_uuid);
+    } else {
+        let removed_ids = removed.iter().copied().collect::<Vec<_>>();
+        let update = index
+            .prepare_update(
+                all_subs
+                    .into_iter()
+                    .filter(|c| added.contains(&c.subtitle.id))
+                    .map(|c| search::IndexableEpisode::new(Some(corpus_id), c, &cleanup)),
+                removed_ids.as_slice(),
+                max_window,
+            )
+            .with_context(|| format!("could not update search index {}", index_uuid))?;
+        // the database only records the update once the index has it
+        app.db
+            .update_index_assoc(
+                index_uuid,
+                &added,
+                &removed,
+                &search::index_info(max_window, language, &cleanup),
+                || {
+                    update
+                        .commit()
+                        .with_context(|| format!("could not update search index {}", index_uuid))
+                },
+            )
+            .await?;
+    }
+
+    Ok(IndexUpdate {
+        index,
+        added,
+        removed,
+        created: false,
+    })
+}
+
+async fn create_index(
+    app: &LucilleApp,
+    corpus_id: CorpusId,
+    language: Language,
+    max_window: Option<usize>,
+) -> anyhow::Result<IndexUpdate> {
+    let index = index_subtitles_for_language(app, corpus_id, language, max_window).await?;
+    let added = app.db.get_srts_for_search_index(index.uuid()).await?;
+    Ok(IndexUpdate {
+        index,
+        added,
+        removed: HashSet::new(),
+        created: true,
+    })
+}
+
 // pub fn guess_content_name(content: &[ScannedMedia]) -> String {
 //     let mut content_name_guesser = HashMap::new();
 //     for (path, e) in media {
@@ -176,3 +468,439 @@ pub async fn index_subtitles(
 //         content,
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use lucille_core::{
+        clean_sub::{CleanupRule, SubtitleCleaner},
+        language::Language,
+        test_util::generate_subtitle,
+    };
+
+    use super::*;
+    use crate::app::tests::lucille_test_app;
+
+    async fn corpus_with_episode(app: &LucilleApp) -> CorpusId {
+        let corpus_id = app.db.add_corpus("show").await.unwrap().id.unwrap();
+        let chapter_id = app
+            .db
+            .define_chapter(
+                corpus_id,
+                "episode",
+                Some(1),
+                Some(1),
+                MediaHash::from_bytes(b"episode"),
+            )
+            .await
+            .unwrap();
+        app.db
+            .add_subtitles(
+                chapter_id,
+                &generate_subtitle(&["no", "touching", "in", "the", "banana", "stand"]),
+            )
+            .await
+            .unwrap();
+        corpus_id
+    }
+
+    #[tokio::test]
+    async fn export_and_import_every_language() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+        db.set_corpus_language(corpus_id, Language::Spanish)
+            .await
+            .unwrap();
+        let chapter_id = db
+            .get_chapter_by_hash(MediaHash::from_bytes(b"episode"))
+            .await
+            .unwrap()
+            .unwrap()
+            .id;
+        db.add_media_view(chapter_id, "original").await.unwrap();
+        let spanish = generate_subtitle(&["nada", "de", "tocar"]);
+        db.add_subtitle_track(chapter_id, Some(Language::Spanish), &spanish)
+            .await
+            .unwrap();
+
+        let packet = export_corpus_packet(&tapp.app, corpus_id).await.unwrap();
+        assert_eq!(packet.language, Some(Language::Spanish));
+        assert_eq!(packet.content.len(), 2);
+        let packet: CorpusExport =
+            serde_json::from_str(&serde_json::to_string(&packet).unwrap()).unwrap();
+
+        let other = lucille_test_app().await;
+        let imported = import_corpus_packet(&other.app, &packet).await.unwrap();
+        let db = &other.app.db;
+        assert_eq!(
+            db.get_corpus_language(imported).await.unwrap(),
+            Language::Spanish
+        );
+        assert_eq!(
+            db.get_subtitle_languages_for_corpus(imported)
+                .await
+                .unwrap(),
+            vec![Language::English, Language::Spanish]
+        );
+        let (_, content) = db
+            .get_all_subs_for_corpus_language(imported, Language::Spanish)
+            .await
+            .unwrap();
+        assert_eq!(content[0].subtitle.subs, spanish);
+        let (_, content) = db
+            .get_all_subs_for_corpus_language(imported, Language::English)
+            .await
+            .unwrap();
+        assert_eq!(content[0].subtitle.subs[0].text, "no\n");
+        let views = db
+            .get_media_views_for_srt(content[0].subtitle.uuid)
+            .await
+            .unwrap();
+        assert_eq!(views.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn index_records_info() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+
+        let index = index_subtitles(&tapp.app, corpus_id, Some(3))
+            .await
+            .unwrap();
+        let info = tapp.app.db.get_search_index_info(index.uuid).await.unwrap();
+        assert_eq!(
+            info,
+            Some(search::index_info(
+                3,
+                Language::English,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+        assert_eq!(
+            index.info().unwrap(),
+            Some(search::index_info(
+                3,
+                Language::English,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+        assert!(tapp.app.search_service(index.uuid).is_ok());
+    }
+
+    #[tokio::test]
+    async fn update_keeps_index_on_window_change() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+        let index = index_subtitles(&tapp.app, corpus_id, Some(3))
+            .await
+            .unwrap();
+
+        let same = update_index(&tapp.app, corpus_id, None).await.unwrap();
+        assert!(!same.created);
+        assert_eq!(same.index.uuid, index.uuid);
+
+        let wider = update_index(&tapp.app, corpus_id, Some(4)).await.unwrap();
+        assert!(!wider.created);
+        assert_eq!(wider.index.uuid, index.uuid);
+        assert!(wider.added.is_empty() && wider.removed.is_empty());
+        let info = Some(search::index_info(
+            4,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        ));
+        assert_eq!(wider.index.info().unwrap(), info);
+        assert_eq!(
+            tapp.app.db.get_search_index_info(index.uuid).await.unwrap(),
+            info
+        );
+    }
+
+    #[tokio::test]
+    async fn update_rebuilds_on_cleanup_change() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+
+        // an index built before the configured cleanup changed
+        let markup = SubtitleCleaner::new(&[CleanupRule::Markup]);
+        let uuid = Uuid::generate();
+        let path = tapp.app.config.index_root().join(uuid.to_string());
+        std::fs::create_dir_all(&path).unwrap();
+        let (srts, all_subs) = current_subs_for_corpus(&tapp.app, corpus_id, Language::English)
+            .await
+            .unwrap();
+        search::build_index(
+            uuid,
+            &path,
+            all_subs
+                .into_iter()
+                .map(|c| search::IndexableEpisode::new(Some(corpus_id), c, &markup)),
+            3,
+            Language::English,
+            &markup,
+        )
+        .unwrap();
+        tapp.app
+            .db
+            .assoc_index_with_srts(
+                uuid,
+                srts,
+                &search::index_info(3, Language::English, &markup),
+            )
+            .await
+            .unwrap();
+
+        let rebuilt = update_index(&tapp.app, corpus_id, None).await.unwrap();
+        assert!(rebuilt.created);
+        assert_ne!(rebuilt.index.uuid, uuid);
+        assert_eq!(
+            rebuilt.index.info().unwrap(),
+            Some(search::index_info(
+                3,
+                Language::English,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn update_rebuilds_on_language_change() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+        let index = index_subtitles(&tapp.app, corpus_id, Some(3))
+            .await
+            .unwrap();
+
+        tapp.app
+            .db
+            .set_corpus_language(corpus_id, Language::Spanish)
+            .await
+            .unwrap();
+        // the existing subtitles stay in english
+        let chapter = tapp
+            .app
+            .db
+            .get_chapter_by_hash(MediaHash::from_bytes(b"episode"))
+            .await
+            .unwrap()
+            .unwrap();
+        tapp.app
+            .db
+            .add_subtitle_track(
+                chapter.id,
+                None,
+                &generate_subtitle(&["no", "tocar", "el", "puesto", "de", "plátanos"]),
+            )
+            .await
+            .unwrap();
+        let rebuilt = update_index(&tapp.app, corpus_id, Some(3)).await.unwrap();
+        assert!(rebuilt.created);
+        assert_ne!(rebuilt.index.uuid, index.uuid);
+        assert_eq!(
+            rebuilt.index.info().unwrap(),
+            Some(search::index_info(
+                3,
+                Language::Spanish,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+        assert_eq!(
+            tapp.app
+                .db
+                .get_latest_search_index_for_corpus_language(corpus_id, Language::English)
+                .await
+                .unwrap(),
+            Some(index.uuid)
+        );
+    }
+
+    #[tokio::test]
+    async fn update_after_rollback() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+        let chapter = db
+            .get_chapter_by_hash(MediaHash::from_bytes(b"episode"))
+            .await
+            .unwrap()
+            .unwrap();
+        db.add_subtitles(chapter.id, &generate_subtitle(&["a", "new", "revision"]))
+            .await
+            .unwrap();
+        let index = index_subtitles(&tapp.app, corpus_id, Some(3))
+            .await
+            .unwrap();
+
+        let first = db.get_subtitle_revisions(chapter.id).await.unwrap()[0].id;
+        let restored = db.restore_subtitle_revision(first).await.unwrap();
+        assert_eq!(
+            db.get_stale_search_indexes().await.unwrap(),
+            vec![index.uuid]
+        );
+
+        let update = update_index(&tapp.app, corpus_id, None).await.unwrap();
+        assert!(!update.created);
+        assert_eq!(update.index.uuid, index.uuid);
+        let restored_id = db.get_srt_id_by_uuid(restored).await.unwrap();
+        assert_eq!(update.added, HashSet::from([restored_id]));
+        assert!(!db.is_search_index_stale(index.uuid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn index_every_language() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+        let chapter_id = tapp
+            .app
+            .db
+            .get_chapter_by_episode(corpus_id, 1, 1)
+            .await
+            .unwrap()
+            .unwrap()
+            .id;
+        tapp.app
+            .db
+            .add_subtitle_track(
+                chapter_id,
+                Some(Language::Spanish),
+                &generate_subtitle(&["no", "tocar", "el", "puesto", "de", "plátanos"]),
+            )
+            .await
+            .unwrap();
+
+        let english = index_subtitles(&tapp.app, corpus_id, None).await.unwrap();
+        let spanish = update_index_for_language(&tapp.app, corpus_id, Language::Spanish, None)
+            .await
+            .unwrap();
+        assert!(spanish.created);
+        assert_eq!(
+            spanish.index.info().unwrap(),
+            Some(search::index_info(
+                DEFAULT_INDEX_WINDOW_SIZE,
+                Language::Spanish,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+        assert_eq!(
+            tapp.app.latest_search_indexes().await.unwrap(),
+            vec![english.uuid]
+        );
+
+        let search = |uuid, query| {
+            let service = tapp.app.search_service(uuid).unwrap();
+            async move {
+                service
+                    .search_and_rank(search_manager::SearchRequest {
+                        query,
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap()
+                    .results
+                    .len()
+            }
+        };
+        assert_eq!(search(spanish.index.uuid, "plátanos").await, 1);
+        assert_eq!(search(spanish.index.uuid, "banana").await, 0);
+        assert_eq!(search(english.uuid, "banana").await, 1);
+
+        let same = update_index_for_language(&tapp.app, corpus_id, Language::Spanish, None)
+            .await
+            .unwrap();
+        assert!(!same.created);
+        assert_eq!(same.index.uuid, spanish.index.uuid);
+    }
+
+    #[tokio::test]
+    async fn index_cleaned_subtitles() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = tapp.app.db.add_corpus("show").await.unwrap().id.unwrap();
+        let chapter_id = tapp
+            .app
+            .db
+            .define_chapter(
+                corpus_id,
+                "episode",
+                Some(1),
+                Some(1),
+                MediaHash::from_bytes(b"episode"),
+            )
+            .await
+            .unwrap();
+        tapp.app
+            .db
+            .add_subtitles(
+                chapter_id,
+                &generate_subtitle(&[
+                    "♪ the story of a wealthy family ♪",
+                    "there's always money",
+                    "in the banana stand",
+                    "[DOOR SLAMS]\n<i>MICHAEL: No touching!</i>",
+                    "I've made a huge mistake",
+                ]),
+            )
+            .await
+            .unwrap();
+
+        let index = index_subtitles_in_memory(&tapp.app, corpus_id, Some(1))
+            .await
+            .unwrap();
+        let service = tapp
+            .app
+            .search_service_from_bundle(&index.to_bundle().unwrap())
+            .unwrap();
+        let search = |query| {
+            service.search_and_rank(search_manager::SearchRequest {
+                query,
+                ..Default::default()
+            })
+        };
+        for query in ["door", "michael"] {
+            assert!(search(query).await.unwrap().results.is_empty(), "{}", query);
+        }
+        let resp = search("touching").await.unwrap();
+        let clip = &resp.results[0];
+        let matched = clip.lines.iter().find(|l| l.score > 0.0).unwrap();
+        assert_eq!(matched.text, "No touching!");
+        assert_eq!(matched.highlights, vec![3..11]);
+
+        // lyrics are searchable, without their music notes
+        let resp = search("wealthy").await.unwrap();
+        let matched = resp.results[0]
+            .lines
+            .iter()
+            .find(|l| l.score > 0.0)
+            .unwrap();
+        assert_eq!(matched.text, "the story of a wealthy family");
+    }
+
+    #[tokio::test]
+    async fn search_index_in_memory() {
+        let tapp = lucille_test_app().await;
+        let corpus_id = corpus_with_episode(&tapp.app).await;
+
+        let index = index_subtitles_in_memory(&tapp.app, corpus_id, Some(3))
+            .await
+            .unwrap();
+        assert!(tapp.app.db.get_search_indexes().await.unwrap().is_empty());
+        assert!(!tapp
+            .app
+            .config
+            .index_root()
+            .join(index.uuid.to_string())
+            .exists());
+
+        let service = tapp
+            .app
+            .search_service_from_bundle(&index.to_bundle().unwrap())
+            .unwrap();
+        assert_eq!(service.uuid(), index.uuid);
+        let resp = service
+            .search_and_rank(search_manager::SearchRequest {
+                query: "banana",
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.results.len(), 1);
+    }
+}
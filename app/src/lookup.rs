@@ -0,0 +1,334 @@
+use std::{ops::Range, time::Duration};
+
+use anyhow::Context;
+use lucille_core::{
+    export::ChapterExport, identifiers::CorpusId, metadata::MediaHash, timing::TimingAdjustment,
+    Subtitle,
+};
+
+use crate::{
+    app::LucilleApp,
+    search_manager::{ClipResult, LineScore},
+};
+
+const DEFAULT_CONTEXT: usize = 2;
+
+/// Which chapter's subtitles a lookup should read
+#[derive(Debug, Clone)]
+pub enum ChapterSelector {
+    Episode {
+        corpus_id: CorpusId,
+        season: u32,
+        episode: u32,
+    },
+    Hash(MediaHash),
+}
+
+#[derive(Debug, Clone)]
+pub struct LookupRequest {
+    pub chapter: ChapterSelector,
+    pub timestamp: Duration,
+    /// How many lines to include before and after the line at `timestamp`
+    pub context: Option<usize>,
+    /// Correct the subtitle times with this, instead of the chapter's own timing
+    pub timing: Option<TimingAdjustment>,
+}
+
+impl LookupRequest {
+    fn get_context(&self) -> usize {
+        self.context.unwrap_or(DEFAULT_CONTEXT)
+    }
+}
+
+/// Find the subtitle lines spoken around a moment in a chapter.
+///
+/// The line on screen at `timestamp` (or the next one, if nothing is on screen)
+/// is scored `1.0`, the surrounding context lines are scored `0.0`.
+pub async fn lookup_timestamp(
+    app: &LucilleApp,
+    request: &LookupRequest,
+) -> anyhow::Result<ClipResult> {
+    let chapter = find_chapter(app, &request.chapter).await?;
+
+    let srt = app
+        .db
+        .lookup_latest_sub_for_chapter(chapter.id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("chapter {} has no subtitles", chapter.id))?;
+    let corpus = app
+        .db
+        .get_corpus(chapter.corpus_id)
+        .await
+        .context("could not find corpus for chapter")?;
+    let timing = match request.timing {
+        Some(timing) => timing,
+        None => app.db.get_chapter_timing(chapter.id).await?,
+    };
+    let subs = timing.apply(&srt.subs);
+
+    let (range, target) = select_lines(&subs, request.timestamp, request.get_context())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{:?} is after the last subtitle of {}",
+                request.timestamp,
+                chapter.metadata
+            )
+        })?;
+
+    let cleanup = app.config.subtitle_index_cleanup();
+    let lines = subs[range.clone()]
+        .iter()
+        .enumerate()
+        .map(|(idx, sub)| LineScore {
+            score: if range.start + idx == target {
+                1.0
+            } else {
+                0.0
+            },
+            text: cleanup.single_line(sub),
+            start: sub.start,
+            end: sub.end,
+            highlights: vec![],
+        })
+        .collect();
+
+    Ok(ClipResult {
+        index: None,
+        corpus: Some(corpus.title),
+        srt_id: srt.id,
+        srt_uuid: srt.uuid,
+        metadata: chapter.metadata,
+        offset: range.start,
+        score: 1.0,
+        exact: true,
+        lines,
+        explanation: None,
+    })
+}
+
+/// The chapter a selector refers to, an error if there is none
+pub async fn find_chapter(
+    app: &LucilleApp,
+    selector: &ChapterSelector,
+) -> anyhow::Result<ChapterExport> {
+    Ok(match selector {
+        ChapterSelector::Episode {
+            corpus_id,
+            season,
+            episode,
+        } => app
+            .db
+            .get_chapter_by_episode(*corpus_id, *season as i64, *episode as i64)
+            .await?
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no chapter for S{:02} E{:02} in corpus {}",
+                    season,
+                    episode,
+                    corpus_id
+                )
+            })?,
+        ChapterSelector::Hash(hash) => app
+            .db
+            .get_chapter_by_hash(*hash)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("no chapter with hash {}", hash))?,
+    })
+}
+
+/// The range of lines to show for `timestamp`, and the index of the line at `timestamp`
+fn select_lines(
+    subs: &[Subtitle],
+    timestamp: Duration,
+    context: usize,
+) -> Option<(Range<usize>, usize)> {
+    let target = subs.iter().position(|s| timestamp < s.end)?;
+    let start = target.saturating_sub(context);
+    let end = (target + context + 1).min(subs.len());
+    Some((start..end, target))
+}
+
+/// Parse a timecode like `12:34`, `1:02:03` or `754.5`.
+///
+/// Only the first part may be 60 or more, `90:00` is fine but `1:75` is not.
+pub fn parse_timecode(s: &str) -> anyhow::Result<Duration> {
+    let mut seconds = 0.0;
+    for (idx, part) in s.trim().split(':').enumerate() {
+        if idx >= 3 {
+            anyhow::bail!("timecode `{}` has too many parts", s);
+        }
+        let value = part
+            .parse::<f64>()
+            .with_context(|| format!("invalid timecode `{}`", s))?;
+        if value < 0.0 || !value.is_finite() {
+            anyhow::bail!("invalid timecode `{}`", s);
+        }
+        if idx > 0 && value >= 60.0 {
+            anyhow::bail!(
+                "invalid timecode `{}`, minutes and seconds must be less than 60",
+                s
+            );
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod test {
+    use lucille_core::test_util::generate_subtitle;
+
+    use super::*;
+    use crate::app::tests::lucille_test_app;
+
+    // lines are on screen at 0-1.5s, 3-4.5s, 6-7.5s, ...
+    const LINES: [&str; 6] = ["zero", "one", "two", "three", "four", "five"];
+
+    #[test]
+    fn parse_timecodes() {
+        assert_eq!(parse_timecode("12:34").unwrap(), Duration::from_secs(754));
+        assert_eq!(
+            parse_timecode("1:02:03").unwrap(),
+            Duration::from_secs(3723)
+        );
+        assert_eq!(
+            parse_timecode("754.5").unwrap(),
+            Duration::from_secs_f64(754.5)
+        );
+        assert!(parse_timecode("1:2:3:4").is_err());
+        assert!(parse_timecode("12:xx").is_err());
+        assert!(parse_timecode("-3").is_err());
+        assert_eq!(parse_timecode("90:00").unwrap(), Duration::from_secs(5400));
+        assert!(parse_timecode("1:75").is_err());
+        assert!(parse_timecode("1:60:00").is_err());
+        assert!(parse_timecode("12:60").is_err());
+    }
+
+    #[test]
+    fn select_line_on_screen() {
+        let subs = generate_subtitle(&LINES);
+        let selected = select_lines(&subs, Duration::from_secs_f64(6.5), 1);
+        assert_eq!(selected, Some((1..4, 2)));
+    }
+
+    #[test]
+    fn select_next_line_between_subtitles() {
+        let subs = generate_subtitle(&LINES);
+        let selected = select_lines(&subs, Duration::from_secs_f64(5.0), 1);
+        assert_eq!(selected, Some((1..4, 2)));
+    }
+
+    #[test]
+    fn select_lines_clamps_context() {
+        let subs = generate_subtitle(&LINES);
+        let selected = select_lines(&subs, Duration::ZERO, 2);
+        assert_eq!(selected, Some((0..3, 0)));
+        let selected = select_lines(&subs, Duration::from_secs(15), 2);
+        assert_eq!(selected, Some((3..6, 5)));
+        let selected = select_lines(&subs, Duration::from_secs(20), 2);
+        assert_eq!(selected, None);
+    }
+
+    #[tokio::test]
+    async fn lookup_by_episode() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let chapter_id = db
+            .define_chapter(
+                corpus_id,
+                "the one with the lines",
+                Some(3),
+                Some(12),
+                MediaHash::from_bytes(b"episode"),
+            )
+            .await
+            .unwrap();
+        let srt_uuid = db
+            .add_subtitles(chapter_id, &generate_subtitle(&LINES))
+            .await
+            .unwrap();
+
+        let request = LookupRequest {
+            chapter: ChapterSelector::Episode {
+                corpus_id,
+                season: 3,
+                episode: 12,
+            },
+            timestamp: Duration::from_secs(9),
+            context: Some(1),
+            timing: None,
+        };
+        let clip = lookup_timestamp(&tapp.app, &request).await.unwrap();
+        assert_eq!(clip.srt_uuid, srt_uuid);
+        assert_eq!(clip.corpus.as_deref(), Some("show"));
+        assert_eq!(clip.offset, 2);
+        let text = clip
+            .lines
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(text, vec!["two", "three", "four"]);
+        assert_eq!(clip.segment().sub_range, 2..4);
+
+        // the chapter's timing is corrected before the line is found
+        db.set_chapter_timing(chapter_id, TimingAdjustment::offset(-3000))
+            .await
+            .unwrap();
+        let clip = lookup_timestamp(&tapp.app, &request).await.unwrap();
+        assert_eq!(clip.offset, 3);
+        assert_eq!(clip.lines[1].text, "four");
+        assert_eq!(clip.lines[1].start, Duration::from_secs(9));
+        let preview = LookupRequest {
+            timing: Some(TimingAdjustment::IDENTITY),
+            ..request.clone()
+        };
+        let clip = lookup_timestamp(&tapp.app, &preview).await.unwrap();
+        assert_eq!(clip.offset, 2);
+
+        let missing = LookupRequest {
+            chapter: ChapterSelector::Episode {
+                corpus_id,
+                season: 3,
+                episode: 13,
+            },
+            ..request
+        };
+        assert!(lookup_timestamp(&tapp.app, &missing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn segment_ending_at_last_line() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let hash = MediaHash::from_bytes(b"episode");
+        let chapter_id = db
+            .define_chapter(corpus_id, "the last line", None, None, hash)
+            .await
+            .unwrap();
+        let subs = generate_subtitle(&LINES);
+        db.add_subtitles(chapter_id, &subs).await.unwrap();
+
+        let request = LookupRequest {
+            chapter: ChapterSelector::Hash(hash),
+            timestamp: Duration::from_secs(16),
+            context: Some(1),
+            timing: None,
+        };
+        let clip = lookup_timestamp(&tapp.app, &request).await.unwrap();
+        let segment = clip.segment();
+        assert_eq!(segment.sub_range, 4..5);
+
+        // the subtitles a gif of the clip is rendered with
+        let rendered = segment.subtitles(&subs).unwrap();
+        let text = rendered.iter().map(|s| s.text.trim()).collect::<Vec<_>>();
+        assert_eq!(text, vec!["four", "five"]);
+
+        let past_the_end = crate::transcode::SubSegment {
+            sub_range: 4..6,
+            ..segment
+        };
+        assert!(past_the_end.subtitles(&subs).is_err());
+    }
+}
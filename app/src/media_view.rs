@@ -32,6 +32,21 @@ fn select_best_media_view(priorities: &[String], mut views: Vec<MediaView>) -> O
     views.into_iter().next()
 }
 
+/// The position in `priorities` of the best of `views`, lower is better.
+///
+/// Views which are not in `priorities` rank after all of them, as
+/// [`select_best_media_view`] would still fall back to one of them.
+/// `None` when there are no views at all.
+pub(crate) fn best_media_view_rank(priorities: &[String], views: &[MediaView]) -> Option<usize> {
+    if views.is_empty() {
+        return None;
+    }
+    let rank = priorities
+        .iter()
+        .position(|p| views.iter().any(|v| v.name == *p));
+    Some(rank.unwrap_or(priorities.len()))
+}
+
 pub async fn get_media_view_in_corpus(
     db: &Database,
     corpus_id: CorpusId,
@@ -1,15 +1,67 @@
-use lucille_core::uuid::Uuid;
-use search::SearchIndex;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    hash::Hash,
+    ops::{Range, RangeInclusive},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use database::Database;
+use lucille_core::{
+    clean_sub::{CleanSub, SubtitleCleaner},
+    media_segment::MediaView,
+    metadata::MediaMetadata,
+    uuid::Uuid,
+    Subtitle,
+};
+pub use search::ClipExplanation;
+use search::{EpisodeScore, Highlighter, QueryMode, SearchFilter, SearchIndex, Suggestions};
+
+use crate::{media_view::best_media_view_rank, transcode::SubSegment};
 use serde::{Deserialize, Serialize};
 
 const DEFAULT_WINDOW: usize = 5;
 const DEFAULT_MAX_RESPONSES: usize = 5;
+const DEFAULT_MAX_SUGGESTIONS: usize = 5;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SearchRequest<'a> {
     pub query: &'a str,
     pub window: Option<usize>,
+    /// The number of results per page
     pub max_responses: Option<usize>,
+    /// Allow terms to match within this many edits (typos).
+    ///
+    /// Exact matches will still be ranked above fuzzy matches.
+    #[serde(default)]
+    pub fuzzy: Option<u8>,
+    /// Only match episodes in these seasons
+    #[serde(default)]
+    pub seasons: Option<RangeInclusive<u32>>,
+    /// Only match episodes with these episode numbers
+    #[serde(default)]
+    pub episodes: Option<RangeInclusive<u32>>,
+    /// Only match episodes from these corpus ids
+    #[serde(default)]
+    pub corpus: Vec<i64>,
+    /// Continue from the end of a previous page, see [`SearchResponse::next_cursor`].
+    ///
+    /// The rest of the request should be the same as the one which returned the cursor.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Explain how each clip was scored, see [`ClipResult::explanation`].
+    ///
+    /// This makes the search much slower.
+    #[serde(default)]
+    pub explain: bool,
+    /// Return every copy of a clip, instead of only the best one to render.
+    ///
+    /// Duplicate encodes of an episode and revisions of its subtitles
+    /// otherwise show up as the same clip once, see [`SearchService::search_and_rank`].
+    #[serde(default)]
+    pub keep_duplicates: bool,
 }
 
 impl<'a> SearchRequest<'a> {
@@ -19,69 +71,1449 @@ impl<'a> SearchRequest<'a> {
     fn get_max_responses(&self) -> usize {
         self.max_responses.unwrap_or(DEFAULT_MAX_RESPONSES)
     }
+    fn get_query_mode(&self) -> QueryMode {
+        match self.fuzzy {
+            Some(0) | None => QueryMode::Exact,
+            Some(distance) => QueryMode::Fuzzy {
+                distance: distance.min(search::MAX_FUZZY_DISTANCE),
+            },
+        }
+    }
+    fn get_filter(&self) -> SearchFilter {
+        SearchFilter {
+            seasons: self.seasons.clone(),
+            episodes: self.episodes.clone(),
+            corpus: self.corpus.clone(),
+        }
+    }
+    fn get_cursor(&self) -> anyhow::Result<Option<SearchCursor>> {
+        self.cursor.as_deref().map(SearchCursor::decode).transpose()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClipResult {
+    /// The search index this result was found in, if it came from a search
+    pub index: Option<Uuid>,
+    /// The title of the corpus behind `index`, if known
+    pub corpus: Option<String>,
     pub srt_id: i64,
+    pub srt_uuid: Uuid,
+    /// The episode the clip is from
+    pub metadata: MediaMetadata,
     pub offset: usize,
     pub score: f32,
+    /// Was this an exact match, rather than only a fuzzy one
+    pub exact: bool,
     pub lines: Vec<LineScore>,
+    /// How the clip was scored, if the request asked for it.
+    ///
+    /// Scores here are as the index computed them, before any normalization
+    /// across indexes.
+    #[serde(default)]
+    pub explanation: Option<ClipExplanation>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LineScore {
     pub score: f32,
+    /// The cleaned up text of the subtitle line
+    pub text: String,
+    pub start: Duration,
+    pub end: Duration,
+    /// Byte ranges of `text` which matched the query
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl ClipResult {
+    /// The subtitle lines of this clip, ready for a `MakeGifRequest`
+    pub fn segment(&self) -> SubSegment {
+        SubSegment {
+            srt_uuid: self.srt_uuid,
+            sub_range: self.offset..(self.offset + self.lines.len().saturating_sub(1)),
+        }
+    }
+}
+
+impl LineScore {
+    /// The line's text with every highlight wrapped in `open` and `close`
+    pub fn marked_text(&self, open: &str, close: &str) -> String {
+        let mut marked = String::with_capacity(self.text.len());
+        let mut cursor = 0;
+        for range in &self.highlights {
+            marked.push_str(&self.text[cursor..range.start]);
+            marked.push_str(open);
+            marked.push_str(&self.text[range.clone()]);
+            marked.push_str(close);
+            cursor = range.end;
+        }
+        marked.push_str(&self.text[cursor..]);
+        marked
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
-    pub index: Uuid,
+    /// Every search index which was consulted for these results
+    pub indexes: Vec<Uuid>,
     pub results: Vec<ClipResult>,
+    /// Pass this as [`SearchRequest::cursor`] to get the next page, `None` on the last page
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// A clip which matched, before its lines are loaded from the database
+#[derive(Debug, Clone)]
+struct RankedClip {
+    index: Uuid,
+    srt_id: i64,
+    offset: usize,
+    score: f32,
+    exact: bool,
+    line_scores: Vec<f32>,
+    explanation: Option<ClipExplanation>,
+}
+
+impl RankedClip {
+    fn cursor(&self) -> SearchCursor {
+        SearchCursor {
+            exact: self.exact,
+            score: self.score,
+            index: self.index,
+            srt_id: self.srt_id,
+            offset: self.offset,
+        }
+    }
+}
+
+/// The position of a clip in the ranking.
+///
+/// A page resumes after the last clip of the previous page, rather than at a
+/// count of results. Cursors are only meaningful for the index they came from
+/// as it was: updating an index changes the scores of its clips, and a rebuilt
+/// index is a different index, so a cursor from before may repeat or skip clips.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SearchCursor {
+    exact: bool,
+    score: f32,
+    index: Uuid,
+    srt_id: i64,
+    offset: usize,
+}
+
+impl SearchCursor {
+    fn encode(&self) -> String {
+        let json = serde_json::to_string(self).expect("unable to serialize JSON");
+        lucille_core::base64::encode_string(json)
+    }
+
+    fn decode(cursor: &str) -> anyhow::Result<SearchCursor> {
+        lucille_core::base64::deserialize_json(cursor).context("invalid search cursor")
+    }
+
+    /// Exact matches first, then by descending score. Ties are broken by
+    /// position so that the order is total and the same on every request.
+    fn rank_cmp(&self, other: &SearchCursor) -> Ordering {
+        other
+            .exact
+            .cmp(&self.exact)
+            .then_with(|| {
+                other
+                    .score
+                    .partial_cmp(&self.score)
+                    .expect("search gave invalid score")
+            })
+            .then_with(|| self.index.cmp(&other.index))
+            .then_with(|| self.srt_id.cmp(&other.srt_id))
+            .then_with(|| self.offset.cmp(&other.offset))
+    }
+}
+
+/// Sort `clips` into rank order and select the page after `cursor`,
+/// returning the cursor for the following page if there is one.
+fn select_page<T>(
+    mut clips: Vec<T>,
+    cursor: Option<&SearchCursor>,
+    page_size: usize,
+    clip_cursor: impl Fn(&T) -> SearchCursor,
+) -> (Vec<T>, Option<String>) {
+    clips.sort_by(|a, b| clip_cursor(a).rank_cmp(&clip_cursor(b)));
+    let mut page = clips
+        .into_iter()
+        .filter(|c| match cursor {
+            Some(cursor) => clip_cursor(c).rank_cmp(cursor) == Ordering::Greater,
+            None => true,
+        })
+        .take(page_size + 1)
+        .collect::<Vec<_>>();
+    let next_cursor = if page.len() > page_size {
+        page.truncate(page_size);
+        page.last().map(|c| clip_cursor(c).encode())
+    } else {
+        None
+    };
+    (page, next_cursor)
+}
+
+/// Where a [`SearchService`] loads the episodes of the clips it finds.
+///
+/// Every method takes a batch of srt ids, and fails if any of them is missing.
+#[async_trait::async_trait]
+pub trait EpisodeSource: Send + Sync {
+    /// The uuid and metadata of each srt, by srt id
+    async fn episode_metadata(
+        &self,
+        srt_ids: &[i64],
+    ) -> anyhow::Result<HashMap<i64, (Uuid, MediaMetadata)>>;
+    /// The subtitle lines of each srt, by srt id, with the chapter's timing
+    /// correction applied so the times match the media
+    async fn episode_subs(&self, srt_ids: &[i64]) -> anyhow::Result<HashMap<i64, Vec<Subtitle>>>;
+    /// The media views of the chapter of each srt, by srt id
+    async fn media_views(&self, srt_ids: &[i64]) -> anyhow::Result<HashMap<i64, Vec<MediaView>>>;
+    /// The srt id of the subtitles `srt_uuid`
+    async fn srt_id(&self, srt_uuid: Uuid) -> anyhow::Result<i64>;
+}
+
+#[async_trait::async_trait]
+impl EpisodeSource for Database {
+    async fn episode_metadata(
+        &self,
+        srt_ids: &[i64],
+    ) -> anyhow::Result<HashMap<i64, (Uuid, MediaMetadata)>> {
+        Ok(self.get_episodes_by_ids(srt_ids).await?)
+    }
+
+    async fn episode_subs(&self, srt_ids: &[i64]) -> anyhow::Result<HashMap<i64, Vec<Subtitle>>> {
+        let timings = self.get_chapter_timings_for_srts(srt_ids).await?;
+        let subs = self.get_all_subs_for_srts(srt_ids).await?;
+        Ok(subs
+            .into_iter()
+            .map(|(srt_id, subs)| (srt_id, timings[&srt_id].apply(&subs)))
+            .collect())
+    }
+
+    async fn media_views(&self, srt_ids: &[i64]) -> anyhow::Result<HashMap<i64, Vec<MediaView>>> {
+        Ok(self.get_media_views_for_srts(srt_ids).await?)
+    }
+
+    async fn srt_id(&self, srt_uuid: Uuid) -> anyhow::Result<i64> {
+        Ok(self.get_srt_id_by_uuid(srt_uuid).await?)
+    }
 }
 
 pub struct SearchService {
     pub(crate) index: SearchIndex,
+    pub(crate) episodes: Arc<dyn EpisodeSource>,
+    pub(crate) corpus: Option<String>,
+    /// Media view names, most preferred first, see [`crate::app::LucilleConfig::media_view_priority`]
+    pub(crate) media_view_priority: Vec<String>,
+    /// How the text of result lines is cleaned, the same way as the indexed text
+    pub(crate) subtitle_cleanup: SubtitleCleaner,
 }
 
 impl SearchService {
-    pub fn new(index: SearchIndex) -> SearchService {
-        SearchService { index }
+    pub fn new(index: SearchIndex, episodes: Arc<dyn EpisodeSource>) -> SearchService {
+        SearchService {
+            index,
+            episodes,
+            corpus: None,
+            media_view_priority: vec![],
+            subtitle_cleanup: SubtitleCleaner::index_default(),
+        }
+    }
+    pub fn with_corpus<S: Into<String>>(mut self, corpus: S) -> SearchService {
+        self.corpus = Some(corpus.into());
+        self
+    }
+    /// Prefer duplicate clips from chapters with these media views, most preferred first
+    pub fn with_media_view_priority(mut self, priorities: Vec<String>) -> SearchService {
+        self.media_view_priority = priorities;
+        self
+    }
+    /// Clean result lines like the index was, see [`crate::app::LucilleConfig::subtitle_index_cleanup`]
+    pub fn with_subtitle_cleanup(mut self, cleaner: SubtitleCleaner) -> SearchService {
+        self.subtitle_cleanup = cleaner;
+        self
+    }
+    /// How the text of result lines is cleaned
+    pub fn subtitle_cleanup(&self) -> &SubtitleCleaner {
+        &self.subtitle_cleanup
+    }
+    pub fn uuid(&self) -> Uuid {
+        self.index.uuid()
     }
+
+    /// Search the index and return a page of the best clips.
+    ///
+    /// When several clips show the same lines of the same episode, from
+    /// duplicate encodes of a chapter or revisions of its subtitles, only
+    /// the one from the chapter with the most preferred media view is kept,
+    /// unless [`SearchRequest::keep_duplicates`] is set.
     pub async fn search_and_rank<'r>(
         &self,
         request: SearchRequest<'r>,
     ) -> anyhow::Result<SearchResponse> {
-        let scores = self.index.search(request.query, request.get_window())?;
-
-        // TODO what does this actually do? nothing? I think its nothing...
-        // it reverses a list
-        //
-        //
-        let mut results = Vec::new();
-        for rm in search::rank(&scores)
+        let cursor = request.get_cursor()?;
+        let ranked = self.unique_clips(&request).await?;
+        let (page, next_cursor) = select_page(
+            ranked,
+            cursor.as_ref(),
+            request.get_max_responses(),
+            RankedClip::cursor,
+        );
+        let highlighter = self
+            .index
+            .highlighter(request.query, request.get_query_mode())?;
+        let results = self.load_clips(&highlighter, page).await?;
+        Ok(SearchResponse {
+            indexes: vec![self.index.uuid()],
+            results,
+            next_cursor,
+        })
+    }
+
+    /// Find clips worded like lines `lines` of the subtitles `srt_uuid`,
+    /// such as a clip from an earlier search.
+    ///
+    /// The most distinctive terms of the lines are searched for, weighted by
+    /// how rare they are in the index. The lines themselves are left out, in
+    /// every copy of their chapter. The window, filters, paging and duplicate
+    /// handling of `request` apply as they do to [`SearchService::search_and_rank`],
+    /// its `query`, `fuzzy` and `explain` are ignored.
+    pub async fn more_like_this<'r>(
+        &self,
+        srt_uuid: Uuid,
+        lines: Range<usize>,
+        request: SearchRequest<'r>,
+    ) -> anyhow::Result<SearchResponse> {
+        let cursor = request.get_cursor()?;
+        let srt_id = self.episodes.srt_id(srt_uuid).await?;
+        let (_, metadata, subs) = self.load_episode(srt_id).await?;
+        if lines.is_empty() || lines.end > subs.len() {
+            anyhow::bail!(
+                "lines {:?} are not within the {} lines of srt {}",
+                lines,
+                subs.len(),
+                srt_uuid
+            );
+        }
+        let source = &subs[lines];
+        let text = source
+            .iter()
+            .map(|sub| self.subtitle_cleanup.single_line(sub))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let scores =
+            self.index
+                .more_like_this(&text, request.get_window(), &request.get_filter())?;
+        let clips = self.ranked_clips(&scores);
+        let clip_metadata = self
+            .episodes
+            .episode_metadata(&unique_srt_ids(&clips))
+            .await?;
+
+        // a clip showing any of the lines is the source, not something like it,
+        // only the lines of clips from the source's chapter are needed to tell
+        let source_chapter = ChapterKey::from(&metadata);
+        let source_lines = source
+            .iter()
+            .map(|sub| clip_text(&self.subtitle_cleanup, std::slice::from_ref(sub)))
+            .filter(|line| !line.is_empty())
+            .collect::<HashSet<_>>();
+        let (same_chapter, mut clips): (Vec<_>, Vec<_>) = clips
             .into_iter()
-            .rev()
-            .take(request.get_max_responses())
-        {
-            let srt_id = rm.ep as i64;
-            let offset = rm.clip.index;
-            let lines = rm
-                .clip
-                .scores
+            .partition(|clip| ChapterKey::from(&clip_metadata[&clip.srt_id].1) == source_chapter);
+        let same_chapter_subs = self
+            .episodes
+            .episode_subs(&unique_srt_ids(&same_chapter))
+            .await?;
+        clips.extend(same_chapter.into_iter().filter(|clip| {
+            !clip_lines(&same_chapter_subs[&clip.srt_id], clip)
                 .iter()
-                .map(|score| LineScore { score: score.0 })
+                .any(|sub| {
+                    source_lines.contains(&clip_text(
+                        &self.subtitle_cleanup,
+                        std::slice::from_ref(sub),
+                    ))
+                })
+        }));
+        let clips = if request.keep_duplicates {
+            clips
+        } else {
+            self.remove_duplicates(clips, &clip_metadata).await?
+        };
+
+        let (page, next_cursor) = select_page(
+            clips,
+            cursor.as_ref(),
+            request.get_max_responses(),
+            RankedClip::cursor,
+        );
+        let highlighter = self.index.more_like_this_highlighter(&text)?;
+        let results = self.load_clips(&highlighter, page).await?;
+        Ok(SearchResponse {
+            indexes: vec![self.index.uuid()],
+            results,
+            next_cursor,
+        })
+    }
+
+    /// Serialize the index, see [`crate::app::LucilleApp::search_service_from_bundle`]
+    pub fn to_bundle(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.index.to_bundle()?)
+    }
+
+    /// Completions and spelling corrections for a partly typed `query`,
+    /// at most `limit` of each (per misspelled word for corrections)
+    pub fn suggest(&self, query: &str, limit: Option<usize>) -> anyhow::Result<Suggestions> {
+        let limit = limit.unwrap_or(DEFAULT_MAX_SUGGESTIONS);
+        Ok(self.index.suggest(query, limit)?)
+    }
+
+    /// Every clip which matched `request`, without duplicates unless the request keeps them
+    async fn unique_clips(&self, request: &SearchRequest<'_>) -> anyhow::Result<Vec<RankedClip>> {
+        let clips = self.rank_clips(request)?;
+        if request.keep_duplicates {
+            return Ok(clips);
+        }
+        let metadata = self
+            .episodes
+            .episode_metadata(&unique_srt_ids(&clips))
+            .await?;
+        self.remove_duplicates(clips, &metadata).await
+    }
+
+    /// Keep the best copy of every clip, see [`dedup_episode_clips`].
+    ///
+    /// `metadata` must have every srt of `clips`.
+    async fn remove_duplicates(
+        &self,
+        clips: Vec<RankedClip>,
+        metadata: &HashMap<i64, (Uuid, MediaMetadata)>,
+    ) -> anyhow::Result<Vec<RankedClip>> {
+        // only clips from a chapter with several matching srts can have duplicates,
+        // the lines and media views of the rest aren't needed
+        let mut srts_per_chapter: HashMap<ChapterKey, usize> = HashMap::new();
+        for (_, episode) in metadata.values() {
+            *srts_per_chapter
+                .entry(ChapterKey::from(episode))
+                .or_default() += 1;
+        }
+        let (shared, mut unique): (Vec<_>, Vec<_>) = clips
+            .into_iter()
+            .partition(|clip| srts_per_chapter[&ChapterKey::from(&metadata[&clip.srt_id].1)] > 1);
+        let episodes = self.clip_episodes(&shared, metadata).await?;
+        unique.extend(dedup_episode_clips(
+            shared,
+            &episodes,
+            &self.subtitle_cleanup,
+        ));
+        Ok(unique)
+    }
+
+    /// The episode of every clip by srt id, with what is needed to tell duplicates apart.
+    ///
+    /// `metadata` must have every srt of `clips`.
+    async fn clip_episodes(
+        &self,
+        clips: &[RankedClip],
+        metadata: &HashMap<i64, (Uuid, MediaMetadata)>,
+    ) -> anyhow::Result<HashMap<i64, ClipEpisode>> {
+        let srt_ids = unique_srt_ids(clips);
+        if srt_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut subs = self.episodes.episode_subs(&srt_ids).await?;
+        let views = self.episodes.media_views(&srt_ids).await?;
+        Ok(srt_ids
+            .into_iter()
+            .map(|srt_id| {
+                let episode = ClipEpisode {
+                    chapter: ChapterKey::from(&metadata[&srt_id].1),
+                    subs: subs.remove(&srt_id).unwrap_or_default(),
+                    render_rank: best_media_view_rank(
+                        &self.media_view_priority,
+                        views.get(&srt_id).map_or(&[], Vec::as_slice),
+                    ),
+                };
+                (srt_id, episode)
+            })
+            .collect())
+    }
+
+    /// Every clip which matched `request`, in no particular order
+    fn rank_clips(&self, request: &SearchRequest) -> anyhow::Result<Vec<RankedClip>> {
+        let search = if request.explain {
+            SearchIndex::explain
+        } else {
+            SearchIndex::search
+        };
+        let scores = search(
+            &self.index,
+            request.query,
+            request.get_window(),
+            request.get_query_mode(),
+            &request.get_filter(),
+        )?;
+        Ok(self.ranked_clips(&scores))
+    }
+
+    /// Every clip with a score, in no particular order
+    fn ranked_clips(&self, scores: &HashMap<usize, EpisodeScore>) -> Vec<RankedClip> {
+        search::rank(scores)
+            .into_iter()
+            .map(|rm| RankedClip {
+                index: self.index.uuid(),
+                srt_id: rm.ep as i64,
+                offset: rm.clip.index,
+                score: rm.score.0,
+                exact: rm.exact,
+                line_scores: rm.clip.scores.iter().map(|s| s.0).collect(),
+                explanation: scores[&rm.ep].explain(&rm.clip),
+            })
+            .collect()
+    }
+
+    /// Fetch the subtitle lines for each clip, and highlight the matches
+    async fn load_clips(
+        &self,
+        highlighter: &Highlighter,
+        clips: Vec<RankedClip>,
+    ) -> anyhow::Result<Vec<ClipResult>> {
+        let srt_ids = unique_srt_ids(&clips);
+        let metadata = self.episodes.episode_metadata(&srt_ids).await?;
+        let episode_subs = self.episodes.episode_subs(&srt_ids).await?;
+
+        let mut results = Vec::with_capacity(clips.len());
+        for clip in clips {
+            let (srt_uuid, metadata) = &metadata[&clip.srt_id];
+            let subs = &episode_subs[&clip.srt_id];
+            let clip_subs = subs.get(clip.offset..).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "search index {} has a clip at line {} of srt {}, which only has {} lines, the index may be stale",
+                    clip.index,
+                    clip.offset,
+                    srt_uuid,
+                    subs.len()
+                )
+            })?;
+
+            let lines = clip
+                .line_scores
+                .into_iter()
+                .zip(clip_subs)
+                .map(|(score, sub)| {
+                    let text = self.subtitle_cleanup.single_line(sub);
+                    LineScore {
+                        score,
+                        highlights: highlighter.highlight(&text),
+                        text,
+                        start: sub.start,
+                        end: sub.end,
+                    }
+                })
                 .collect::<Vec<_>>();
             results.push(ClipResult {
-                srt_id,
-                offset,
-                score: rm.score.0,
+                index: Some(clip.index),
+                corpus: self.corpus.clone(),
+                srt_id: clip.srt_id,
+                srt_uuid: *srt_uuid,
+                metadata: metadata.clone(),
+                offset: clip.offset,
+                score: clip.score,
+                exact: clip.exact,
                 lines,
+                explanation: clip.explanation,
             })
         }
+        Ok(results)
+    }
+
+    async fn load_episode(
+        &self,
+        srt_id: i64,
+    ) -> anyhow::Result<(Uuid, MediaMetadata, Vec<Subtitle>)> {
+        let (srt_uuid, metadata) = self
+            .episodes
+            .episode_metadata(&[srt_id])
+            .await?
+            .remove(&srt_id)
+            .with_context(|| format!("no metadata for srt {}", srt_id))?;
+        let subs = self
+            .episodes
+            .episode_subs(&[srt_id])
+            .await?
+            .remove(&srt_id)
+            .with_context(|| format!("no subtitles for srt {}", srt_id))?;
+        Ok((srt_uuid, metadata, subs))
+    }
+}
+
+/// The srt ids of `clips`, each once
+fn unique_srt_ids(clips: &[RankedClip]) -> Vec<i64> {
+    let mut seen = HashSet::new();
+    clips
+        .iter()
+        .map(|clip| clip.srt_id)
+        .filter(|srt_id| seen.insert(*srt_id))
+        .collect()
+}
+
+/// Search several indexes (typically one per corpus) at once
+pub struct FederatedSearchService {
+    pub(crate) services: Vec<SearchService>,
+}
+
+impl FederatedSearchService {
+    pub fn new(services: Vec<SearchService>) -> FederatedSearchService {
+        FederatedSearchService { services }
+    }
+
+    /// Run the request against every index, and merge the results.
+    ///
+    /// Scores from different indexes are not comparable, so each index's
+    /// scores are normalized against its own best match before merging.
+    pub async fn search_and_rank<'r>(
+        &self,
+        request: SearchRequest<'r>,
+    ) -> anyhow::Result<SearchResponse> {
+        let cursor = request.get_cursor()?;
+        let mut ranked = Vec::new();
+        for (service_idx, service) in self.services.iter().enumerate() {
+            let mut clips = service.unique_clips(&request).await?;
+            if self.services.len() > 1 {
+                normalize_scores(&mut clips);
+            }
+            ranked.extend(clips.into_iter().map(|c| (service_idx, c)));
+        }
+
+        let (page, next_cursor) = select_page(
+            ranked,
+            cursor.as_ref(),
+            request.get_max_responses(),
+            |(_, clip)| clip.cursor(),
+        );
+
+        // load each index's clips together, then put them back in rank order
+        let mut results = std::iter::repeat_with(|| None)
+            .take(page.len())
+            .collect::<Vec<_>>();
+        for (service_idx, service) in self.services.iter().enumerate() {
+            let (positions, clips): (Vec<_>, Vec<_>) = page
+                .iter()
+                .enumerate()
+                .filter(|(_, (idx, _))| *idx == service_idx)
+                .map(|(pos, (_, clip))| (pos, clip.clone()))
+                .unzip();
+            if clips.is_empty() {
+                continue;
+            }
+            let highlighter = service
+                .index
+                .highlighter(request.query, request.get_query_mode())?;
+            for (pos, clip) in positions
+                .into_iter()
+                .zip(service.load_clips(&highlighter, clips).await?)
+            {
+                results[pos] = Some(clip);
+            }
+        }
+
         Ok(SearchResponse {
-            index: self.index.uuid(),
-            results,
+            indexes: self.services.iter().map(|s| s.uuid()).collect(),
+            results: results
+                .into_iter()
+                .map(|c| c.expect("every clip in the page was loaded"))
+                .collect(),
+            next_cursor,
         })
     }
 }
+
+/// The episode a clip is from, see [`SearchService::clip_episodes`]
+struct ClipEpisode {
+    chapter: ChapterKey,
+    subs: Vec<Subtitle>,
+    /// How well the episode renders, see [`dedup_clips`]
+    render_rank: Option<usize>,
+}
+
+impl ClipEpisode {
+    /// The subtitle lines shown by `clip`
+    fn clip_lines(&self, clip: &RankedClip) -> &[Subtitle] {
+        clip_lines(&self.subs, clip)
+    }
+}
+
+/// The lines of `subs` shown by `clip`, which must be from them
+fn clip_lines<'s>(subs: &'s [Subtitle], clip: &RankedClip) -> &'s [Subtitle] {
+    let end = (clip.offset + clip.line_scores.len()).min(subs.len());
+    &subs[clip.offset.min(end)..end]
+}
+
+/// Keep the best copy of every clip, see [`dedup_clips`]
+fn dedup_episode_clips(
+    clips: Vec<RankedClip>,
+    episodes: &HashMap<i64, ClipEpisode>,
+    cleaner: &SubtitleCleaner,
+) -> Vec<RankedClip> {
+    let keys = duplicate_keys(&clips, |clip| {
+        let episode = &episodes[&clip.srt_id];
+        (
+            episode.chapter.clone(),
+            clip_text(cleaner, episode.clip_lines(clip)),
+        )
+    });
+    dedup_clips(clips, |clip| {
+        let key = keys[&(clip.srt_id, clip.offset)].clone();
+        (key, episodes[&clip.srt_id].render_rank)
+    })
+}
+
+/// What a clip shows, clips with the same key are duplicates of each other
+type DuplicateKey = (ChapterKey, String, usize);
+
+/// Identifies an episode across duplicate encodes and subtitle revisions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ChapterKey {
+    Episode {
+        /// The show, ignoring case, a corpus may have several
+        show: Option<String>,
+        season: Option<u32>,
+        episode: u32,
+    },
+    Title(String),
+}
+
+impl From<&MediaMetadata> for ChapterKey {
+    fn from(metadata: &MediaMetadata) -> Self {
+        match metadata {
+            MediaMetadata::Episode(e) => ChapterKey::Episode {
+                show: e.show.as_deref().map(str::to_lowercase),
+                season: e.season,
+                episode: e.episode,
+            },
+            MediaMetadata::Movie { .. } => ChapterKey::Title(metadata.to_string()),
+            MediaMetadata::Unknown(title) => ChapterKey::Title(title.clone()),
+        }
+    }
+}
+
+/// The words of some subtitle lines, ignoring case, punctuation and line breaks
+fn clip_text(cleaner: &SubtitleCleaner, lines: &[Subtitle]) -> String {
+    lines
+        .iter()
+        .flat_map(|sub| {
+            CleanSub(cleaner, sub)
+                .to_string()
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The [`DuplicateKey`] of every clip by srt id and offset.
+///
+/// A quote said more than once in an episode is told apart by counting
+/// the clips with the same text within each srt, in order.
+fn duplicate_keys(
+    clips: &[RankedClip],
+    describe: impl Fn(&RankedClip) -> (ChapterKey, String),
+) -> HashMap<(i64, usize), DuplicateKey> {
+    let mut positions = clips
+        .iter()
+        .map(|c| (c.srt_id, c.offset, c))
+        .collect::<Vec<_>>();
+    positions.sort_by_key(|(srt_id, offset, _)| (*srt_id, *offset));
+
+    let mut occurrences: HashMap<(i64, ChapterKey, String), usize> = HashMap::new();
+    positions
+        .into_iter()
+        .map(|(srt_id, offset, clip)| {
+            let (chapter, text) = describe(clip);
+            let count = occurrences
+                .entry((srt_id, chapter.clone(), text.clone()))
+                .or_default();
+            let key = (chapter, text, *count);
+            *count += 1;
+            ((srt_id, offset), key)
+        })
+        .collect()
+}
+
+/// Sort `clips` into rank order, keeping one clip for each key.
+///
+/// `describe` gives the key of a clip, and how well it renders: the position
+/// of its best media view in the priority list, lower is better, and `None`
+/// if it can't be rendered at all. Of clips which render equally well, the
+/// best ranked is kept.
+fn dedup_clips<K: Hash + Eq>(
+    mut clips: Vec<RankedClip>,
+    describe: impl Fn(&RankedClip) -> (K, Option<usize>),
+) -> Vec<RankedClip> {
+    clips.sort_by(|a, b| a.cursor().rank_cmp(&b.cursor()));
+
+    let mut best: HashMap<K, (usize, Option<usize>)> = HashMap::new();
+    for (idx, clip) in clips.iter().enumerate() {
+        let (key, render_rank) = describe(clip);
+        match best.entry(key) {
+            Entry::Vacant(e) => {
+                e.insert((idx, render_rank));
+            }
+            Entry::Occupied(mut e) => {
+                let renders_better = match (render_rank, e.get().1) {
+                    (Some(rank), Some(best_rank)) => rank < best_rank,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if renders_better {
+                    e.insert((idx, render_rank));
+                }
+            }
+        }
+    }
+
+    let keep = best
+        .into_values()
+        .map(|(idx, _)| idx)
+        .collect::<HashSet<_>>();
+    clips
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| keep.contains(idx))
+        .map(|(_, clip)| clip)
+        .collect()
+}
+
+fn normalize_scores(clips: &mut [RankedClip]) {
+    let max = clips.iter().map(|c| c.score).fold(0.0f32, f32::max);
+    if max <= 0.0 {
+        return;
+    }
+    for clip in clips {
+        clip.score /= max;
+        for line in &mut clip.line_scores {
+            *line /= max;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lucille_core::{
+        identifiers::{ChapterId, MediaViewId},
+        language::Language,
+        media_segment::MediaView,
+        metadata::{EpisodeMetadata, MediaHash},
+        test_util::generate_subtitle,
+        timing::TimingAdjustment,
+        ContentData, LucilleSub,
+    };
+
+    use super::*;
+    use crate::app::tests::lucille_test_app;
+
+    /// Episodes kept in memory, to search without a database
+    struct MemoryEpisodes {
+        episodes: Vec<ContentData>,
+        /// The srts whose media views were asked for
+        views_requested: std::sync::Mutex<Vec<i64>>,
+    }
+
+    impl MemoryEpisodes {
+        fn new(episodes: Vec<ContentData>) -> MemoryEpisodes {
+            MemoryEpisodes {
+                episodes,
+                views_requested: Default::default(),
+            }
+        }
+
+        fn get(&self, srt_id: i64) -> anyhow::Result<&ContentData> {
+            self.episodes
+                .iter()
+                .find(|c| c.subtitle.id == srt_id)
+                .with_context(|| format!("no srt {}", srt_id))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EpisodeSource for MemoryEpisodes {
+        async fn episode_metadata(
+            &self,
+            srt_ids: &[i64],
+        ) -> anyhow::Result<HashMap<i64, (Uuid, MediaMetadata)>> {
+            srt_ids
+                .iter()
+                .map(|&id| {
+                    let c = self.get(id)?;
+                    Ok((id, (c.subtitle.uuid, c.metadata.clone())))
+                })
+                .collect()
+        }
+
+        async fn episode_subs(
+            &self,
+            srt_ids: &[i64],
+        ) -> anyhow::Result<HashMap<i64, Vec<Subtitle>>> {
+            srt_ids
+                .iter()
+                .map(|&id| Ok((id, self.get(id)?.subtitle.subs.clone())))
+                .collect()
+        }
+
+        async fn media_views(
+            &self,
+            srt_ids: &[i64],
+        ) -> anyhow::Result<HashMap<i64, Vec<MediaView>>> {
+            self.views_requested.lock().unwrap().extend(srt_ids);
+            Ok(srt_ids.iter().map(|&id| (id, vec![])).collect())
+        }
+
+        async fn srt_id(&self, srt_uuid: Uuid) -> anyhow::Result<i64> {
+            self.episodes
+                .iter()
+                .find(|c| c.subtitle.uuid == srt_uuid)
+                .map(|c| c.subtitle.id)
+                .with_context(|| format!("no srt {}", srt_uuid))
+        }
+    }
+
+    #[tokio::test]
+    async fn search_without_database() {
+        let eps = (1..=2)
+            .map(|i| ContentData {
+                metadata: MediaMetadata::Unknown(format!("episode {}", i)),
+                hash: MediaHash::from_bytes(format!("episode {}", i).as_bytes()),
+                subtitle: LucilleSub {
+                    id: i,
+                    uuid: Uuid::generate(),
+                    subs: generate_subtitle(&["no touching", &"money ".repeat(i as usize)]),
+                },
+            })
+            .collect::<Vec<_>>();
+        let index = search::build_index_in_ram(
+            Uuid::generate(),
+            eps.clone().into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+        let episodes = Arc::new(MemoryEpisodes::new(eps));
+        let service = SearchService::new(index, episodes.clone());
+
+        let resp = service
+            .search_and_rank(SearchRequest {
+                query: "money",
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.results.len(), 2);
+        let best = &resp.results[0];
+        assert_eq!(best.srt_id, 2);
+        assert_eq!(
+            best.metadata,
+            MediaMetadata::Unknown("episode 2".to_string())
+        );
+        assert!(best.lines.iter().any(|l| l.text == "money money"));
+        // no two srts are the same chapter, so none can be duplicates
+        assert!(episodes.views_requested.lock().unwrap().is_empty());
+
+        let resp = service
+            .more_like_this(
+                best.srt_uuid,
+                0..1,
+                SearchRequest {
+                    keep_duplicates: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let found = resp
+            .results
+            .iter()
+            .map(|c| (c.srt_id, c.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(1, 0)]);
+        assert!(episodes.views_requested.lock().unwrap().is_empty());
+    }
+
+    fn clip(score: f32, lines: &[f32]) -> RankedClip {
+        RankedClip {
+            index: Uuid::generate(),
+            srt_id: 1,
+            offset: 0,
+            score,
+            exact: true,
+            line_scores: lines.to_vec(),
+            explanation: None,
+        }
+    }
+
+    #[test]
+    fn normalize_against_best_clip() {
+        let mut clips = vec![clip(8.0, &[4.0, 8.0]), clip(2.0, &[2.0])];
+        normalize_scores(&mut clips);
+        assert_eq!(clips[0].score, 1.0);
+        assert_eq!(clips[0].line_scores[0], 0.5);
+        assert_eq!(clips[1].score, 0.25);
+        assert_eq!(clips[1].line_scores[0], 0.25);
+    }
+
+    #[test]
+    fn mark_highlights() {
+        let line = LineScore {
+            score: 1.0,
+            text: "in the banana stand".to_string(),
+            start: Duration::ZERO,
+            end: Duration::ZERO,
+            highlights: vec![7..13, 14..19],
+        };
+        assert_eq!(line.marked_text("<", ">"), "in the <banana> <stand>");
+    }
+
+    #[test]
+    fn normalize_empty_results() {
+        let mut clips = vec![clip(0.0, &[0.0])];
+        normalize_scores(&mut clips);
+        assert_eq!(clips[0].score, 0.0);
+    }
+
+    #[test]
+    fn pages_follow_rank_order() {
+        let index = Uuid::generate();
+        let clips = [
+            (false, 9.0, 1),
+            (true, 1.0, 2),
+            (true, 3.0, 3),
+            (true, 1.0, 1),
+        ]
+        .into_iter()
+        .map(|(exact, score, srt_id)| RankedClip {
+            index,
+            srt_id,
+            exact,
+            ..clip(score, &[score])
+        })
+        .collect::<Vec<_>>();
+
+        let ids = |page: &[RankedClip]| page.iter().map(|c| c.srt_id).collect::<Vec<_>>();
+
+        let (first, next) = select_page(clips.clone(), None, 2, RankedClip::cursor);
+        assert_eq!(ids(&first), vec![3, 1]);
+        let cursor = SearchCursor::decode(&next.unwrap()).unwrap();
+        assert_eq!(cursor, first[1].cursor());
+
+        let (second, next) = select_page(clips, Some(&cursor), 2, RankedClip::cursor);
+        assert_eq!(ids(&second), vec![2, 1]);
+        assert!(!second[1].exact);
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn reject_invalid_cursor() {
+        let request = SearchRequest {
+            query: "money",
+            cursor: Some("not a cursor".to_string()),
+            ..Default::default()
+        };
+        assert!(request.get_cursor().is_err());
+    }
+
+    #[tokio::test]
+    async fn paginate_search_results() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        for episode in 1..=5 {
+            let chapter_id = db
+                .define_chapter(
+                    corpus_id,
+                    format!("episode {}", episode),
+                    Some(1),
+                    Some(episode),
+                    MediaHash::from_bytes(format!("episode {}", episode).as_bytes()),
+                )
+                .await
+                .unwrap();
+            let mut lines = vec!["no touching"; 6];
+            lines[episode as usize] = "there's always money";
+            db.add_subtitles(chapter_id, &generate_subtitle(&lines))
+                .await
+                .unwrap();
+        }
+        let index = crate::index_subtitles(&tapp.app, corpus_id, None)
+            .await
+            .unwrap();
+        let service = SearchService::new(index, Arc::new(db.clone()));
+
+        let request = SearchRequest {
+            query: "money",
+            max_responses: Some(2),
+            ..Default::default()
+        };
+        let mut pages = vec![];
+        let mut cursor = None;
+        loop {
+            let resp = service
+                .search_and_rank(SearchRequest {
+                    cursor,
+                    ..request.clone()
+                })
+                .await
+                .unwrap();
+            pages.push(resp.results);
+            cursor = resp.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+
+        let all = service
+            .search_and_rank(SearchRequest {
+                max_responses: Some(10),
+                ..request.clone()
+            })
+            .await
+            .unwrap();
+        let key = |c: &ClipResult| (c.srt_id, c.offset);
+        assert_eq!(
+            pages.iter().flatten().map(key).collect::<Vec<_>>(),
+            all.results.iter().map(key).collect::<Vec<_>>()
+        );
+        assert!(all.results.iter().all(|c| c.explanation.is_none()));
+
+        let explained = service
+            .search_and_rank(SearchRequest {
+                explain: true,
+                ..request
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            explained.results.iter().map(key).collect::<Vec<_>>(),
+            all.results[..2].iter().map(key).collect::<Vec<_>>()
+        );
+        for clip in &explained.results {
+            let explanation = clip.explanation.as_ref().unwrap();
+            assert!(!explanation.windows.is_empty());
+            assert_eq!(explanation.line_totals.len(), clip.lines.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn search_results_have_corrected_times() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let chapter_id = db
+            .define_chapter(
+                corpus_id,
+                "episode",
+                Some(1),
+                Some(1),
+                MediaHash::from_bytes(b"episode"),
+            )
+            .await
+            .unwrap();
+        let subs = generate_subtitle(&["no touching", "there's always money"]);
+        db.add_subtitles(chapter_id, &subs).await.unwrap();
+        let timing = TimingAdjustment::new(-500, 2.0).unwrap();
+        db.set_chapter_timing(chapter_id, timing).await.unwrap();
+        let index = crate::index_subtitles(&tapp.app, corpus_id, None)
+            .await
+            .unwrap();
+        let service = SearchService::new(index, Arc::new(db.clone()));
+
+        let resp = service
+            .search_and_rank(SearchRequest {
+                query: "money",
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let clip = &resp.results[0];
+        let line = &clip.lines[1 - clip.offset];
+        assert_eq!(line.text, "there's always money");
+        assert_eq!(line.start, timing.apply_time(subs[1].start));
+        assert_eq!(line.end, timing.apply_time(subs[1].end));
+    }
+
+    #[test]
+    fn dedup_prefers_renderable_clips() {
+        let at = |srt_id: i64, score: f32| RankedClip {
+            srt_id,
+            ..clip(score, &[score])
+        };
+        // srt 1 and 2 are duplicates, only 2 has the preferred view; srt 3 has no views
+        let clips = vec![at(1, 3.0), at(2, 1.0), at(3, 2.0), at(4, 0.5)];
+        let kept = dedup_clips(clips, |c| match c.srt_id {
+            1 => ("a", Some(1)),
+            2 => ("a", Some(0)),
+            3 => ("b", None),
+            _ => ("b", Some(1)),
+        });
+        let kept = kept.iter().map(|c| c.srt_id).collect::<Vec<_>>();
+        assert_eq!(kept, vec![2, 4]);
+
+        let views = |names: &[&str]| {
+            names
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| MediaView {
+                    id: MediaViewId::new(idx as i64 + 1),
+                    chapter_id: ChapterId::new(1),
+                    name: name.to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let priorities = vec!["1080p".to_string(), "720p".to_string()];
+        assert_eq!(best_media_view_rank(&priorities, &views(&[])), None);
+        assert_eq!(best_media_view_rank(&priorities, &views(&["x"])), Some(2));
+        assert_eq!(
+            best_media_view_rank(&priorities, &views(&["720p", "1080p"])),
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn deduplicate_duplicate_chapters() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let mut chapters = vec![];
+        for (name, episode, lines) in [
+            ("encode a", 1, &["no touching", "there's always money"][..]),
+            (
+                "encode b",
+                1,
+                &["No touching!", "There's always money."][..],
+            ),
+            (
+                "other episode",
+                2,
+                &[
+                    "there's always money",
+                    "no touching",
+                    "there's always money",
+                ][..],
+            ),
+        ] {
+            let chapter_id = db
+                .define_chapter(
+                    corpus_id,
+                    name,
+                    Some(1),
+                    Some(episode),
+                    MediaHash::from_bytes(name.as_bytes()),
+                )
+                .await
+                .unwrap();
+            db.add_subtitles(chapter_id, &generate_subtitle(lines))
+                .await
+                .unwrap();
+            chapters.push(chapter_id);
+        }
+        db.add_media_view(chapters[0], "small").await.unwrap();
+        db.add_media_view(chapters[1], "large").await.unwrap();
+
+        let index = crate::index_subtitles(&tapp.app, corpus_id, Some(1))
+            .await
+            .unwrap();
+        let service = SearchService::new(index, Arc::new(db.clone()))
+            .with_media_view_priority(vec!["large".to_string(), "small".to_string()]);
+        let request = SearchRequest {
+            query: "money",
+            window: Some(1),
+            max_responses: Some(10),
+            ..Default::default()
+        };
+        let titles = |resp: &SearchResponse| {
+            let mut titles = resp
+                .results
+                .iter()
+                .map(|c| (c.metadata.title(), c.offset))
+                .collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+
+        let resp = service.search_and_rank(request.clone()).await.unwrap();
+        assert_eq!(
+            titles(&resp),
+            vec![
+                ("encode b".to_string(), 1),
+                ("other episode".to_string(), 0),
+                ("other episode".to_string(), 2),
+            ]
+        );
+
+        let resp = service
+            .search_and_rank(SearchRequest {
+                keep_duplicates: true,
+                ..request
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.results.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn more_like_this_clip() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let mut srts = vec![];
+        for (name, episode, lines) in [
+            (
+                "encode a",
+                1,
+                &["no touching", "there's always money in the banana stand"][..],
+            ),
+            (
+                "encode b",
+                1,
+                &["No touching!", "There's always money in the banana stand."][..],
+            ),
+            (
+                "other episode",
+                2,
+                &[
+                    "burn the banana stand down",
+                    "no touching",
+                    "here's some money",
+                ][..],
+            ),
+        ] {
+            let chapter_id = db
+                .define_chapter(
+                    corpus_id,
+                    name,
+                    Some(1),
+                    Some(episode),
+                    MediaHash::from_bytes(name.as_bytes()),
+                )
+                .await
+                .unwrap();
+            let srt_uuid = db
+                .add_subtitles(chapter_id, &generate_subtitle(lines))
+                .await
+                .unwrap();
+            srts.push(srt_uuid);
+        }
+        let index = crate::index_subtitles(&tapp.app, corpus_id, Some(1))
+            .await
+            .unwrap();
+        let service = SearchService::new(index, Arc::new(db.clone()));
+        let request = SearchRequest {
+            window: Some(1),
+            max_responses: Some(10),
+            ..Default::default()
+        };
+
+        // the source line and its copy in the other encode are left out
+        let resp = service
+            .more_like_this(srts[0], 1..2, request.clone())
+            .await
+            .unwrap();
+        let clips = resp
+            .results
+            .iter()
+            .map(|c| (c.metadata.title(), c.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            clips,
+            vec![
+                ("other episode".to_string(), 0),
+                ("other episode".to_string(), 2),
+            ]
+        );
+        assert_eq!(
+            resp.results[0].lines[0].marked_text("<", ">"),
+            "burn the <banana> <stand> down"
+        );
+
+        assert!(service
+            .more_like_this(srts[0], 1..3, request)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn same_episode_of_different_shows_is_not_a_duplicate() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("shows").await.unwrap().id.unwrap();
+        let mut srts = vec![];
+        for (show, encode) in [("Arrested", "a"), ("arrested", "b"), ("Development", "c")] {
+            let metadata = MediaMetadata::Episode(EpisodeMetadata {
+                show: Some(show.to_string()),
+                ..EpisodeMetadata::new(1, 1, "pilot")
+            });
+            let chapter_id = db
+                .define_media_chapter(
+                    corpus_id,
+                    &metadata,
+                    MediaHash::from_bytes(format!("{} {}", show, encode).as_bytes()),
+                )
+                .await
+                .unwrap();
+            let srt_uuid = db
+                .add_subtitles(
+                    chapter_id,
+                    &generate_subtitle(&["no touching", "there's always money"]),
+                )
+                .await
+                .unwrap();
+            srts.push(srt_uuid);
+        }
+        let index = crate::index_subtitles(&tapp.app, corpus_id, Some(1))
+            .await
+            .unwrap();
+        let service = SearchService::new(index, Arc::new(db.clone()));
+        let request = SearchRequest {
+            window: Some(1),
+            max_responses: Some(10),
+            ..Default::default()
+        };
+        let shows = |resp: &SearchResponse| {
+            let mut shows = resp
+                .results
+                .iter()
+                .map(|c| match &c.metadata {
+                    MediaMetadata::Episode(e) => e.show.clone().unwrap().to_lowercase(),
+                    other => panic!("expected an episode, found {:?}", other),
+                })
+                .collect::<Vec<_>>();
+            shows.sort();
+            shows
+        };
+
+        // the two encodes of arrested are duplicates, development is not
+        let resp = service
+            .search_and_rank(SearchRequest {
+                query: "money",
+                ..request.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(shows(&resp), vec!["arrested", "development"]);
+
+        // only the source's own show is left out
+        let resp = service
+            .more_like_this(srts[0], 1..2, request)
+            .await
+            .unwrap();
+        assert_eq!(shows(&resp), vec!["development"]);
+    }
+}
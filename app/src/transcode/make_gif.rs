@@ -1,9 +1,12 @@
+use std::time::Duration;
+
 use anyhow::Context;
+use lucille_core::{identifiers::ChapterId, timing::TimingAdjustment, Subtitle};
 
 use super::MakeGifRequest;
 use crate::{
     app::LucilleApp,
-    ffmpeg::gif::{FFMpegCmdAsyncResult, FFMpegGifTranscoder, GifSettings},
+    ffmpeg::gif::{CutSetting, FFMpegCmdAsyncResult, FFMpegGifTranscoder, GifSettings},
 };
 
 pub async fn handle_make_gif_request(
@@ -13,13 +16,30 @@ pub async fn handle_make_gif_request(
     if request.segments.len() != 1 {
         anyhow::bail!("not supported: gifs must contain exactly 1 segment")
     }
-    let subsegment = &request.segments[0];
-    let srt_uuid = subsegment.srt_uuid;
-    let subs = app.db.get_all_subs_for_srt_by_uuid(srt_uuid).await?;
-    let clip_subs = &subs[subsegment.sub_range.start..subsegment.sub_range.end + 1];
-    let mut settings = GifSettings::default();
+    let srt_uuid = request.segments[0].srt_uuid;
+    let (chapter_id, timing, clip_subs) = segment_subtitles(app, request).await?;
+    let clip_subs = clip_subs.as_slice();
+    let mut settings = GifSettings {
+        subtitle_cleanup: app.config.subtitle_render_cleanup(),
+        ..Default::default()
+    };
     let (start, end) = settings.cut_selection.content_cut_times(clip_subs);
 
+    let burned_subs = match request.subtitle_language {
+        Some(language) => {
+            let track = app
+                .db
+                .lookup_latest_sub_for_chapter_language(chapter_id, language)
+                .await?
+                .with_context(|| format!("no {} subtitles for this chapter", language))?;
+            // cut where the segment's own subtitles start and stop
+            settings.cut_selection.start = CutSetting::Exact(start);
+            settings.cut_selection.end = CutSetting::Exact(end);
+            subtitles_between(&timing.apply(&track.subs), start, end)
+        }
+        None => clip_subs.to_vec(),
+    };
+
     let target_media_view = crate::media_view::get_media_view_for_transcode(app, srt_uuid)
         .await?
         .context("no media view found")?;
@@ -28,7 +48,7 @@ pub async fn handle_make_gif_request(
         crate::media_view::get_surrounding_media(app, target_media_view.id, start, end).await?;
     settings.cut_selection.segment_start = Some(segment_start);
 
-    let transcoder = FFMpegGifTranscoder::build_cmd(app.config.ffmpeg(), clip_subs, &settings)
+    let transcoder = FFMpegGifTranscoder::build_cmd(app.config.ffmpeg(), &burned_subs, &settings)
         .await
         .context("could not build transcoder command")?;
     let res = transcoder
@@ -38,3 +58,97 @@ pub async fn handle_make_gif_request(
 
     Ok(res)
 }
+
+/// The chapter of the request's first segment, the timing its subtitles are
+/// corrected with, and the corrected subtitles of the segment
+async fn segment_subtitles(
+    app: &LucilleApp,
+    request: &MakeGifRequest,
+) -> anyhow::Result<(ChapterId, TimingAdjustment, Vec<Subtitle>)> {
+    let subsegment = &request.segments[0];
+    let chapter_id = app.db.get_chapter_id_for_srt(subsegment.srt_uuid).await?;
+    let timing = match request.timing {
+        Some(timing) => timing,
+        None => app.db.get_chapter_timing(chapter_id).await?,
+    };
+    let subs = timing.apply(
+        &app.db
+            .get_all_subs_for_srt_by_uuid(subsegment.srt_uuid)
+            .await?,
+    );
+    let clip_subs = subsegment.subtitles(&subs)?.to_vec();
+    Ok((chapter_id, timing, clip_subs))
+}
+
+/// The subtitles on screen between `start` and `end`, trimmed to that time
+fn subtitles_between(subs: &[Subtitle], start: Duration, end: Duration) -> Vec<Subtitle> {
+    subs.iter()
+        .filter(|s| s.start < end && start < s.end)
+        .map(|s| {
+            let mut trimmed = s.clone();
+            trimmed.start = s.start.max(start);
+            trimmed.end = s.end.min(end);
+            trimmed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lucille_core::{metadata::MediaHash, test_util::generate_subtitle};
+
+    use super::*;
+    use crate::{
+        app::tests::lucille_test_app,
+        lookup::{lookup_timestamp, ChapterSelector, LookupRequest},
+    };
+
+    #[test]
+    fn trim_subtitles_to_clip() {
+        let subs = generate_subtitle(&["uno", "dos", "tres", "cuatro"]);
+        let (start, end) = (subs[1].start + Duration::from_millis(1), subs[2].end);
+        let between = subtitles_between(&subs, start, end);
+        assert_eq!(between.len(), 2);
+        assert_eq!(between[0].text, subs[1].text);
+        assert_eq!(between[0].start, start);
+        assert_eq!(between[1], subs[2]);
+    }
+
+    #[tokio::test]
+    async fn preview_timing_renders_the_corrected_last_line() {
+        let tapp = lucille_test_app().await;
+        let db = &tapp.app.db;
+        let corpus_id = db.add_corpus("show").await.unwrap().id.unwrap();
+        let hash = MediaHash::from_bytes(b"episode");
+        let chapter_id = db
+            .define_chapter(corpus_id, "out of sync", None, None, hash)
+            .await
+            .unwrap();
+        let subs = generate_subtitle(&["zero", "one", "two", "three", "four", "five"]);
+        db.add_subtitles(chapter_id, &subs).await.unwrap();
+        db.set_chapter_timing(chapter_id, TimingAdjustment::offset(-3000))
+            .await
+            .unwrap();
+
+        // what `lucille timing preview` renders: the clip at the corrected
+        // time, with the previewed timing instead of the saved one
+        let timing = TimingAdjustment::offset(3000);
+        let lookup = LookupRequest {
+            chapter: ChapterSelector::Hash(hash),
+            timestamp: Duration::from_millis(18500),
+            context: Some(1),
+            timing: Some(timing),
+        };
+        let clip = lookup_timestamp(&tapp.app, &lookup).await.unwrap();
+        let request = MakeGifRequest {
+            segments: vec![clip.segment()],
+            subtitle_language: None,
+            timing: Some(timing),
+        };
+        let (found_chapter, found_timing, rendered) =
+            segment_subtitles(&tapp.app, &request).await.unwrap();
+        assert_eq!(found_chapter, chapter_id);
+        assert_eq!(found_timing, timing);
+        assert_eq!(rendered, timing.apply(&subs[4..]));
+    }
+}
@@ -1,6 +1,7 @@
 use std::ops::Range;
 
-use lucille_core::uuid::Uuid;
+use anyhow::Context;
+use lucille_core::{language::Language, timing::TimingAdjustment, uuid::Uuid, Subtitle};
 use serde::{Deserialize, Serialize};
 
 pub use self::make_gif::handle_make_gif_request;
@@ -11,12 +12,38 @@ mod make_gif;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubSegment {
     pub srt_uuid: Uuid,
+    /// The subtitles of the segment, `sub_range.end` is the last one, so
+    /// `3..5` is subtitles 3, 4 and 5
     pub sub_range: Range<usize>,
 }
 
+impl SubSegment {
+    /// The subtitles of the segment in `subs`, an error if they are not all there
+    pub fn subtitles<'a>(&self, subs: &'a [Subtitle]) -> anyhow::Result<&'a [Subtitle]> {
+        subs.get(self.sub_range.start..=self.sub_range.end)
+            .with_context(|| {
+                format!(
+                    "subtitles {}-{} are not in the {} subtitles of {}",
+                    self.sub_range.start,
+                    self.sub_range.end,
+                    subs.len(),
+                    self.srt_uuid
+                )
+            })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MakeGifRequest {
     pub segments: Vec<SubSegment>,
+    /// Burn in the chapter's subtitles in this language over the time of the
+    /// segments, instead of the segments' own subtitles
+    #[serde(default)]
+    pub subtitle_language: Option<Language>,
+    /// Correct the subtitle times with this, instead of the chapter's own
+    /// timing, to preview a correction before it is saved
+    #[serde(default)]
+    pub timing: Option<TimingAdjustment>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,5 +1,9 @@
+use std::str::FromStr;
+
 use anyhow::Context;
+use app::lookup::ChapterSelector;
 use clap::{Parser, ValueEnum};
+use lucille_core::metadata::MediaHash;
 
 #[derive(Parser, Debug, Clone)]
 pub struct FileCheckSettings {
@@ -129,7 +133,7 @@ impl MediaStorage {
 
 #[derive(Parser, Debug, Default)]
 pub struct FFMpegConfig {
-    /// Override binary called for `ffmpeg`
+    /// Override binary called for `ffmpeg`, `ffprobe` is expected next to it
     #[clap(long)]
     pub ffmpeg: Option<std::path::PathBuf>,
 }
@@ -154,3 +158,43 @@ impl AppConfigFile {
         self.config_file.as_deref()
     }
 }
+
+/// Select a chapter by its episode, or by the hash of its media
+#[derive(Parser, Debug)]
+pub struct ChapterArgs {
+    /// The name of the corpus the episode is in
+    #[clap(long, required_unless_present = "hash")]
+    pub corpus: Option<String>,
+
+    /// The season number of the episode
+    #[clap(long, required_unless_present = "hash")]
+    pub season: Option<u32>,
+
+    /// The episode number within the season
+    #[clap(long, required_unless_present = "hash")]
+    pub episode: Option<u32>,
+
+    /// Find the chapter by media hash instead of by episode
+    #[clap(long, conflicts_with_all = ["corpus", "season", "episode"])]
+    pub hash: Option<String>,
+}
+
+impl ChapterArgs {
+    pub async fn selector(&self, app: &app::app::LucilleApp) -> anyhow::Result<ChapterSelector> {
+        Ok(if let Some(hash) = &self.hash {
+            ChapterSelector::Hash(MediaHash::from_str(hash).context("could not parse hash")?)
+        } else {
+            let corpus = self.corpus.as_deref().expect("clap requires corpus");
+            let corpus_id = app
+                .db
+                .get_corpus_id(corpus)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("no corpus named `{}`", corpus))?;
+            ChapterSelector::Episode {
+                corpus_id,
+                season: self.season.expect("clap requires season"),
+                episode: self.episode.expect("clap requires episode"),
+            }
+        })
+    }
+}
@@ -7,7 +7,7 @@ use database::Database;
 use lucille_core::metadata::MediaHash;
 
 use super::argparse::MediaStorage;
-use crate::cli::argparse::DatabaseConfig;
+use crate::cli::argparse::{AppConfig, DatabaseConfig};
 
 #[derive(Parser, Debug)]
 pub enum CleanCommand {
@@ -16,6 +16,9 @@ pub enum CleanCommand {
 
     /// Remove unknown files from media root
     MediaRoot(CleanMediaRootCmd),
+
+    /// Remove old search indexes, and index directories unknown to the database
+    Indexes(CleanIndexesCmd),
 }
 
 impl CleanCommand {
@@ -23,6 +26,7 @@ impl CleanCommand {
         match self {
             CleanCommand::LocalStorage(cmd) => cmd.run().await,
             CleanCommand::MediaRoot(cmd) => cmd.run().await,
+            CleanCommand::Indexes(cmd) => cmd.run().await,
         }
     }
 }
@@ -100,6 +104,44 @@ impl CleanMediaRootCmd {
     }
 }
 
+#[derive(Parser, Debug)]
+pub struct CleanIndexesCmd {
+    /// How many of the newest indexes to keep for each corpus
+    #[clap(long, default_value_t = 1)]
+    pub keep: usize,
+
+    /// Do not perform deletion
+    #[clap(long)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+impl CleanIndexesCmd {
+    async fn run(&self) -> anyhow::Result<()> {
+        let app = self.cfg.build_app().await?;
+
+        let stale = app::index_gc::find_stale_indexes(&app, self.keep).await?;
+        for uuid in &stale.superseded {
+            println!("erase index: {}", uuid);
+        }
+        for path in &stale.orphaned {
+            println!("erase unknown index: {:?}", path);
+        }
+        println!(
+            "total to erase = {}",
+            stale.superseded.len() + stale.orphaned.len()
+        );
+
+        if self.dry_run {
+            log::info!("not performing erase due to --dry-run");
+            return Ok(());
+        }
+        app::index_gc::remove_stale_indexes(&app, &stale).await
+    }
+}
+
 #[derive(Parser, Debug)]
 pub struct CleanLocalStorage {
     /// Do not perform deletion
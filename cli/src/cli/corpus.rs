@@ -1,6 +1,10 @@
 use clap::Parser;
+use lucille_core::language::Language;
 
-use crate::cli::{argparse::DatabaseConfig, helpers};
+use crate::cli::{
+    argparse::{AppConfig, DatabaseConfig},
+    helpers,
+};
 
 #[derive(Parser, Debug)]
 pub enum CorpusCommand {
@@ -8,6 +12,8 @@ pub enum CorpusCommand {
     New(CorpusNewOpts),
     /// List existing corpuses
     List(CorpusListOpts),
+    /// Change the language a corpus is indexed with, indexes are rebuilt on the next update
+    SetLanguage(CorpusSetLanguageOpts),
 }
 
 impl CorpusCommand {
@@ -15,6 +21,7 @@ impl CorpusCommand {
         match self {
             CorpusCommand::New(args) => create_new_corpus(args).await,
             CorpusCommand::List(args) => list_all_corpus(args).await,
+            CorpusCommand::SetLanguage(args) => set_corpus_language(args).await,
         }
     }
 }
@@ -22,10 +29,22 @@ impl CorpusCommand {
 #[derive(Parser, Debug)]
 pub struct CorpusNewOpts {
     pub name: String,
+    /// The language of the subtitles, decides stemming and stopwords for search
+    #[clap(long)]
+    pub language: Option<Language>,
     #[clap(flatten)]
     pub db: DatabaseConfig,
 }
 
+#[derive(Parser, Debug)]
+pub struct CorpusSetLanguageOpts {
+    pub name: String,
+    /// An ISO 639-1 code or english name, e.g. `es` or `spanish`
+    pub language: Language,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
 #[derive(Parser, Debug)]
 pub struct CorpusListOpts {
     #[clap(flatten)]
@@ -37,6 +56,23 @@ pub(crate) async fn create_new_corpus(args: &CorpusNewOpts) -> anyhow::Result<()
     log::info!("creating new corpus with name: {:?}", args.name);
     let corpus = app.db.add_corpus(&args.name).await?;
     log::info!("inserted `{}` with id={}", args.name, corpus.id.unwrap());
+    if let Some(language) = args.language {
+        app.db
+            .set_corpus_language(corpus.id.unwrap(), language)
+            .await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn set_corpus_language(args: &CorpusSetLanguageOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let corpus_id = app
+        .db
+        .get_corpus_id(&args.name)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no corpus named `{}`", args.name))?;
+    app.db.set_corpus_language(corpus_id, args.language).await?;
+    log::info!("set language of `{}` to {}", args.name, args.language);
     Ok(())
 }
 
@@ -44,7 +80,8 @@ pub(crate) async fn list_all_corpus(args: &CorpusListOpts) -> anyhow::Result<()>
     let app = helpers::get_app(Some(&args.db), None).await?;
     let corpus = app.db.list_corpus().await?;
     for c in corpus {
-        println!("{:?}", c);
+        let language = app.db.get_corpus_language(c.id.unwrap()).await?;
+        println!("{:?} ({})", c, language);
     }
     Ok(())
 }
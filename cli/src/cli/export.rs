@@ -2,19 +2,24 @@ use anyhow::Context;
 use clap::Parser;
 use lucille_core::export::CorpusExport;
 
-use super::argparse::DatabaseConfig;
+use lucille_core::uuid::Uuid;
+
+use super::argparse::{AppConfig, DatabaseConfig};
 use crate::cli::helpers;
 
 #[derive(Parser, Debug)]
 pub enum ExportCommand {
     /// Export all details for a corpus
     Corpus(ExportCorpusOpts),
+    /// Export a search index as a single bundle file, which can be loaded into memory
+    Index(ExportIndexOpts),
 }
 
 impl ExportCommand {
     pub(crate) async fn run(&self) -> anyhow::Result<()> {
         match self {
             ExportCommand::Corpus(o) => export_corpus(o).await,
+            ExportCommand::Index(o) => export_index(o).await,
         }
     }
 }
@@ -54,6 +59,19 @@ pub struct ExportCorpusOpts {
     pub db: DatabaseConfig,
 }
 
+#[derive(Parser, Debug)]
+pub struct ExportIndexOpts {
+    /// The UUID of the search index to export
+    pub index: Uuid,
+
+    /// File to write the bundle to
+    #[clap(long)]
+    pub out: std::path::PathBuf,
+
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
 pub(crate) async fn import_corpus(args: &ImportCorpusOpts) -> anyhow::Result<()> {
     let app = helpers::get_app(Some(&args.db), None)
         .await
@@ -95,3 +113,11 @@ pub(crate) async fn export_corpus(args: &ExportCorpusOpts) -> anyhow::Result<()>
 
     Ok(())
 }
+
+pub(crate) async fn export_index(args: &ExportIndexOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let bundle = app.search_service(args.index)?.to_bundle()?;
+    std::fs::write(&args.out, bundle)
+        .with_context(|| format!("could not write search index bundle: {:?}", args.out))?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use app::{lookup::LookupRequest, transcode::MakeGifRequest};
+use clap::Parser;
+
+use super::argparse;
+
+#[derive(Parser, Debug)]
+pub struct LookupCommand {
+    /// The time to look at, e.g. `12:34`, `1:02:03` or `754.5`
+    #[clap(value_parser = app::lookup::parse_timecode)]
+    pub timecode: Duration,
+
+    #[clap(flatten)]
+    pub chapter: argparse::ChapterArgs,
+
+    /// How many lines to show before and after the timecode
+    #[clap(long)]
+    pub context: Option<usize>,
+
+    #[clap(flatten)]
+    cfg: argparse::AppConfig,
+}
+
+impl LookupCommand {
+    pub(crate) async fn run(&self) -> anyhow::Result<()> {
+        let app = self.cfg.build_app().await?;
+
+        let chapter = self.chapter.selector(&app).await?;
+
+        let request = LookupRequest {
+            chapter,
+            timestamp: self.timecode,
+            context: self.context,
+            timing: None,
+        };
+        let clip = app::lookup::lookup_timestamp(&app, &request).await?;
+
+        println!("{}", clip.metadata);
+        for line in &clip.lines {
+            let marker = if line.score > 0.0 { ">" } else { " " };
+            println!("{} [{}] {}", marker, format_timecode(line.start), line.text);
+        }
+
+        let gif_request = MakeGifRequest {
+            segments: vec![clip.segment()],
+            subtitle_language: None,
+            timing: None,
+        };
+        let json = serde_json::to_string(&gif_request)?;
+        println!(
+            "\nrender: {}",
+            lucille_core::base64::B64Bytes::from(json.as_str())
+        );
+        Ok(())
+    }
+}
+
+pub(crate) fn format_timecode(t: Duration) -> String {
+    let secs = t.as_secs();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60,
+        t.subsec_millis()
+    )
+}
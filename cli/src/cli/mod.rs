@@ -7,10 +7,13 @@ mod corpus;
 mod debug_utils;
 mod export;
 mod helpers;
+mod lookup;
 mod media_view;
 mod render;
+mod revisions;
 mod scan;
 mod search;
+mod timing;
 
 pub fn get_args() -> CliOpts {
     CliOpts::parse()
@@ -19,6 +22,7 @@ pub fn get_args() -> CliOpts {
 #[derive(Parser, Debug)]
 #[clap(version = clap::crate_version!(), author = "Scott S. <scottschroeder@sent.com>")]
 pub struct CliOpts {
+    /// Log more: `-v` info, `-vv` debug, `-vvv` trace, `-vvvv` trace from noisy dependencies too
     #[clap(short, long, global = true, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
@@ -53,12 +57,23 @@ enum SubCommand {
     #[clap(subcommand)]
     Export(export::ExportCommand),
 
+    /// Show what is said at a moment in an episode
+    Lookup(lookup::LookupCommand),
+
     /// Interactive Gif Creation
     Interactive(search::InteractiveOpts),
 
     /// Render a previously saved request
     Render(render::RenderRequest),
 
+    /// Subtitle revisions of a chapter: list, diff and roll back
+    #[clap(subcommand)]
+    Revisions(revisions::RevisionsCommand),
+
+    /// Correct the subtitle timing of a chapter
+    #[clap(subcommand)]
+    Timing(timing::TimingCommand),
+
     /// Debugging Utilities
     #[clap(subcommand)]
     Debug(debug_utils::DebugCommand),
@@ -79,11 +94,14 @@ impl CliOpts {
             SubCommand::Search(cmd) => cmd.run().await,
             SubCommand::Export(cmd) => cmd.run().await,
             SubCommand::Import(cmd) => cmd.run().await,
+            SubCommand::Lookup(cmd) => cmd.run().await,
             SubCommand::Interactive(cmd) => cmd.run().await,
             SubCommand::Debug(cmd) => cmd.run().await,
             SubCommand::MediaView(cmd) => cmd.run().await,
             SubCommand::Clean(cmd) => cmd.run().await,
             SubCommand::Render(cmd) => cmd.run().await,
+            SubCommand::Revisions(cmd) => cmd.run().await,
+            SubCommand::Timing(cmd) => cmd.run().await,
             SubCommand::Test(cmd) => do_test(cmd).await,
         }
     }
@@ -0,0 +1,180 @@
+use clap::Parser;
+use lucille_core::{
+    revision::{diff_subtitles, SubtitleChange, SubtitleRevision},
+    Subtitle,
+};
+
+use super::{
+    argparse::{AppConfig, ChapterArgs},
+    lookup::format_timecode,
+};
+
+#[derive(Parser, Debug)]
+pub enum RevisionsCommand {
+    /// List every subtitle revision of a chapter
+    List(RevisionsListOpts),
+    /// Show the subtitles which changed between two revisions
+    Diff(RevisionsDiffOpts),
+    /// Make an earlier revision the current subtitles of its language again.
+    ///
+    /// Scanning subtitles which were rolled back does not undo the rollback.
+    Rollback(RevisionsRollbackOpts),
+}
+
+impl RevisionsCommand {
+    pub(crate) async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            RevisionsCommand::List(args) => list_revisions(args).await,
+            RevisionsCommand::Diff(args) => diff_revisions(args).await,
+            RevisionsCommand::Rollback(args) => rollback_revision(args).await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct RevisionsListOpts {
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+#[derive(Parser, Debug)]
+pub struct RevisionsDiffOpts {
+    /// The revision number to compare from, as listed
+    pub old: usize,
+    /// The revision number to compare to
+    pub new: usize,
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+#[derive(Parser, Debug)]
+pub struct RevisionsRollbackOpts {
+    /// The revision number to roll back to, as listed
+    pub revision: usize,
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+fn find_revision(
+    revisions: &[SubtitleRevision],
+    number: usize,
+) -> anyhow::Result<&SubtitleRevision> {
+    revisions
+        .iter()
+        .find(|r| r.number == number)
+        .ok_or_else(|| anyhow::anyhow!("no revision {}, there are {}", number, revisions.len()))
+}
+
+fn one_line(sub: &Subtitle) -> String {
+    sub.text.lines().collect::<Vec<_>>().join(" / ")
+}
+
+pub(crate) async fn list_revisions(args: &RevisionsListOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let revisions = app.db.get_subtitle_revisions(chapter.id).await?;
+
+    println!("{}", chapter.metadata);
+    for revision in &revisions {
+        let mut notes = vec![];
+        if revision.current {
+            notes.push("current".to_string());
+        }
+        if let Some(number) = revision.restored_from {
+            notes.push(format!("restored from {}", number));
+        }
+        println!(
+            "{:>3}  {:<19}  {}  {:>5} lines  {}  {}",
+            revision.number,
+            revision.created_at.as_deref().unwrap_or("unknown"),
+            revision.language.code(),
+            revision.lines,
+            revision.uuid,
+            notes.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub(crate) async fn diff_revisions(args: &RevisionsDiffOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let revisions = app.db.get_subtitle_revisions(chapter.id).await?;
+    let old = find_revision(&revisions, args.old)?;
+    let new = find_revision(&revisions, args.new)?;
+    let old_subs = app.db.get_all_subs_for_srt(old.id).await?;
+    let new_subs = app.db.get_all_subs_for_srt(new.id).await?;
+
+    let (mut added, mut removed, mut retimed) = (0, 0, 0);
+    for change in diff_subtitles(&old_subs, &new_subs) {
+        match change {
+            SubtitleChange::Unchanged(_) => {}
+            SubtitleChange::Removed(sub) => {
+                removed += 1;
+                println!("- [{}] {}", format_timecode(sub.start), one_line(sub));
+            }
+            SubtitleChange::Added(sub) => {
+                added += 1;
+                println!("+ [{}] {}", format_timecode(sub.start), one_line(sub));
+            }
+            SubtitleChange::Retimed { old, new } => {
+                retimed += 1;
+                println!(
+                    "~ [{} -> {}] {}",
+                    format_timecode(old.start),
+                    format_timecode(new.start),
+                    one_line(new)
+                );
+            }
+        }
+    }
+    println!(
+        "\nrevision {} -> {}: {} added, {} removed, {} retimed",
+        old.number, new.number, added, removed, retimed
+    );
+    Ok(())
+}
+
+pub(crate) async fn rollback_revision(args: &RevisionsRollbackOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let revisions = app.db.get_subtitle_revisions(chapter.id).await?;
+    let revision = find_revision(&revisions, args.revision)?;
+    if revision.current {
+        println!(
+            "revision {} is already the current {} subtitles",
+            revision.number, revision.language
+        );
+        return Ok(());
+    }
+
+    let restored = app.db.restore_subtitle_revision(revision.id).await?;
+    println!(
+        "restored revision {} of {} as {}",
+        revision.number, chapter.metadata, restored
+    );
+    let stale = app
+        .db
+        .get_stale_search_indexes_for_corpus(chapter.corpus_id)
+        .await?;
+    if !stale.is_empty() {
+        let corpus = app.db.get_corpus(chapter.corpus_id).await?;
+        println!(
+            "search indexes are stale until updated with `index --update {}`:",
+            corpus.title
+        );
+        for index in stale {
+            println!("  {}", index);
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,5 @@
-use app::DEFAULT_INDEX_WINDOW_SIZE;
 use clap::Parser;
+use lucille_core::language::Language;
 
 use super::argparse::{DatabaseConfig, StorageConfig};
 use crate::cli::helpers;
@@ -25,8 +25,18 @@ pub struct ScanChaptersOpts {
 pub struct IndexCommand {
     pub corpus_name: String,
 
-    #[clap(long, default_value_t=DEFAULT_INDEX_WINDOW_SIZE)]
-    pub window_size: usize,
+    /// The most subtitle lines a search result may span [default: 5, or the window of the
+    /// index being updated]
+    #[clap(long)]
+    pub window_size: Option<usize>,
+
+    /// Update the most recent index for this corpus, instead of creating a new one
+    #[clap(long)]
+    pub update: bool,
+
+    /// Only index the subtitles in this language, instead of every language of the corpus
+    #[clap(long)]
+    pub language: Option<Language>,
 
     #[clap(flatten)]
     pub db: DatabaseConfig,
@@ -58,8 +68,37 @@ impl IndexCommand {
             .await?
             .ok_or_else(|| anyhow::anyhow!("could not find corpus: {:?}", self.corpus_name))?;
 
-        let index = app::index_subtitles(&app, corpus_id, Some(self.window_size)).await?;
-        println!("Created Index: {}", index.uuid);
+        let languages = match self.language {
+            Some(language) => vec![language],
+            None => app.db.get_subtitle_languages_for_corpus(corpus_id).await?,
+        };
+        if languages.is_empty() {
+            anyhow::bail!("corpus {:?} has no subtitles", self.corpus_name);
+        }
+
+        for language in languages {
+            if self.update {
+                let update =
+                    app::update_index_for_language(&app, corpus_id, language, self.window_size)
+                        .await?;
+                if update.created {
+                    println!("Created Index: {} ({})", update.index.uuid, language);
+                } else {
+                    println!(
+                        "Updated Index: {} ({}) (+{} -{})",
+                        update.index.uuid,
+                        language,
+                        update.added.len(),
+                        update.removed.len()
+                    );
+                }
+            } else {
+                let index =
+                    app::index_subtitles_for_language(&app, corpus_id, language, self.window_size)
+                        .await?;
+                println!("Created Index: {} ({})", index.uuid, language);
+            }
+        }
         Ok(())
     }
 }
@@ -1,25 +1,51 @@
-use std::str::FromStr;
+use std::{ops::RangeInclusive, str::FromStr};
 
 use anyhow::Context;
 use app::{
     app::LucilleApp,
-    search_manager::{SearchRequest, SearchResponse},
+    search_manager::{ClipExplanation, SearchRequest, SearchResponse},
     transcode::{MakeGifRequest, SubSegment},
 };
 use clap::Parser;
-use lucille_core::{clean_sub::CleanSubs, uuid::Uuid};
+use lucille_core::{language::Language, uuid::Uuid};
 
 mod select;
 
 use super::argparse::{DatabaseConfig, StorageConfig};
 #[derive(Parser, Debug)]
 pub struct SearchCommand {
-    /// The search query
+    /// The search query. Quote "phrases", require +word in every clip, exclude
+    /// clips with -word, and filter with season:N or episode:N-M
     pub query: Vec<String>,
 
-    /// The UUID of the search index to use
+    /// The UUID of a search index to use, may be given more than once.
+    /// Defaults to the most recent index of every corpus, in its language
     #[clap(long)]
-    pub index: String,
+    pub index: Vec<String>,
+
+    /// Allow query terms to match with up to this many typos
+    #[clap(long)]
+    pub fuzzy: Option<u8>,
+
+    /// Rebuild any search index which is out of date, instead of failing
+    #[clap(long)]
+    pub rebuild_outdated: bool,
+
+    #[clap(flatten)]
+    pub filter: SearchFilterOpts,
+
+    /// Show the page of results after this cursor, printed by a previous search
+    #[clap(long)]
+    pub cursor: Option<String>,
+
+    /// Show every copy of a result from duplicate chapters or subtitle revisions
+    #[clap(long)]
+    pub keep_duplicates: bool,
+
+    /// Show how each result was scored, tantivy's explanation of each term is
+    /// logged at debug level, shown with `-vv`
+    #[clap(long)]
+    pub explain: bool,
 
     #[clap(flatten)]
     pub db: DatabaseConfig,
@@ -30,16 +56,33 @@ pub struct SearchCommand {
 
 #[derive(Parser, Debug)]
 pub struct InteractiveOpts {
-    /// The search query
+    /// The search query. Quote "phrases", require +word in every clip, exclude
+    /// clips with -word, and filter with season:N or episode:N-M
     pub query: Vec<String>,
 
     /// output gif file
     #[clap(long, default_value = "out.gif")]
     pub output: String,
 
-    /// The UUID of the search index to use
+    /// Burn in the subtitles in this language, instead of the ones which matched
     #[clap(long)]
-    pub index: Option<String>,
+    pub subtitle_language: Option<Language>,
+
+    /// The UUID of a search index to use, may be given more than once.
+    /// Defaults to the most recent index of every corpus, in its language
+    #[clap(long)]
+    pub index: Vec<String>,
+
+    /// Allow query terms to match with up to this many typos
+    #[clap(long)]
+    pub fuzzy: Option<u8>,
+
+    /// Rebuild any search index which is out of date, instead of failing
+    #[clap(long)]
+    pub rebuild_outdated: bool,
+
+    #[clap(flatten)]
+    pub filter: SearchFilterOpts,
 
     #[clap(flatten)]
     pub db: DatabaseConfig,
@@ -48,6 +91,31 @@ pub struct InteractiveOpts {
     pub storage: StorageConfig,
 }
 
+#[derive(Parser, Debug)]
+pub struct SearchFilterOpts {
+    /// Only search these seasons, e.g. `3` or `1-5`
+    #[clap(long, value_parser = parse_range)]
+    pub season: Option<RangeInclusive<u32>>,
+
+    /// Only search these episode numbers, e.g. `3` or `1-5`
+    #[clap(long, value_parser = parse_range)]
+    pub episode: Option<RangeInclusive<u32>>,
+
+    /// Only search the corpus with this name, may be given more than once
+    #[clap(long)]
+    pub corpus: Vec<String>,
+}
+
+fn parse_range(s: &str) -> anyhow::Result<RangeInclusive<u32>> {
+    let (start, end) = s.split_once('-').unwrap_or((s, s));
+    let start = start.trim().parse::<u32>()?;
+    let end = end.trim().parse::<u32>()?;
+    if start > end {
+        anyhow::bail!("range `{}` is empty", s);
+    }
+    Ok(start..=end)
+}
+
 impl InteractiveOpts {
     pub(crate) async fn run(&self) -> anyhow::Result<()> {
         let app = app::app::LucilleBuilder::new_with_user_dirs()?
@@ -57,17 +125,25 @@ impl InteractiveOpts {
             .await?;
 
         let query = self.query.join(" ");
-        let resp = setup_search(&app, self.index.as_deref(), query.as_str()).await?;
-        let (clip, range) = select::ask_user_for_clip(&app, &resp).await?;
+        let req = SearchRequest {
+            query: query.as_str(),
+            fuzzy: self.fuzzy,
+            ..Default::default()
+        };
+        let resp =
+            setup_search(&app, &self.index, &self.filter, self.rebuild_outdated, req).await?;
+        let (clip, range) = select::ask_user_for_clip(&resp).await?;
 
         let sub_range = (clip.offset + range.start)..(clip.offset + range.end);
-        let srt_uuid = app.db.get_srt_uuid_by_id(clip.srt_id).await?;
+        let srt_uuid = clip.srt_uuid;
 
         let gif_request = MakeGifRequest {
             segments: vec![SubSegment {
                 srt_uuid,
                 sub_range,
             }],
+            subtitle_language: self.subtitle_language,
+            timing: None,
         };
 
         let mut res = app::transcode::handle_make_gif_request(&app, &gif_request).await?;
@@ -79,31 +155,53 @@ impl InteractiveOpts {
     }
 }
 
+/// Run `req` against `indexes`, applying `filter` and the CLI's window and page size
 async fn setup_search(
     app: &LucilleApp,
-    index: Option<&str>,
-    query: &str,
+    indexes: &[String],
+    filter: &SearchFilterOpts,
+    rebuild_outdated: bool,
+    req: SearchRequest<'_>,
 ) -> anyhow::Result<SearchResponse> {
     log::trace!("using app: {:?}", app);
 
-    let index_uuid = if let Some(index) = index {
-        Uuid::from_str(index)
-            .with_context(|| format!("provided search index `{}` is not a valid UUID", &index))?
+    let index_uuids = if indexes.is_empty() {
+        app.latest_search_indexes().await?
     } else {
-        app.db
-            .get_search_indexes()
-            .await?
-            .into_iter()
-            .last()
-            .ok_or_else(|| anyhow::anyhow!("unable to find recent search index"))?
+        indexes
+            .iter()
+            .map(|index| {
+                Uuid::from_str(index).with_context(|| {
+                    format!("provided search index `{}` is not a valid UUID", &index)
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
     };
+    if index_uuids.is_empty() {
+        anyhow::bail!("unable to find recent search index");
+    }
 
-    let searcher = app.search_service(index_uuid)?;
-    log::info!("query: {:?}", query);
+    let mut corpus = Vec::with_capacity(filter.corpus.len());
+    for name in &filter.corpus {
+        let corpus_id = app
+            .db
+            .get_corpus_id(name)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("no corpus named `{}`", name))?;
+        corpus.push(corpus_id.get());
+    }
+
+    let searcher = app
+        .federated_search_service(&index_uuids, rebuild_outdated)
+        .await?;
+    log::info!("query: {:?}", req.query);
     let req = SearchRequest {
-        query,
         window: Some(5),
         max_responses: Some(3),
+        seasons: filter.season.clone(),
+        episodes: filter.episode.clone(),
+        corpus,
+        ..req
     };
     let resp = searcher
         .search_and_rank(req)
@@ -114,6 +212,18 @@ async fn setup_search(
 }
 
 const HIST: [&str; 6] = ["     ", "    *", "   **", "  ***", " ****", "*****"];
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The markers to wrap highlighted text in, no escape codes unless stdout is a terminal
+pub(crate) fn highlight_markers() -> (&'static str, &'static str) {
+    if atty::is(atty::Stream::Stdout) {
+        (BOLD, RESET)
+    } else {
+        ("", "")
+    }
+}
+
 impl SearchCommand {
     pub(crate) async fn run(&self) -> anyhow::Result<()> {
         let app = app::app::LucilleBuilder::new_with_user_dirs()?
@@ -123,21 +233,73 @@ impl SearchCommand {
             .await?;
 
         let query = self.query.join(" ");
-        let resp = setup_search(&app, Some(self.index.as_str()), query.as_str()).await?;
+        let req = SearchRequest {
+            query: query.as_str(),
+            fuzzy: self.fuzzy,
+            cursor: self.cursor.clone(),
+            explain: self.explain,
+            keep_duplicates: self.keep_duplicates,
+            ..Default::default()
+        };
+        let resp =
+            setup_search(&app, &self.index, &self.filter, self.rebuild_outdated, req).await?;
 
+        let (open, close) = highlight_markers();
         for clip in resp.results {
-            let (_, m) = app.db.get_episode_by_id(clip.srt_id).await?;
-            let subs = app.db.get_all_subs_for_srt(clip.srt_id).await?;
-            println!("{:?}: {}", clip.score, m);
+            let m = &clip.metadata;
+            match (&clip.corpus, clip.index) {
+                (Some(corpus), _) => println!("{:?}: [{}] {}", clip.score, corpus, m),
+                (None, Some(index)) => println!("{:?}: [{}] {}", clip.score, index, m),
+                (None, None) => println!("{:?}: {}", clip.score, m),
+            }
 
-            let base = clip.offset;
             for (offset, linescore) in clip.lines.iter().enumerate() {
                 let normalized = ((5.0 * linescore.score / clip.score) + 0.5) as usize;
-                let script = CleanSubs(&subs[base + offset..base + offset + 1]);
+                let script = linescore.marked_text(open, close);
                 println!("  ({:2}) [{}]- {}", offset, HIST[normalized], script);
             }
+            if let Some(explanation) = &clip.explanation {
+                print_explanation(clip.offset, explanation);
+            }
+        }
+        if let Some(cursor) = resp.next_cursor {
+            println!("\nmore results: --cursor {}", cursor);
         }
 
         Ok(())
     }
 }
+
+fn print_explanation(clip_offset: usize, explanation: &ClipExplanation) {
+    println!(
+        "  line totals (lines scoring <= {} are cut):",
+        explanation.min_score
+    );
+    for (offset, total) in explanation.line_totals.iter().enumerate() {
+        println!("    ({:2}) {:.4}", offset, total);
+    }
+    println!("  windows:");
+    for window in &explanation.windows {
+        // line numbers relative to the clip, as above
+        let relative = |line: usize| line as isize - clip_offset as isize;
+        let terms = window
+            .terms
+            .iter()
+            .map(|t| format!("{} ({}) {:.4}", t.term, relative(t.line), t.score))
+            .collect::<Vec<_>>();
+        println!(
+            "    ({:2})..({:2}) {} {:.4} = [{}] x {:.4}",
+            relative(window.start),
+            relative(window.start + window.len - 1),
+            if window.exact { "exact" } else { "fuzzy" },
+            window.score,
+            terms.join(" + "),
+            window.norm,
+        );
+        for term in &window.terms {
+            if let Some(e) = &term.explanation {
+                log::debug!("tantivy explanation of `{}`: {:#}", term.term, e);
+            }
+        }
+    }
+}
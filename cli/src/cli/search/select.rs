@@ -1,27 +1,15 @@
-use std::{collections::HashMap, ops::Range};
+use std::ops::Range;
 
 use anyhow::{Context, Result};
-use app::{
-    app::LucilleApp,
-    search_manager::{ClipResult, SearchResponse},
-};
-use lucille_core::{clean_sub::CleanSubs, metadata::MediaMetadata, Subtitle};
+use app::search_manager::{ClipResult, SearchResponse};
 use tokio::io::AsyncBufReadExt;
 
-const HIST: [&str; 6] = ["     ", "    *", "   **", "  ***", " ****", "*****"];
+use super::highlight_markers;
 
-pub async fn ask_user_for_clip<'a>(
-    app: &LucilleApp,
-    response: &'a SearchResponse,
-) -> Result<(&'a ClipResult, Range<usize>)> {
-    let mut content = HashMap::new();
+const HIST: [&str; 6] = ["     ", "    *", "   **", "  ***", " ****", "*****"];
 
-    for clip in &response.results {
-        let (_, m) = app.db.get_episode_by_id(clip.srt_id).await?;
-        let subs = app.db.get_all_subs_for_srt(clip.srt_id).await?;
-        content.insert(clip.srt_id, (m, subs));
-    }
-    print_top_scores(&content, response);
+pub async fn ask_user_for_clip(response: &SearchResponse) -> Result<(&ClipResult, Range<usize>)> {
+    print_top_scores(response);
     let input = get_user_input("make a selection: e.g. 'B 3-5'").await?;
     let (index, start, end) = parse_user_selection(input.as_str())?;
     let user_clip = &response.results[index];
@@ -30,18 +18,14 @@ pub async fn ask_user_for_clip<'a>(
     Ok((user_clip, (start..end)))
 }
 
-fn print_top_scores(
-    content: &HashMap<i64, (MediaMetadata, Vec<Subtitle>)>,
-    response: &SearchResponse,
-) {
+fn print_top_scores(response: &SearchResponse) {
+    let (open, close) = highlight_markers();
     let mut c = 'A';
     for clip in &response.results {
-        let (m, subs) = &content[&clip.srt_id];
-        println!("{}) {:?}: {}", c, clip.score, m);
-        let base = clip.offset;
+        println!("{}) {:?}: {}", c, clip.score, clip.metadata);
         for (offset, linescore) in clip.lines.iter().enumerate() {
             let normalized = ((5.0 * linescore.score / clip.score) + 0.5) as usize;
-            let script = CleanSubs(&subs[base + offset..base + offset + 1]);
+            let script = linescore.marked_text(open, close);
             println!("  ({:2}) [{}]- {}", offset, HIST[normalized], script);
         }
         c = ((c as u8) + 1) as char
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use app::{lookup::LookupRequest, transcode::MakeGifRequest};
+use clap::Parser;
+use lucille_core::timing::{parse_frame_rates, TimingAdjustment};
+
+use super::{
+    argparse::{AppConfig, ChapterArgs},
+    lookup::format_timecode,
+};
+
+#[derive(Parser, Debug)]
+pub enum TimingCommand {
+    /// Show the subtitle timing correction of a chapter
+    Show(TimingShowOpts),
+    /// Correct the subtitle times of a chapter whenever they are rendered
+    Set(TimingSetOpts),
+    /// Remove the subtitle timing correction of a chapter
+    Clear(TimingClearOpts),
+    /// Render a short clip with a timing correction, without saving it
+    Preview(TimingPreviewOpts),
+}
+
+impl TimingCommand {
+    pub(crate) async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            TimingCommand::Show(args) => show_timing(args).await,
+            TimingCommand::Set(args) => set_timing(args).await,
+            TimingCommand::Clear(args) => clear_timing(args).await,
+            TimingCommand::Preview(args) => preview_timing(args).await,
+        }
+    }
+}
+
+/// A timing correction, subtitle times become `time * scale + offset`
+#[derive(Parser, Debug)]
+pub struct TimingArgs {
+    /// Seconds to shift the subtitles by, negative shows them earlier
+    #[clap(long, allow_hyphen_values = true)]
+    pub offset: Option<f64>,
+
+    /// Multiply every subtitle time by this
+    #[clap(long, conflicts_with = "fps")]
+    pub scale: Option<f64>,
+
+    /// Subtitles made for another frame rate, as `SUBTITLE_FPS:MEDIA_FPS`, e.g. `25:23.976`
+    #[clap(long, value_parser = parse_frame_rates)]
+    pub fps: Option<(f64, f64)>,
+}
+
+impl TimingArgs {
+    /// `current` with the parts of a correction given on the command line
+    /// replaced, `None` if no part of one was
+    fn merged_with(&self, current: TimingAdjustment) -> anyhow::Result<Option<TimingAdjustment>> {
+        if self.offset.is_none() && self.scale.is_none() && self.fps.is_none() {
+            return Ok(None);
+        }
+        let scale = match (self.fps, self.scale) {
+            (Some((subtitle_fps, media_fps)), _) => {
+                TimingAdjustment::frame_rates(subtitle_fps, media_fps)?.scale
+            }
+            (None, Some(scale)) => scale,
+            (None, None) => current.scale,
+        };
+        let offset_ms = match self.offset {
+            Some(offset) => (offset * 1000.0).round() as i64,
+            None => current.offset_ms,
+        };
+        Ok(Some(TimingAdjustment::new(offset_ms, scale)?))
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct TimingShowOpts {
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+#[derive(Parser, Debug)]
+pub struct TimingClearOpts {
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+#[derive(Parser, Debug)]
+pub struct TimingSetOpts {
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+    /// Parts of the correction which are not given are kept
+    #[clap(flatten)]
+    pub timing: TimingArgs,
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+#[derive(Parser, Debug)]
+pub struct TimingPreviewOpts {
+    /// The time to preview, e.g. `12:34`, `1:02:03` or `754.5`
+    #[clap(value_parser = app::lookup::parse_timecode)]
+    pub timecode: Duration,
+
+    #[clap(flatten)]
+    pub chapter: ChapterArgs,
+
+    /// The correction to preview, parts which are not given are the chapter's saved timing
+    #[clap(flatten)]
+    pub timing: TimingArgs,
+
+    /// How many lines to include before and after the timecode
+    #[clap(long, default_value_t = 1)]
+    pub context: usize,
+
+    /// output gif file
+    #[clap(long, default_value = "preview.gif")]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub cfg: AppConfig,
+}
+
+pub(crate) async fn show_timing(args: &TimingShowOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let timing = app.db.get_chapter_timing(chapter.id).await?;
+    if timing.is_identity() {
+        println!("{}: no timing correction", chapter.metadata);
+    } else {
+        println!("{}: {}", chapter.metadata, timing);
+    }
+    Ok(())
+}
+
+pub(crate) async fn set_timing(args: &TimingSetOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let current = app.db.get_chapter_timing(chapter.id).await?;
+    let timing = args
+        .timing
+        .merged_with(current)?
+        .context("give an --offset, --scale or --fps to set")?;
+    app.db.set_chapter_timing(chapter.id, timing).await?;
+    println!("{}: set timing to {}", chapter.metadata, timing);
+    Ok(())
+}
+
+pub(crate) async fn clear_timing(args: &TimingClearOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    app.db
+        .set_chapter_timing(chapter.id, TimingAdjustment::IDENTITY)
+        .await?;
+    println!("{}: cleared timing correction", chapter.metadata);
+    Ok(())
+}
+
+pub(crate) async fn preview_timing(args: &TimingPreviewOpts) -> anyhow::Result<()> {
+    let app = args.cfg.build_app().await?;
+    let selector = args.chapter.selector(&app).await?;
+    let chapter = app::lookup::find_chapter(&app, &selector).await?;
+    let saved = app.db.get_chapter_timing(chapter.id).await?;
+    let timing = args.timing.merged_with(saved)?.unwrap_or(saved);
+
+    let request = LookupRequest {
+        chapter: selector,
+        timestamp: args.timecode,
+        context: Some(args.context),
+        timing: Some(timing),
+    };
+    let clip = app::lookup::lookup_timestamp(&app, &request).await?;
+    println!("{} ({})", clip.metadata, timing);
+    for line in &clip.lines {
+        let marker = if line.score > 0.0 { ">" } else { " " };
+        println!("{} [{}] {}", marker, format_timecode(line.start), line.text);
+    }
+
+    let gif_request = MakeGifRequest {
+        segments: vec![clip.segment()],
+        subtitle_language: None,
+        timing: Some(timing),
+    };
+    let mut res = app::transcode::handle_make_gif_request(&app, &gif_request).await?;
+    let mut output = res.output();
+    let mut out_gif = tokio::fs::File::create(&args.output).await?;
+    let bytes = tokio::io::copy(&mut output, &mut out_gif).await?;
+    log::debug!("GIF is size: {}", bytes);
+    res.wait().await?;
+    println!("\npreview: {}", args.output);
+    Ok(())
+}
@@ -0,0 +1,4 @@
+-- Record how each search index was built, older indexes leave these NULL
+ALTER TABLE search_index ADD COLUMN schema_version INTEGER;
+ALTER TABLE search_index ADD COLUMN tokenizer TEXT CHECK(tokenizer <> '');
+ALTER TABLE search_index ADD COLUMN max_window INTEGER;
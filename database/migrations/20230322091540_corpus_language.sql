@@ -0,0 +1,2 @@
+-- The language used to tokenize a corpus for search, NULL means english
+ALTER TABLE corpus ADD COLUMN language TEXT CHECK(language <> '');
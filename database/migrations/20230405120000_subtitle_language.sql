@@ -0,0 +1,2 @@
+-- The language of a subtitle track, NULL means the language of its corpus
+ALTER TABLE srtfile ADD COLUMN language TEXT CHECK(language <> '');
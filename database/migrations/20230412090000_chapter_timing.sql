@@ -0,0 +1,3 @@
+-- A correction for the times of a chapter's subtitles, `time * scale + offset`
+ALTER TABLE chapter ADD COLUMN timing_offset_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE chapter ADD COLUMN timing_scale REAL NOT NULL DEFAULT 1.0 CHECK(timing_scale > 0);
@@ -0,0 +1,6 @@
+-- When a subtitle file was added in UTC, NULL for files added before this was recorded
+ALTER TABLE srtfile ADD COLUMN created_at TEXT;
+-- The subtitle file that a rolled back file is a copy of
+ALTER TABLE srtfile ADD COLUMN restored_from INTEGER REFERENCES srtfile(id) ON DELETE SET NULL;
+-- An index is stale when the subtitles it covers were rolled back, until it is updated
+ALTER TABLE search_index ADD COLUMN stale BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,11 @@
+-- Untagged subtitle tracks were in the language of their corpus, record it so
+-- that they keep it when the corpus' language changes
+UPDATE srtfile
+SET language = (
+    SELECT COALESCE(corpus.language, 'en')
+    FROM chapter
+    JOIN corpus
+      ON chapter.corpus_id = corpus.id
+    WHERE chapter.id = srtfile.chapter_id
+)
+WHERE language IS NULL;
@@ -0,0 +1,3 @@
+-- Record how subtitles were cleaned before they were indexed, as a comma
+-- separated list of rule names
+ALTER TABLE search_index ADD COLUMN cleanup TEXT;
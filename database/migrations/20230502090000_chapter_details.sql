@@ -0,0 +1,6 @@
+-- What the name of a chapter's media says beyond its title, season and
+-- episode: the show, the episodes after the first of a multi-episode file as
+-- a comma separated list, and the year of a movie
+ALTER TABLE chapter ADD COLUMN show TEXT;
+ALTER TABLE chapter ADD COLUMN more_episodes TEXT;
+ALTER TABLE chapter ADD COLUMN year INTEGER;
@@ -1,10 +1,18 @@
+use std::collections::HashMap;
+
 use lucille_core::{
     export::ChapterExport,
     identifiers::{ChapterId, CorpusId},
-    metadata::MediaHash,
+    metadata::{MediaHash, MediaMetadata},
+    timing::TimingAdjustment,
 };
 
-use crate::{metadata_from_chapter, parse_media_hash, Database, DatabaseError};
+use sqlx::Row;
+
+use crate::{
+    ensure_all_found, fetch_for_ids, metadata_from_chapter, parse_media_hash, ChapterColumns,
+    Database, DatabaseError,
+};
 
 impl Database {
     pub async fn define_chapter<S: Into<String>>(
@@ -16,6 +24,39 @@ impl Database {
         hash: MediaHash,
     ) -> Result<ChapterId, DatabaseError> {
         let title = title.into();
+        self.upsert_chapter(
+            corpus_id,
+            ChapterColumns::new(&title, season, episode),
+            hash,
+        )
+        .await
+    }
+
+    /// Define the chapter of some media, with everything its metadata says
+    pub async fn define_media_chapter(
+        &self,
+        corpus_id: CorpusId,
+        metadata: &MediaMetadata,
+        hash: MediaHash,
+    ) -> Result<ChapterId, DatabaseError> {
+        self.upsert_chapter(corpus_id, ChapterColumns::from(metadata), hash)
+            .await
+    }
+
+    async fn upsert_chapter(
+        &self,
+        corpus_id: CorpusId,
+        columns: ChapterColumns<'_>,
+        hash: MediaHash,
+    ) -> Result<ChapterId, DatabaseError> {
+        let ChapterColumns {
+            title,
+            season,
+            episode,
+            show,
+            more_episodes,
+            year,
+        } = columns;
         log::trace!(
             "define chapter: C={}, title={:?}, S[{:?}] E[{:?}] {:?}",
             corpus_id,
@@ -34,14 +75,20 @@ impl Database {
                     corpus_id = ?1,
                     title = ?2,
                     season = ?3,
-                    episode = ?4
+                    episode = ?4,
+                    show = ?5,
+                    more_episodes = ?6,
+                    year = ?7
                 WHERE
-                    hash = ?5
+                    hash = ?8
             "#,
             cid,
             title,
             season,
             episode,
+            show,
+            more_episodes,
+            year,
             hash_data
         )
         .execute(&self.pool)
@@ -67,13 +114,16 @@ impl Database {
         } else {
             sqlx::query!(
                 r#"
-                    INSERT INTO chapter (corpus_id, title, season, episode, hash)
-                    VALUES ( ?1, ?2, ?3, ?4, ?5 )
+                    INSERT INTO chapter (corpus_id, title, season, episode, show, more_episodes, year, hash)
+                    VALUES ( ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8 )
                     "#,
                 cid,
                 title,
                 season,
                 episode,
+                show,
+                more_episodes,
+                year,
                 hash_data
             )
             .execute(&self.pool)
@@ -92,7 +142,7 @@ impl Database {
         let row_opt = sqlx::query!(
             r#"
                     SELECT
-                        id, corpus_id, title, season, episode
+                        id, corpus_id, title, season, episode, show, more_episodes, year
                     FROM chapter
                     WHERE
                         hash = ?
@@ -108,7 +158,14 @@ impl Database {
             Some(ChapterExport {
                 id: ChapterId::new(row.id),
                 corpus_id: CorpusId::new(row.corpus_id),
-                metadata: metadata_from_chapter(row.title, row.season, row.episode),
+                metadata: metadata_from_chapter(
+                    row.title,
+                    row.season,
+                    row.episode,
+                    row.show,
+                    row.more_episodes,
+                    row.year,
+                )?,
                 hash,
             })
         } else {
@@ -116,6 +173,53 @@ impl Database {
         })
     }
 
+    /// Find the chapter for an episode of a corpus.
+    ///
+    /// If several chapters claim the same episode, the most recent is used.
+    pub async fn get_chapter_by_episode(
+        &self,
+        corpus_id: CorpusId,
+        season: i64,
+        episode: i64,
+    ) -> Result<Option<ChapterExport>, DatabaseError> {
+        let cid = corpus_id.get();
+        let row_opt = sqlx::query!(
+            r#"
+                    SELECT
+                        id, corpus_id, title, season, episode, show, more_episodes, year, hash
+                    FROM chapter
+                    WHERE
+                        corpus_id = ? AND season = ? AND episode = ?
+                    ORDER BY
+                        id DESC
+                    LIMIT 1
+                    "#,
+            cid,
+            season,
+            episode,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(if let Some(row) = row_opt {
+            Some(ChapterExport {
+                id: ChapterId::new(row.id),
+                corpus_id: CorpusId::new(row.corpus_id),
+                metadata: metadata_from_chapter(
+                    row.title,
+                    row.season,
+                    row.episode,
+                    row.show,
+                    row.more_episodes,
+                    row.year,
+                )?,
+                hash: parse_media_hash(&row.hash)?,
+            })
+        } else {
+            None
+        })
+    }
+
     pub async fn get_chapter_by_id(
         &self,
         chapter_id: ChapterId,
@@ -124,7 +228,7 @@ impl Database {
         let row = sqlx::query!(
             r#"
                     SELECT
-                        id, corpus_id, title, season, episode, hash
+                        id, corpus_id, title, season, episode, show, more_episodes, year, hash
                     FROM chapter
                     WHERE
                         id = ?
@@ -137,7 +241,14 @@ impl Database {
         Ok(ChapterExport {
             id: ChapterId::new(row.id),
             corpus_id: CorpusId::new(row.corpus_id),
-            metadata: metadata_from_chapter(row.title, row.season, row.episode),
+            metadata: metadata_from_chapter(
+                row.title,
+                row.season,
+                row.episode,
+                row.show,
+                row.more_episodes,
+                row.year,
+            )?,
             hash: parse_media_hash(&row.hash)?,
         })
     }
@@ -150,7 +261,7 @@ impl Database {
         let rows = sqlx::query!(
             r#"
                     SELECT
-                        id, corpus_id, title, season, episode, hash
+                        id, corpus_id, title, season, episode, show, more_episodes, year, hash
                     FROM chapter
                     WHERE
                         corpus_id = ?
@@ -165,18 +276,99 @@ impl Database {
             chapters.push(ChapterExport {
                 id: ChapterId::new(row.id),
                 corpus_id: CorpusId::new(row.corpus_id),
-                metadata: metadata_from_chapter(row.title, row.season, row.episode),
+                metadata: metadata_from_chapter(
+                    row.title,
+                    row.season,
+                    row.episode,
+                    row.show,
+                    row.more_episodes,
+                    row.year,
+                )?,
                 hash: parse_media_hash(&row.hash)?,
             });
         }
         Ok(chapters)
     }
+
+    /// The correction for the times of a chapter's subtitles, the identity if none was set
+    pub async fn get_chapter_timing(
+        &self,
+        chapter_id: ChapterId,
+    ) -> Result<TimingAdjustment, DatabaseError> {
+        let cid = chapter_id.get();
+        let row = sqlx::query!(
+            r#"
+                    SELECT
+                        timing_offset_ms, timing_scale
+                    FROM chapter
+                    WHERE
+                        id = ?
+                    "#,
+            cid,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        TimingAdjustment::new(row.timing_offset_ms, row.timing_scale)
+            .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e)))
+    }
+
+    /// The timing correction of the chapter of each srt, by srt id
+    pub async fn get_chapter_timings_for_srts(
+        &self,
+        srt_ids: &[i64],
+    ) -> Result<HashMap<i64, TimingAdjustment>, DatabaseError> {
+        let rows = fetch_for_ids(
+            &self.pool,
+            "SELECT srtfile.id, chapter.timing_offset_ms, chapter.timing_scale FROM srtfile JOIN chapter ON srtfile.chapter_id = chapter.id WHERE srtfile.id IN",
+            srt_ids,
+        )
+        .await?;
+        let mut timings = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let timing = TimingAdjustment::new(
+                row.try_get("timing_offset_ms")?,
+                row.try_get("timing_scale")?,
+            )
+            .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e)))?;
+            timings.insert(row.try_get("id")?, timing);
+        }
+        ensure_all_found(srt_ids, &timings)?;
+        Ok(timings)
+    }
+
+    pub async fn set_chapter_timing(
+        &self,
+        chapter_id: ChapterId,
+        timing: TimingAdjustment,
+    ) -> Result<(), DatabaseError> {
+        let cid = chapter_id.get();
+        let updated = sqlx::query!(
+            r#"
+                    UPDATE chapter
+                    SET timing_offset_ms = ?, timing_scale = ?
+                    WHERE id = ?
+                    "#,
+            timing.offset_ms,
+            timing.scale,
+            cid,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
 
-    use lucille_core::{identifiers::ChapterId, metadata::MediaHash};
+    use lucille_core::{
+        identifiers::ChapterId,
+        metadata::{EpisodeMetadata, MediaHash},
+    };
 
     use super::*;
     use crate::database_test::assert_err_is_constraint;
@@ -215,6 +407,63 @@ mod test {
         assert_eq!(id, ChapterId::new(1))
     }
 
+    #[tokio::test]
+    async fn define_media_chapters() {
+        let db = Database::memory().await.unwrap();
+        let c = db.add_corpus("media").await.unwrap().id.unwrap();
+        let cases = [
+            MediaMetadata::Episode(EpisodeMetadata {
+                show: Some("Show".to_string()),
+                season: Some(2),
+                episode: 3,
+                more_episodes: vec![4, 5],
+                title: "Finale".to_string(),
+            }),
+            MediaMetadata::Episode(EpisodeMetadata {
+                show: Some("One Piece".to_string()),
+                season: None,
+                episode: 1015,
+                more_episodes: vec![],
+                title: "The Supernova".to_string(),
+            }),
+            MediaMetadata::Movie {
+                title: "The Movie".to_string(),
+                year: 2004,
+            },
+            MediaMetadata::Unknown("home video".to_string()),
+        ];
+        for (i, metadata) in cases.into_iter().enumerate() {
+            let hash = MediaHash::from_bytes(&[i as u8]);
+            let id = db.define_media_chapter(c, &metadata, hash).await.unwrap();
+            let chapter = db.get_chapter_by_id(id).await.unwrap();
+            assert_eq!(chapter.metadata, metadata);
+        }
+    }
+
+    #[tokio::test]
+    async fn absolute_episodes_are_not_in_season_one() {
+        let db = Database::memory().await.unwrap();
+        let c = db.add_corpus("media").await.unwrap().id.unwrap();
+        let absolute = MediaMetadata::Episode(EpisodeMetadata {
+            show: None,
+            season: None,
+            episode: 12,
+            more_episodes: vec![],
+            title: "absolute".to_string(),
+        });
+        db.define_media_chapter(c, &absolute, MediaHash::from_bytes(b"absolute"))
+            .await
+            .unwrap();
+        let s01e12 = MediaMetadata::Episode(EpisodeMetadata::new(1, 12, "S01E12"));
+        db.define_media_chapter(c, &s01e12, MediaHash::from_bytes(b"s01e12"))
+            .await
+            .unwrap();
+
+        let found = db.get_chapter_by_episode(c, 1, 12).await.unwrap().unwrap();
+        assert_eq!(found.metadata, s01e12);
+        assert_eq!(db.get_active_chapters_for_corpus(c).await.unwrap().len(), 2);
+    }
+
     #[tokio::test]
     async fn define_chapter_without_title() {
         let db = Database::memory().await.unwrap();
@@ -412,4 +661,68 @@ mod test {
         assert_eq!(chapters[0].id, c1id1);
         assert_eq!(chapters[1].id, c1id2);
     }
+
+    #[tokio::test]
+    async fn get_chapter_by_episode() {
+        let db = Database::memory().await.unwrap();
+        let c1 = db.add_corpus("media").await.unwrap().id.unwrap();
+        let c2 = db.add_corpus("other").await.unwrap().id.unwrap();
+        let hash = |s: &str| MediaHash::from_bytes(s.as_bytes());
+        let s1e1 = db
+            .define_chapter(c1, "title1", Some(1), Some(1), hash("a"))
+            .await
+            .unwrap();
+        let s1e2 = db
+            .define_chapter(c1, "title2", Some(1), Some(2), hash("b"))
+            .await
+            .unwrap();
+        db.define_chapter(c2, "title1", Some(1), Some(1), hash("c"))
+            .await
+            .unwrap();
+
+        let found = db.get_chapter_by_episode(c1, 1, 1).await.unwrap();
+        assert_eq!(found.map(|c| c.id), Some(s1e1));
+        let found = db.get_chapter_by_episode(c1, 1, 2).await.unwrap();
+        assert_eq!(found.map(|c| c.id), Some(s1e2));
+        let found = db.get_chapter_by_episode(c1, 2, 1).await.unwrap();
+        assert!(found.is_none());
+    }
+
+    #[tokio::test]
+    async fn chapter_timing() {
+        let db = Database::memory().await.unwrap();
+        let c = db.add_corpus("media").await.unwrap().id.unwrap();
+        let hash = MediaHash::from_bytes(b"data");
+        let id = db
+            .define_chapter(c, "title", Some(1), Some(1), hash)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_chapter_timing(id).await.unwrap(),
+            TimingAdjustment::IDENTITY
+        );
+
+        let timing = TimingAdjustment::new(-1500, 25.0 / 23.976).unwrap();
+        db.set_chapter_timing(id, timing).await.unwrap();
+        assert_eq!(db.get_chapter_timing(id).await.unwrap(), timing);
+        let srt_uuid = db.add_subtitles(id, &[]).await.unwrap();
+        let srt_id = db.get_srt_id_by_uuid(srt_uuid).await.unwrap();
+        let timings = db.get_chapter_timings_for_srts(&[srt_id]).await.unwrap();
+        assert_eq!(timings[&srt_id], timing);
+        assert!(db
+            .get_chapter_timings_for_srts(&[srt_id, 99])
+            .await
+            .is_err());
+
+        // redefining the chapter keeps its timing
+        db.define_chapter(c, "renamed", Some(1), Some(1), hash)
+            .await
+            .unwrap();
+        assert_eq!(db.get_chapter_timing(id).await.unwrap(), timing);
+
+        assert!(db
+            .set_chapter_timing(ChapterId::new(99), timing)
+            .await
+            .is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use futures::TryStreamExt;
-use lucille_core::{identifiers::CorpusId, Corpus};
+use lucille_core::{identifiers::CorpusId, language::Language, Corpus};
 
 use crate::{Database, DatabaseError};
 
@@ -78,6 +78,56 @@ impl Database {
         })
     }
 
+    /// The language a corpus is tokenized with for search, english if none was set
+    pub async fn get_corpus_language(&self, id: CorpusId) -> Result<Language, DatabaseError> {
+        let cid = id.get();
+        let language = sqlx::query!(
+            r#"
+            SELECT 
+                language
+            FROM 
+                corpus
+            WHERE
+                id = ?
+         "#,
+            cid
+        )
+        .map(|r| r.language)
+        .fetch_one(&self.pool)
+        .await?;
+        match language {
+            Some(code) => code
+                .parse()
+                .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e))),
+            None => Ok(Language::default()),
+        }
+    }
+
+    pub async fn set_corpus_language(
+        &self,
+        id: CorpusId,
+        language: Language,
+    ) -> Result<(), DatabaseError> {
+        let cid = id.get();
+        let code = language.code();
+        let updated = sqlx::query!(
+            r#"
+            UPDATE corpus
+            SET language = ?
+            WHERE id = ?
+         "#,
+            code,
+            cid
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
+        Ok(())
+    }
+
     pub async fn list_corpus(&self) -> Result<Vec<Corpus>, DatabaseError> {
         let rows = sqlx::query!(
             r#"
@@ -158,6 +208,19 @@ mod test {
         assert_eq!(c2, c2_2);
     }
 
+    #[tokio::test]
+    async fn corpus_language() {
+        let db = Database::memory().await.unwrap();
+        let id = db.add_corpus("media").await.unwrap().id.unwrap();
+        assert_eq!(db.get_corpus_language(id).await.unwrap(), Language::English);
+        db.set_corpus_language(id, Language::Spanish).await.unwrap();
+        assert_eq!(db.get_corpus_language(id).await.unwrap(), Language::Spanish);
+        assert!(db
+            .set_corpus_language(CorpusId::new(99), Language::French)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn list_all_corpus() {
         let db = Database::memory().await.unwrap();
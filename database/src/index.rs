@@ -1,8 +1,14 @@
 use std::collections::HashSet;
 
 use futures::TryStreamExt;
-use lucille_core::uuid::Uuid;
-use sqlx::{QueryBuilder, Sqlite};
+use lucille_core::{
+    clean_sub::CleanupRule,
+    identifiers::{ChapterId, CorpusId},
+    language::Language,
+    uuid::Uuid,
+    Corpus, SearchIndexInfo,
+};
+use sqlx::{QueryBuilder, Sqlite, Transaction};
 
 use crate::{parse_uuid, Database, DatabaseError};
 
@@ -11,6 +17,7 @@ impl Database {
         &self,
         index_uuid: Uuid,
         srts: HashSet<i64>,
+        info: &SearchIndexInfo,
     ) -> Result<(), DatabaseError> {
         log::debug!(
             "associating {} srt files with search index {}",
@@ -18,14 +25,22 @@ impl Database {
             index_uuid
         );
         let uuid = index_uuid.to_string();
+        let schema_version = info.schema_version;
+        let max_window = info.max_window as i64;
+        let cleanup = cleanup_names(&info.cleanup);
+        let mut tx = self.pool.begin().await?;
         let id = sqlx::query!(
             r#"
-                    INSERT INTO search_index (uuid)
-                    VALUES ( ?1 )
+                    INSERT INTO search_index (uuid, schema_version, tokenizer, max_window, cleanup)
+                    VALUES ( ?1, ?2, ?3, ?4, ?5 )
                     "#,
-            uuid
+            uuid,
+            schema_version,
+            info.tokenizer,
+            max_window,
+            cleanup,
         )
-        .execute(&self.pool)
+        .execute(&mut tx)
         .await?
         .last_insert_rowid();
 
@@ -37,7 +52,8 @@ impl Database {
         });
         let query = insert_builder.build();
 
-        query.execute(&self.pool).await?;
+        query.execute(&mut tx).await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -62,7 +78,700 @@ impl Database {
         }
         Ok(results)
     }
+
+    /// Every search index, newest first, with the corpus it covers if any
+    pub async fn get_search_indexes_with_corpus(
+        &self,
+    ) -> Result<Vec<(Uuid, Option<CorpusId>)>, DatabaseError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    search_index.uuid,
+                    (
+                        SELECT
+                            chapter.corpus_id
+                        FROM search_assoc
+                        JOIN srtfile
+                          ON search_assoc.srt_id = srtfile.id
+                        JOIN chapter
+                          ON srtfile.chapter_id = chapter.id
+                        WHERE
+                          search_assoc.search_index_id = search_index.id
+                        LIMIT 1
+                    ) AS corpus_id
+                FROM search_index
+                ORDER BY
+                    search_index.id DESC
+         "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|r| Ok((parse_uuid(&r.uuid)?, r.corpus_id.map(CorpusId::new))))
+            .collect()
+    }
+
+    /// Forget a search index, the index directory must be removed separately
+    pub async fn delete_search_index(&self, index_uuid: Uuid) -> Result<(), DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+                DELETE FROM search_assoc
+                WHERE
+                  search_index_id IN (SELECT id FROM search_index WHERE uuid = ?)
+                "#,
+            uuid,
+        )
+        .execute(&mut tx)
+        .await?;
+        let deleted = sqlx::query!(
+            r#"
+                DELETE FROM search_index
+                WHERE
+                  uuid = ?
+                "#,
+            uuid,
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(sqlx::Error::RowNotFound.into());
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// How a search index was built, `None` if it was recorded before this was tracked
+    pub async fn get_search_index_info(
+        &self,
+        index_uuid: Uuid,
+    ) -> Result<Option<SearchIndexInfo>, DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    schema_version, tokenizer, max_window, cleanup
+                FROM search_index
+                WHERE
+                  uuid = ?
+         "#,
+            uuid,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(match (row.schema_version, row.tokenizer, row.max_window) {
+            (Some(schema_version), Some(tokenizer), Some(max_window)) => Some(SearchIndexInfo {
+                schema_version: schema_version as u32,
+                tokenizer,
+                max_window: max_window as usize,
+                cleanup: parse_cleanup(row.cleanup.as_deref())?,
+            }),
+            _ => None,
+        })
+    }
+
+    /// The most recently created search index which covers any of this corpus
+    pub async fn get_latest_search_index_for_corpus(
+        &self,
+        corpus_id: CorpusId,
+    ) -> Result<Option<Uuid>, DatabaseError> {
+        let cid = corpus_id.get();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    search_index.uuid
+                FROM search_index
+                JOIN search_assoc
+                  ON search_assoc.search_index_id = search_index.id
+                JOIN srtfile
+                  ON search_assoc.srt_id = srtfile.id
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                WHERE
+                  chapter.corpus_id = ?
+                ORDER BY
+                  search_index.id DESC
+                LIMIT 1
+         "#,
+            cid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.and_then(|r| r.uuid)
+            .map(|uuid| parse_uuid(&uuid))
+            .transpose()
+    }
+
+    /// The most recently created search index which covers any of this corpus'
+    /// subtitles in `language`
+    pub async fn get_latest_search_index_for_corpus_language(
+        &self,
+        corpus_id: CorpusId,
+        language: Language,
+    ) -> Result<Option<Uuid>, DatabaseError> {
+        let cid = corpus_id.get();
+        let code = language.code();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    search_index.uuid
+                FROM search_index
+                JOIN search_assoc
+                  ON search_assoc.search_index_id = search_index.id
+                JOIN srtfile
+                  ON search_assoc.srt_id = srtfile.id
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  chapter.corpus_id = ?1 AND
+                  COALESCE(srtfile.language, corpus.language, 'en') = ?2
+                ORDER BY
+                  search_index.id DESC
+                LIMIT 1
+         "#,
+            cid,
+            code,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.and_then(|r| r.uuid)
+            .map(|uuid| parse_uuid(&uuid))
+            .transpose()
+    }
+
+    /// The language of the subtitles a search index was built from, if it covers any
+    pub async fn get_search_index_language(
+        &self,
+        index_uuid: Uuid,
+    ) -> Result<Option<Language>, DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    COALESCE(srtfile.language, corpus.language, 'en') AS "language!: String"
+                FROM search_index
+                JOIN search_assoc
+                  ON search_assoc.search_index_id = search_index.id
+                JOIN srtfile
+                  ON search_assoc.srt_id = srtfile.id
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  search_index.uuid = ?
+                LIMIT 1
+         "#,
+            uuid,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| {
+            r.language
+                .parse()
+                .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e)))
+        })
+        .transpose()
+    }
+
+    /// The corpus whose subtitles were used to build a search index
+    pub async fn get_corpus_for_search_index(
+        &self,
+        index_uuid: Uuid,
+    ) -> Result<Option<Corpus>, DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    corpus.id, corpus.title
+                FROM search_index
+                JOIN search_assoc
+                  ON search_assoc.search_index_id = search_index.id
+                JOIN srtfile
+                  ON search_assoc.srt_id = srtfile.id
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  search_index.uuid = ?
+                LIMIT 1
+         "#,
+            uuid,
+        )
+        .map(|r| Corpus {
+            id: Some(CorpusId::new(r.id)),
+            title: r.title,
+        })
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    pub async fn get_srts_for_search_index(
+        &self,
+        index_uuid: Uuid,
+    ) -> Result<HashSet<i64>, DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    search_assoc.srt_id
+                FROM search_assoc
+                JOIN search_index
+                  ON search_assoc.search_index_id = search_index.id
+                WHERE
+                  search_index.uuid = ?
+         "#,
+            uuid,
+        )
+        .map(|r| r.srt_id)
+        .fetch(&self.pool);
+
+        Ok(rows.try_collect().await?)
+    }
+
+    /// Mark every search index which covers subtitles of a chapter in
+    /// `language`, or its corpus' language if `None`, as stale
+    pub(crate) async fn mark_search_indexes_stale_for_track(
+        tx: &mut Transaction<'_, Sqlite>,
+        chapter_id: ChapterId,
+        language: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let cid = chapter_id.get();
+        let marked = sqlx::query!(
+            r#"
+                UPDATE search_index
+                SET stale = TRUE
+                WHERE id IN (
+                    SELECT search_assoc.search_index_id
+                    FROM search_assoc
+                    JOIN srtfile
+                      ON search_assoc.srt_id = srtfile.id
+                    JOIN chapter
+                      ON srtfile.chapter_id = chapter.id
+                    JOIN corpus
+                      ON chapter.corpus_id = corpus.id
+                    WHERE
+                      srtfile.chapter_id = ?1 AND
+                      COALESCE(srtfile.language, corpus.language, 'en') = COALESCE(?2, corpus.language, 'en')
+                )
+         "#,
+            cid,
+            language,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        log::debug!("marked {} search indexes stale for {}", marked, chapter_id);
+        Ok(())
+    }
+
+    /// Search indexes covering subtitles which were rolled back since they were built
+    pub async fn get_stale_search_indexes(&self) -> Result<Vec<Uuid>, DatabaseError> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    uuid
+                FROM search_index
+                WHERE
+                  stale
+                ORDER BY
+                    id
+         "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(|r| parse_uuid(&r.uuid)).collect()
+    }
+
+    /// Like [`Database::get_stale_search_indexes`], but only those covering this corpus
+    pub async fn get_stale_search_indexes_for_corpus(
+        &self,
+        corpus_id: CorpusId,
+    ) -> Result<Vec<Uuid>, DatabaseError> {
+        let cid = corpus_id.get();
+        let rows = sqlx::query!(
+            r#"
+                SELECT DISTINCT
+                    search_index.id, search_index.uuid
+                FROM search_index
+                JOIN search_assoc
+                  ON search_assoc.search_index_id = search_index.id
+                JOIN srtfile
+                  ON search_assoc.srt_id = srtfile.id
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                WHERE
+                  search_index.stale AND
+                  chapter.corpus_id = ?
+                ORDER BY
+                    search_index.id
+         "#,
+            cid,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(|r| parse_uuid(&r.uuid)).collect()
+    }
+
+    pub async fn is_search_index_stale(&self, index_uuid: Uuid) -> Result<bool, DatabaseError> {
+        let uuid = index_uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    stale
+                FROM search_index
+                WHERE
+                  uuid = ?
+         "#,
+            uuid,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.stale)
+    }
+
+    /// Record that srts were added to, or removed from, an existing search
+    /// index, which brings a stale index up to date, and how the index is
+    /// now built.
+    ///
+    /// The changes are written in a transaction which is only committed once
+    /// `commit_index` has committed the same changes to the index itself. If
+    /// anything fails before then, neither the database nor the index changes.
+    pub async fn update_index_assoc<E: From<DatabaseError>>(
+        &self,
+        index_uuid: Uuid,
+        added: &HashSet<i64>,
+        removed: &HashSet<i64>,
+        info: &SearchIndexInfo,
+        commit_index: impl FnOnce() -> Result<(), E>,
+    ) -> Result<(), E> {
+        log::debug!(
+            "updating search index {}: adding {} srt files, removing {}",
+            index_uuid,
+            added.len(),
+            removed.len()
+        );
+        let uuid = index_uuid.to_string();
+        let mut tx = self.pool.begin().await.map_err(DatabaseError::from)?;
+        let id = sqlx::query!(
+            r#"
+                SELECT
+                    id
+                FROM search_index
+                WHERE
+                  uuid = ?
+         "#,
+            uuid,
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(DatabaseError::from)?
+        .id;
+
+        for srt in removed {
+            sqlx::query!(
+                r#"
+                    DELETE FROM search_assoc
+                    WHERE
+                      search_index_id = ?1 AND
+                      srt_id = ?2
+                    "#,
+                id,
+                srt,
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(DatabaseError::from)?;
+        }
+
+        if !added.is_empty() {
+            let mut insert_builder: QueryBuilder<Sqlite> =
+                QueryBuilder::new(r#"INSERT INTO search_assoc (search_index_id, srt_id)"#);
+
+            insert_builder.push_values(added.iter(), |mut b, srt| {
+                b.push_bind(id).push_bind(srt);
+            });
+            insert_builder
+                .build()
+                .execute(&mut tx)
+                .await
+                .map_err(DatabaseError::from)?;
+        }
+
+        let schema_version = info.schema_version;
+        let max_window = info.max_window as i64;
+        let cleanup = cleanup_names(&info.cleanup);
+        sqlx::query!(
+            r#"
+                    UPDATE search_index
+                    SET
+                      stale = FALSE,
+                      schema_version = ?,
+                      tokenizer = ?,
+                      max_window = ?,
+                      cleanup = ?
+                    WHERE id = ?
+                    "#,
+            schema_version,
+            info.tokenizer,
+            max_window,
+            cleanup,
+            id,
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(DatabaseError::from)?;
+
+        commit_index()?;
+        tx.commit().await.map_err(DatabaseError::from)?;
+        Ok(())
+    }
+}
+
+/// Cleanup rules are stored by name, e.g. `markup,sound-descriptions`
+fn cleanup_names(rules: &[CleanupRule]) -> String {
+    rules
+        .iter()
+        .map(|rule| rule.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Indexes recorded before cleanup rules were tracked have none
+fn parse_cleanup(names: Option<&str>) -> Result<Vec<CleanupRule>, DatabaseError> {
+    names
+        .unwrap_or_default()
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            name.parse()
+                .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e)))
+        })
+        .collect()
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use lucille_core::metadata::MediaHash;
+
+    use super::*;
+
+    fn info() -> SearchIndexInfo {
+        SearchIndexInfo {
+            schema_version: 1,
+            tokenizer: "en_stem".to_string(),
+            max_window: 5,
+            cleanup: vec![CleanupRule::Markup, CleanupRule::SoundDescriptions],
+        }
+    }
+
+    async fn add_srt(db: &Database, corpus_id: CorpusId, name: &str) -> i64 {
+        let ch_id = db
+            .define_chapter(
+                corpus_id,
+                name,
+                None,
+                None,
+                MediaHash::from_bytes(name.as_bytes()),
+            )
+            .await
+            .unwrap();
+        let subs = lucille_core::test_util::generate_subtitle(&[name]);
+        db.add_subtitles(ch_id, &subs).await.unwrap();
+        db.lookup_latest_sub_for_chapter(ch_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+    }
+
+    #[tokio::test]
+    async fn no_index_for_corpus() {
+        let db = Database::memory().await.unwrap();
+        let corpus = db.add_corpus("media").await.unwrap();
+        let index = db
+            .get_latest_search_index_for_corpus(corpus.id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(index, None);
+    }
+
+    #[tokio::test]
+    async fn latest_index_for_corpus() {
+        let db = Database::memory().await.unwrap();
+        let c1 = db.add_corpus("media").await.unwrap().id.unwrap();
+        let c2 = db.add_corpus("media2").await.unwrap().id.unwrap();
+        let s1 = add_srt(&db, c1, "c1").await;
+        let s2 = add_srt(&db, c2, "c2").await;
+
+        let (u1, u2, u3) = (Uuid::generate(), Uuid::generate(), Uuid::generate());
+        db.assoc_index_with_srts(u1, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+        db.assoc_index_with_srts(u2, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+        db.assoc_index_with_srts(u3, HashSet::from([s2]), &info())
+            .await
+            .unwrap();
+
+        let latest = db.get_latest_search_index_for_corpus(c1).await.unwrap();
+        assert_eq!(latest, Some(u2));
+        let latest = db.get_latest_search_index_for_corpus(c2).await.unwrap();
+        assert_eq!(latest, Some(u3));
+    }
+
+    #[tokio::test]
+    async fn list_and_delete_indexes() {
+        let db = Database::memory().await.unwrap();
+        let c1 = db.add_corpus("media").await.unwrap().id.unwrap();
+        let c2 = db.add_corpus("media2").await.unwrap().id.unwrap();
+        let s1 = add_srt(&db, c1, "c1").await;
+        let s2 = add_srt(&db, c2, "c2").await;
+
+        let (u1, u2, u3) = (Uuid::generate(), Uuid::generate(), Uuid::generate());
+        db.assoc_index_with_srts(u1, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+        db.assoc_index_with_srts(u2, HashSet::from([s2]), &info())
+            .await
+            .unwrap();
+        db.assoc_index_with_srts(u3, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+
+        let indexes = db.get_search_indexes_with_corpus().await.unwrap();
+        assert_eq!(
+            indexes,
+            vec![(u3, Some(c1)), (u2, Some(c2)), (u1, Some(c1))]
+        );
+
+        db.delete_search_index(u3).await.unwrap();
+        assert_eq!(
+            db.get_latest_search_index_for_corpus(c1).await.unwrap(),
+            Some(u1)
+        );
+        assert!(db.get_srts_for_search_index(u3).await.unwrap().is_empty());
+        assert!(db.delete_search_index(u3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn lookup_corpus_for_index() {
+        let db = Database::memory().await.unwrap();
+        let c1 = db.add_corpus("media").await.unwrap();
+        let c2 = db.add_corpus("media2").await.unwrap();
+        let s1 = add_srt(&db, c1.id.unwrap(), "c1").await;
+        let s2 = add_srt(&db, c2.id.unwrap(), "c2").await;
+
+        let (u1, u2) = (Uuid::generate(), Uuid::generate());
+        db.assoc_index_with_srts(u1, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+        db.assoc_index_with_srts(u2, HashSet::from([s2]), &info())
+            .await
+            .unwrap();
+
+        assert_eq!(db.get_corpus_for_search_index(u1).await.unwrap(), Some(c1));
+        assert_eq!(db.get_corpus_for_search_index(u2).await.unwrap(), Some(c2));
+        assert_eq!(
+            db.get_corpus_for_search_index(Uuid::generate())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn update_srts_for_index() {
+        let db = Database::memory().await.unwrap();
+        let corpus = db.add_corpus("media").await.unwrap().id.unwrap();
+        let s1 = add_srt(&db, corpus, "c1").await;
+        let s2 = add_srt(&db, corpus, "c2").await;
+        let s3 = add_srt(&db, corpus, "c3").await;
+
+        let uuid = Uuid::generate();
+        db.assoc_index_with_srts(uuid, HashSet::from([s1, s2]), &info())
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_srts_for_search_index(uuid).await.unwrap(),
+            HashSet::from([s1, s2])
+        );
+
+        // nothing is recorded if the index could not be committed
+        let failed = db
+            .update_index_assoc(
+                uuid,
+                &HashSet::from([s3]),
+                &HashSet::from([s1]),
+                &info(),
+                || {
+                    Err(DatabaseError::ConvertFromSqlError(
+                        "index commit".to_string(),
+                    ))
+                },
+            )
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(
+            db.get_srts_for_search_index(uuid).await.unwrap(),
+            HashSet::from([s1, s2])
+        );
+
+        let wider = SearchIndexInfo {
+            max_window: 8,
+            ..info()
+        };
+        db.update_index_assoc(
+            uuid,
+            &HashSet::from([s3]),
+            &HashSet::from([s1]),
+            &wider,
+            || Ok::<_, DatabaseError>(()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db.get_srts_for_search_index(uuid).await.unwrap(),
+            HashSet::from([s2, s3])
+        );
+        assert_eq!(db.get_search_index_info(uuid).await.unwrap(), Some(wider));
+    }
+
+    #[tokio::test]
+    async fn index_info_is_recorded() {
+        let db = Database::memory().await.unwrap();
+        let corpus = db.add_corpus("media").await.unwrap().id.unwrap();
+        let s1 = add_srt(&db, corpus, "c1").await;
+
+        let uuid = Uuid::generate();
+        db.assoc_index_with_srts(uuid, HashSet::from([s1]), &info())
+            .await
+            .unwrap();
+        assert_eq!(db.get_search_index_info(uuid).await.unwrap(), Some(info()));
+    }
+
+    #[tokio::test]
+    async fn index_info_missing_for_old_index() {
+        let db = Database::memory().await.unwrap();
+        let uuid = Uuid::generate();
+        let uuid_str = uuid.to_string();
+        sqlx::query("INSERT INTO search_index (uuid) VALUES (?)")
+            .bind(uuid_str)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(db.get_search_index_info(uuid).await.unwrap(), None);
+    }
+}
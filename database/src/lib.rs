@@ -1,11 +1,11 @@
 #![allow(clippy::uninlined_format_args)]
-use std::{path, str::FromStr};
+use std::{collections::HashMap, path, str::FromStr};
 
 use lucille_core::{
     metadata::{EpisodeMetadata, MediaHash, MediaMetadata},
     uuid::Uuid,
 };
-use sqlx::{Pool, Sqlite};
+use sqlx::{sqlite::SqliteRow, Pool, QueryBuilder, Sqlite};
 
 pub use self::build::{
     DatabaseBuider, DatabaseConnectState, DatabaseSource, LucilleDbConnectOptions, MigrationRecord,
@@ -154,18 +154,92 @@ fn metadata_from_chapter(
     title: String,
     season: Option<i64>,
     episode: Option<i64>,
-) -> MediaMetadata {
-    if let Some((s, e)) = season.zip(episode) {
-        MediaMetadata::Episode(EpisodeMetadata {
-            title,
-            season: s as u32,
+    show: Option<String>,
+    more_episodes: Option<String>,
+    year: Option<i64>,
+) -> Result<MediaMetadata, DatabaseError> {
+    Ok(match (episode, year) {
+        (Some(e), _) => MediaMetadata::Episode(EpisodeMetadata {
+            show,
+            season: season.map(|s| s as u32),
             episode: e as u32,
-        })
-    } else {
-        MediaMetadata::Unknown(title)
+            more_episodes: parse_episodes(more_episodes.as_deref())?,
+            title,
+        }),
+        (None, Some(year)) => MediaMetadata::Movie {
+            title,
+            year: year as u32,
+        },
+        (None, None) => MediaMetadata::Unknown(title),
+    })
+}
+
+/// The columns of a chapter which describe its media
+struct ChapterColumns<'a> {
+    title: &'a str,
+    season: Option<i64>,
+    episode: Option<i64>,
+    show: Option<&'a str>,
+    more_episodes: Option<String>,
+    year: Option<i64>,
+}
+
+impl<'a> ChapterColumns<'a> {
+    fn new(title: &'a str, season: Option<i64>, episode: Option<i64>) -> Self {
+        ChapterColumns {
+            title,
+            season,
+            episode,
+            show: None,
+            more_episodes: None,
+            year: None,
+        }
     }
 }
 
+impl<'a> From<&'a MediaMetadata> for ChapterColumns<'a> {
+    fn from(metadata: &'a MediaMetadata) -> Self {
+        match metadata {
+            MediaMetadata::Episode(e) => ChapterColumns {
+                show: e.show.as_deref(),
+                more_episodes: episode_names(&e.more_episodes),
+                ..ChapterColumns::new(
+                    &e.title,
+                    e.season.map(i64::from),
+                    Some(i64::from(e.episode)),
+                )
+            },
+            MediaMetadata::Movie { title, year } => ChapterColumns {
+                year: Some(i64::from(*year)),
+                ..ChapterColumns::new(title, None, None)
+            },
+            MediaMetadata::Unknown(title) => ChapterColumns::new(title, None, None),
+        }
+    }
+}
+
+/// More episodes are stored as a comma separated list, e.g. `2,3`
+fn episode_names(episodes: &[u32]) -> Option<String> {
+    if episodes.is_empty() {
+        return None;
+    }
+    let names: Vec<_> = episodes.iter().map(u32::to_string).collect();
+    Some(names.join(","))
+}
+
+fn parse_episodes(names: Option<&str>) -> Result<Vec<u32>, DatabaseError> {
+    names
+        .unwrap_or_default()
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            name.parse().map_err(|e| {
+                DatabaseError::ConvertFromSqlError(format!("invalid episode {:?}: {}", name, e))
+            })
+        })
+        .collect()
+}
+
 fn parse_media_hash(text: &str) -> Result<MediaHash, DatabaseError> {
     MediaHash::from_str(text)
         .map_err(|e| DatabaseError::ConvertFromSqlError(format!("invalid hex: {:?}", e)))
@@ -176,6 +250,40 @@ fn parse_uuid(text: &str) -> Result<Uuid, DatabaseError> {
         .map_err(|e| DatabaseError::ConvertFromSqlError(format!("invalid uuid: {:?}", e)))
 }
 
+/// Fail with `RowNotFound` unless every one of `ids` is in `found`
+fn ensure_all_found<T>(ids: &[i64], found: &HashMap<i64, T>) -> Result<(), DatabaseError> {
+    if ids.iter().all(|id| found.contains_key(id)) {
+        Ok(())
+    } else {
+        Err(sqlx::Error::RowNotFound.into())
+    }
+}
+
+/// SQLite limits how many parameters a statement may bind
+const MAX_BOUND_IDS: usize = 500;
+
+/// Run `query`, which must end with `IN`, with `ids` bound as the list.
+///
+/// Long lists are split over several statements.
+async fn fetch_for_ids(
+    pool: &Pool<Sqlite>,
+    query: &str,
+    ids: &[i64],
+) -> Result<Vec<SqliteRow>, DatabaseError> {
+    let mut rows = vec![];
+    for chunk in ids.chunks(MAX_BOUND_IDS) {
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(query);
+        builder.push(" (");
+        let mut list = builder.separated(", ");
+        for id in chunk {
+            list.push_bind(*id);
+        }
+        list.push_unseparated(")");
+        rows.extend(builder.build().fetch_all(pool).await?);
+    }
+    Ok(rows)
+}
+
 #[cfg(test)]
 pub(crate) mod database_test {
     use futures::TryStreamExt;
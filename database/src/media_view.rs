@@ -1,10 +1,14 @@
+use std::collections::HashMap;
+
 use lucille_core::{
     identifiers::{ChapterId, CorpusId, MediaViewId},
     media_segment::MediaView,
     uuid::Uuid,
 };
 
-use crate::{Database, DatabaseError};
+use sqlx::Row;
+
+use crate::{fetch_for_ids, Database, DatabaseError};
 
 impl Database {
     pub async fn add_media_view<S: Into<String>>(
@@ -143,6 +147,42 @@ impl Database {
         Ok(rows)
     }
 
+    /// The media views of the chapter of each of `srt_ids`, by srt id
+    pub async fn get_media_views_for_srts(
+        &self,
+        srt_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<MediaView>>, DatabaseError> {
+        let rows = fetch_for_ids(
+            &self.pool,
+            r#"
+                SELECT
+                    srtfile.id AS srt_id,
+                    media_view.id, media_view.chapter_id, media_view.name
+                FROM media_view
+                JOIN srtfile
+                  ON srtfile.chapter_id = media_view.chapter_id
+                WHERE
+                    srtfile.id IN"#,
+            srt_ids,
+        )
+        .await?;
+        let mut views: HashMap<i64, Vec<MediaView>> =
+            srt_ids.iter().map(|id| (*id, vec![])).collect();
+        for row in rows {
+            let srt_id: i64 = row.try_get("srt_id")?;
+            views.entry(srt_id).or_default().push(MediaView {
+                id: MediaViewId::new(row.try_get("id")?),
+                chapter_id: ChapterId::new(row.try_get("chapter_id")?),
+                name: row.try_get("name")?,
+            });
+        }
+        // newest first, like `get_media_views_for_srt`
+        for v in views.values_mut() {
+            v.sort_by_key(|view| std::cmp::Reverse(view.id.get()));
+        }
+        Ok(views)
+    }
+
     /// Fetch all views for a corpus
     pub async fn get_media_views_for_corpus(
         &self,
@@ -3,65 +3,122 @@ use std::collections::{HashMap, HashSet};
 use futures::TryStreamExt;
 use lucille_core::{
     identifiers::{ChapterId, CorpusId},
+    language::Language,
     metadata::{MediaHash, MediaMetadata},
+    revision::SubtitleRevision,
     uuid::Uuid,
     ContentData, LucilleSub, Subtitle,
 };
+use sqlx::Row;
 
-use crate::{metadata_from_chapter, parse_media_hash, parse_uuid, Database, DatabaseError};
+use crate::{
+    ensure_all_found, fetch_for_ids, metadata_from_chapter, parse_media_hash, parse_uuid, Database,
+    DatabaseError,
+};
 
 fn deserialize_subtitle(data: &[u8]) -> Result<Vec<Subtitle>, DatabaseError> {
     serde_json::from_slice(data)
         .map_err(|e| DatabaseError::ConvertFromSqlError(format!("deserialize JSON: {}", e)))
 }
 
+fn parse_language(code: &str) -> Result<Language, DatabaseError> {
+    code.parse()
+        .map_err(|e| DatabaseError::ConvertFromSqlError(format!("{}", e)))
+}
+
 impl Database {
+    /// Add subtitles in the language of the chapter's corpus
     pub async fn add_subtitles(
         &self,
         chapter_id: ChapterId,
         subtitles: &[Subtitle],
     ) -> Result<Uuid, DatabaseError> {
-        if let Some(latest) = self.lookup_latest_sub_for_chapter(chapter_id).await? {
+        self.add_subtitle_track(chapter_id, None, subtitles).await
+    }
+
+    /// Add subtitles to the track of `language`, or of the corpus' language if `None`.
+    /// The file keeps that language if the corpus' language is changed later.
+    ///
+    /// A chapter has one current subtitle file per language, adding the same
+    /// subtitles as the current ones of the track returns the existing file.
+    /// After a rollback, see [`Database::restore_subtitle_revision`], adding
+    /// any earlier subtitles of the track also keeps the current file, so
+    /// scanning the sidecar which was rolled back does not undo the rollback.
+    pub async fn add_subtitle_track(
+        &self,
+        chapter_id: ChapterId,
+        language: Option<Language>,
+        subtitles: &[Subtitle],
+    ) -> Result<Uuid, DatabaseError> {
+        if let Some(latest) = self
+            .lookup_latest_sub_for_track(chapter_id, language)
+            .await?
+        {
             if latest.subs == subtitles {
                 return Ok(latest.uuid);
             }
+            if self
+                .is_rolled_back(chapter_id, language, latest.id, subtitles)
+                .await?
+            {
+                log::warn!(
+                    "keeping the rolled back subtitles of {}, roll back to a revision to change them",
+                    chapter_id
+                );
+                return Ok(latest.uuid);
+            }
         }
 
         let cid = chapter_id.get();
         let srt_uuid = Uuid::generate();
         let srt_uuid_string = srt_uuid.to_string();
+        let code = language.map(Language::code);
         let data = serde_json::to_vec(subtitles).expect("unable to serialize JSON");
         sqlx::query!(
             r#"
-                    INSERT INTO srtfile (chapter_id, uuid, data)
-                    VALUES ( ?1, ?2, ?3 )
+                    INSERT INTO srtfile (chapter_id, uuid, data, language, created_at)
+                    SELECT ?1, ?2, ?3, COALESCE(?4, corpus.language, 'en'), datetime('now')
+                    FROM chapter
+                    JOIN corpus
+                      ON chapter.corpus_id = corpus.id
+                    WHERE chapter.id = ?1
                     "#,
             cid,
             srt_uuid_string,
             data,
+            code,
         )
         .execute(&self.pool)
         .await?;
         Ok(srt_uuid)
     }
 
+    /// Add subtitles exported from another instance, keeping their uuid, to the
+    /// track of `language`, or of the corpus' language if `None`
     pub async fn import_subtitles(
         &self,
         chapter_id: ChapterId,
+        language: Option<Language>,
         srt_uuid: Uuid,
         subtitles: &[Subtitle],
     ) -> Result<Uuid, DatabaseError> {
         let cid = chapter_id.get();
         let srt_uuid_string = srt_uuid.to_string();
+        let code = language.map(Language::code);
         let data = serde_json::to_vec(subtitles).expect("unable to serialize JSON");
         let id = sqlx::query!(
             r#"
-                    INSERT INTO srtfile (chapter_id, uuid, data)
-                    VALUES ( ?1, ?2, ?3 )
+                    INSERT INTO srtfile (chapter_id, uuid, data, language, created_at)
+                    SELECT ?1, ?2, ?3, COALESCE(?4, corpus.language, 'en'), datetime('now')
+                    FROM chapter
+                    JOIN corpus
+                      ON chapter.corpus_id = corpus.id
+                    WHERE chapter.id = ?1
                     "#,
             cid,
             srt_uuid_string,
             data,
+            code,
         )
         .execute(&self.pool)
         .await?
@@ -72,19 +129,33 @@ impl Database {
         Ok(srt_uuid)
     }
 
+    /// The current subtitles of every chapter, in the corpus' language
     #[deprecated]
     pub async fn get_all_subs_for_corpus(
         &self,
         corpus_id: CorpusId,
+    ) -> Result<(HashSet<i64>, Vec<ContentData>), DatabaseError> {
+        let language = self.get_corpus_language(corpus_id).await?;
+        self.get_all_subs_for_corpus_language(corpus_id, language)
+            .await
+    }
+
+    /// The current subtitles of every chapter with a track in `language`
+    pub async fn get_all_subs_for_corpus_language(
+        &self,
+        corpus_id: CorpusId,
+        language: Language,
     ) -> Result<(HashSet<i64>, Vec<ContentData>), DatabaseError> {
         let cid = corpus_id.get();
+        let code = language.code();
 
         let mut collector = HashMap::new();
 
         let mut rows = sqlx::query!(
             r#"
             SELECT 
-                chapter.id, chapter.title, chapter.season, chapter.episode, chapter.hash
+                chapter.id, chapter.title, chapter.season, chapter.episode, chapter.show,
+                chapter.more_episodes, chapter.year, chapter.hash
             FROM 
                 chapter
             WHERE
@@ -96,14 +167,21 @@ impl Database {
             (
                 r.id,
                 r.hash,
-                metadata_from_chapter(r.title, r.season, r.episode),
+                metadata_from_chapter(
+                    r.title,
+                    r.season,
+                    r.episode,
+                    r.show,
+                    r.more_episodes,
+                    r.year,
+                ),
             )
         })
         .fetch(&self.pool);
 
         while let Some(row) = rows.try_next().await.unwrap() {
             let hash = parse_media_hash(&row.1)?;
-            collector.insert(row.0, (hash, row.2));
+            collector.insert(row.0, (hash, row.2?));
         }
 
         let mut results = Vec::with_capacity(collector.len());
@@ -119,7 +197,7 @@ impl Database {
                 JOIN chapter
                   ON srtfile.chapter_id = chapter.id
                 WHERE 
-                  chapter.corpus_id = ? AND
+                  chapter.corpus_id = ?1 AND
                   srtfile.id in
                     (
                       SELECT 
@@ -127,12 +205,17 @@ impl Database {
                       FROM srtfile
                       JOIN chapter
                         ON srtfile.chapter_id = chapter.id
+                      JOIN corpus
+                        ON chapter.corpus_id = corpus.id
+                      WHERE
+                        COALESCE(srtfile.language, corpus.language, 'en') = ?2
                       GROUP BY chapter.id
                     )
                 ORDER BY
                   srtfile.id ASC
          "#,
-            cid
+            cid,
+            code,
         )
         // .map(|r| (r.id, metadata_from_chapter(r.title, r.season, r.episode)))
         .fetch(&self.pool);
@@ -162,7 +245,12 @@ impl Database {
         }
 
         for (id, metadata) in collector {
-            log::warn!("no subtitles found for chapter_id={}: {:?}", id, metadata);
+            log::warn!(
+                "no {} subtitles found for chapter_id={}: {:?}",
+                language,
+                id,
+                metadata
+            );
         }
 
         Ok((collected_srts, results))
@@ -187,6 +275,26 @@ impl Database {
         Ok(subs)
     }
 
+    /// The subtitles of each of `srt_ids`, by srt id, failing if any is missing
+    pub async fn get_all_subs_for_srts(
+        &self,
+        srt_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<Subtitle>>, DatabaseError> {
+        let rows = fetch_for_ids(
+            &self.pool,
+            "SELECT srtfile.id, srtfile.data FROM srtfile WHERE srtfile.id IN",
+            srt_ids,
+        )
+        .await?;
+        let mut subs = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let data: Vec<u8> = row.try_get("data")?;
+            subs.insert(row.try_get("id")?, deserialize_subtitle(&data)?);
+        }
+        ensure_all_found(srt_ids, &subs)?;
+        Ok(subs)
+    }
+
     pub async fn get_all_subs_for_srt_by_uuid(
         &self,
         uuid: Uuid,
@@ -210,6 +318,8 @@ impl Database {
         Ok(subs)
     }
 
+    /// The current subtitles of a chapter in its corpus' language, or of
+    /// another language if there are none
     pub async fn lookup_latest_sub_for_chapter(
         &self,
         chapter_id: ChapterId,
@@ -218,11 +328,17 @@ impl Database {
         let opt_row = sqlx::query!(
             r#"
                 SELECT
-                    srtfile.id, srtfile.uuid, srtfile.data
+                    srtfile.id AS "id!", srtfile.uuid AS "uuid!", srtfile.data AS "data!"
                 FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
                 WHERE
                   srtfile.chapter_id = ?
-                ORDER BY srtfile.id DESC
+                ORDER BY
+                  COALESCE(srtfile.language, corpus.language, 'en') = COALESCE(corpus.language, 'en') DESC,
+                  srtfile.id DESC
                 LIMIT 1
          "#,
             ch_id,
@@ -244,6 +360,291 @@ impl Database {
         }
     }
 
+    /// Whether `latest` was restored by a rollback, and `subtitles` are an
+    /// earlier file of the same track
+    async fn is_rolled_back(
+        &self,
+        chapter_id: ChapterId,
+        language: Option<Language>,
+        latest: i64,
+        subtitles: &[Subtitle],
+    ) -> Result<bool, DatabaseError> {
+        let ch_id = chapter_id.get();
+        let code = language.map(Language::code);
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    srtfile.data
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.chapter_id = ?1 AND
+                  COALESCE(srtfile.language, corpus.language, 'en') = COALESCE(?2, corpus.language, 'en') AND
+                  srtfile.id < ?3 AND
+                  EXISTS (
+                    SELECT 1 FROM srtfile AS restored
+                    WHERE restored.id = ?3 AND restored.restored_from IS NOT NULL
+                  )
+         "#,
+            ch_id,
+            code,
+            latest,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            if deserialize_subtitle(&row.data)? == subtitles {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The current subtitles of a chapter in `language`
+    pub async fn lookup_latest_sub_for_chapter_language(
+        &self,
+        chapter_id: ChapterId,
+        language: Language,
+    ) -> Result<Option<LucilleSub>, DatabaseError> {
+        self.lookup_latest_sub_for_track(chapter_id, Some(language))
+            .await
+    }
+
+    /// Untagged subtitles are stored in their corpus' language, so they share
+    /// a track with subtitles tagged with it
+    async fn lookup_latest_sub_for_track(
+        &self,
+        chapter_id: ChapterId,
+        language: Option<Language>,
+    ) -> Result<Option<LucilleSub>, DatabaseError> {
+        let ch_id = chapter_id.get();
+        let code = language.map(Language::code);
+        let opt_row = sqlx::query!(
+            r#"
+                SELECT
+                    srtfile.id, srtfile.uuid, srtfile.data
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.chapter_id = ?1 AND
+                  COALESCE(srtfile.language, corpus.language, 'en') = COALESCE(?2, corpus.language, 'en')
+                ORDER BY srtfile.id DESC
+                LIMIT 1
+         "#,
+            ch_id,
+            code,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(record) = opt_row {
+            Ok(Some(LucilleSub {
+                id: record.id,
+                uuid: parse_uuid(&record.uuid)?,
+                subs: deserialize_subtitle(&record.data)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Every language with subtitles in the corpus, in [`Language::ALL`] order
+    pub async fn get_subtitle_languages_for_corpus(
+        &self,
+        corpus_id: CorpusId,
+    ) -> Result<Vec<Language>, DatabaseError> {
+        let cid = corpus_id.get();
+        let codes = sqlx::query!(
+            r#"
+                SELECT DISTINCT
+                    COALESCE(srtfile.language, corpus.language, 'en') AS "language!: String"
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  corpus.id = ?
+         "#,
+            cid,
+        )
+        .map(|r| r.language)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut languages = codes
+            .iter()
+            .map(|code| parse_language(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        languages.sort_by_key(|l| Language::ALL.iter().position(|a| a == l));
+        Ok(languages)
+    }
+
+    /// The language of a subtitle file, untagged files are in their corpus' language
+    pub async fn get_srt_language(&self, uuid: Uuid) -> Result<Language, DatabaseError> {
+        let uuid_str = uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    COALESCE(srtfile.language, corpus.language, 'en') AS "language!: String"
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.uuid = ?
+         "#,
+            uuid_str,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        parse_language(&row.language)
+    }
+
+    /// The chapter which a subtitle file belongs to
+    pub async fn get_chapter_id_for_srt(&self, uuid: Uuid) -> Result<ChapterId, DatabaseError> {
+        let uuid_str = uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    chapter_id
+                FROM srtfile
+                WHERE
+                  uuid = ?
+         "#,
+            uuid_str,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ChapterId::new(row.chapter_id))
+    }
+
+    /// Every subtitle file of a chapter in the order they were added, of all languages
+    pub async fn get_subtitle_revisions(
+        &self,
+        chapter_id: ChapterId,
+    ) -> Result<Vec<SubtitleRevision>, DatabaseError> {
+        let ch_id = chapter_id.get();
+        let rows = sqlx::query!(
+            r#"
+                SELECT
+                    srtfile.id AS "id!", srtfile.uuid AS "uuid!", srtfile.data AS "data!",
+                    COALESCE(srtfile.language, corpus.language, 'en') AS "language!: String",
+                    srtfile.created_at, srtfile.restored_from
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.chapter_id = ?
+                ORDER BY srtfile.id
+         "#,
+            ch_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut latest = HashMap::new();
+        for row in &rows {
+            latest.insert(row.language.as_str(), row.id);
+        }
+        let number = |id: i64| rows.iter().position(|r| r.id == id).map(|idx| idx + 1);
+        rows.iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                Ok(SubtitleRevision {
+                    id: row.id,
+                    uuid: parse_uuid(&row.uuid)?,
+                    number: idx + 1,
+                    language: parse_language(&row.language)?,
+                    created_at: row.created_at.clone(),
+                    restored_from: row.restored_from.and_then(number),
+                    lines: deserialize_subtitle(&row.data)?.len(),
+                    current: latest.get(row.language.as_str()) == Some(&row.id),
+                })
+            })
+            .collect()
+    }
+
+    /// Make an earlier subtitle file the current one of its chapter and language
+    /// again, by adding a copy of it. Search indexes covering the chapter in
+    /// that language are marked stale, see [`Database::get_stale_search_indexes`].
+    ///
+    /// Restoring the current file changes nothing and returns its own Uuid.
+    pub async fn restore_subtitle_revision(&self, srt_id: i64) -> Result<Uuid, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    srtfile.chapter_id, srtfile.uuid, srtfile.data,
+                    COALESCE(srtfile.language, corpus.language, 'en') AS "language!: String"
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.id = ?
+         "#,
+            srt_id,
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        let latest = sqlx::query!(
+            r#"
+                SELECT
+                    MAX(srtfile.id) AS "id!: i64"
+                FROM srtfile
+                JOIN chapter
+                  ON srtfile.chapter_id = chapter.id
+                JOIN corpus
+                  ON chapter.corpus_id = corpus.id
+                WHERE
+                  srtfile.chapter_id = ?1 AND
+                  COALESCE(srtfile.language, corpus.language, 'en') = ?2
+         "#,
+            row.chapter_id,
+            row.language,
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        if latest.id == srt_id {
+            return parse_uuid(&row.uuid);
+        }
+
+        let srt_uuid = Uuid::generate();
+        let srt_uuid_string = srt_uuid.to_string();
+        sqlx::query!(
+            r#"
+                    INSERT INTO srtfile (chapter_id, uuid, data, language, created_at, restored_from)
+                    VALUES ( ?1, ?2, ?3, ?4, datetime('now'), ?5 )
+                    "#,
+            row.chapter_id,
+            srt_uuid_string,
+            row.data,
+            row.language,
+            srt_id,
+        )
+        .execute(&mut tx)
+        .await?;
+        Database::mark_search_indexes_stale_for_track(
+            &mut tx,
+            ChapterId::new(row.chapter_id),
+            Some(&row.language),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(srt_uuid)
+    }
+
     // TODO we should not use numeric ids, or this should be better baked into the index schema?
     pub async fn get_episode_by_id(
         &self,
@@ -253,7 +654,7 @@ impl Database {
             r#"
                 SELECT 
                     chapter.id, chapter.title, chapter.season, chapter.episode,
-                    chapter.hash
+                    chapter.show, chapter.more_episodes, chapter.year, chapter.hash
                 FROM chapter
                 JOIN srtfile
                   ON srtfile.chapter_id = chapter.id
@@ -262,11 +663,60 @@ impl Database {
          "#,
             srt_id
         )
-        .map(|r| (r.hash, metadata_from_chapter(r.title, r.season, r.episode)))
+        .map(|r| {
+            (
+                r.hash,
+                metadata_from_chapter(
+                    r.title,
+                    r.season,
+                    r.episode,
+                    r.show,
+                    r.more_episodes,
+                    r.year,
+                ),
+            )
+        })
         .fetch_one(&self.pool)
         .await?;
         // todo custom struct
-        Ok((parse_media_hash(&ret.0)?, ret.1))
+        Ok((parse_media_hash(&ret.0)?, ret.1?))
+    }
+
+    /// The uuid and metadata of each of `srt_ids`, by srt id, failing if any is missing
+    pub async fn get_episodes_by_ids(
+        &self,
+        srt_ids: &[i64],
+    ) -> Result<HashMap<i64, (Uuid, MediaMetadata)>, DatabaseError> {
+        let rows = fetch_for_ids(
+            &self.pool,
+            r#"
+                SELECT
+                    srtfile.id, srtfile.uuid,
+                    chapter.title, chapter.season, chapter.episode,
+                    chapter.show, chapter.more_episodes, chapter.year
+                FROM chapter
+                JOIN srtfile
+                  ON srtfile.chapter_id = chapter.id
+                WHERE
+                  srtfile.id IN"#,
+            srt_ids,
+        )
+        .await?;
+        let mut episodes = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let uuid: String = row.try_get("uuid")?;
+            let metadata = metadata_from_chapter(
+                row.try_get("title")?,
+                row.try_get("season")?,
+                row.try_get("episode")?,
+                row.try_get("show")?,
+                row.try_get("more_episodes")?,
+                row.try_get("year")?,
+            )?;
+            episodes.insert(row.try_get("id")?, (parse_uuid(&uuid)?, metadata));
+        }
+        ensure_all_found(srt_ids, &episodes)?;
+        Ok(episodes)
     }
 
     /// Translate an srt_id provided by the search index into its Uuid
@@ -285,6 +735,24 @@ impl Database {
         .await?;
         parse_uuid(&row.uuid)
     }
+
+    /// Translate an srt Uuid into the srt_id used by the search index
+    pub async fn get_srt_id_by_uuid(&self, uuid: Uuid) -> Result<i64, DatabaseError> {
+        let uuid_str = uuid.to_string();
+        let row = sqlx::query!(
+            r#"
+                SELECT 
+                    id
+                FROM srtfile
+                WHERE 
+                  uuid = ?
+         "#,
+            uuid_str,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.id)
+    }
 }
 
 #[cfg(test)]
@@ -348,6 +816,45 @@ mod test {
                                        //
         let actual_uuid = db.get_srt_uuid_by_id(sub_meta.id).await.unwrap();
         assert_eq!(actual_uuid, u1);
+        let actual_id = db.get_srt_id_by_uuid(u1).await.unwrap();
+        assert_eq!(actual_id, sub_meta.id);
+    }
+
+    #[tokio::test]
+    async fn get_episodes_in_batch() {
+        let db = Database::memory().await.unwrap();
+        let corpus = db.add_corpus("media").await.unwrap();
+        let ch_id = db
+            .define_chapter(
+                corpus.id.unwrap(),
+                "c1",
+                None,
+                None,
+                MediaHash::from_bytes(b"data"),
+            )
+            .await
+            .unwrap();
+        let s1 = parse_subs(SUB1);
+        let s2 = parse_subs(SUB2);
+        let u1 = db.add_subtitles(ch_id, &s1).await.unwrap();
+        let u2 = db.add_subtitles(ch_id, &s2).await.unwrap();
+        let id1 = db.get_srt_id_by_uuid(u1).await.unwrap();
+        let id2 = db.get_srt_id_by_uuid(u2).await.unwrap();
+
+        let episodes = db.get_episodes_by_ids(&[id1, id2]).await.unwrap();
+        assert_eq!(episodes[&id1].0, u1);
+        assert_eq!(episodes[&id2].0, u2);
+        assert_eq!(episodes[&id1].1, MediaMetadata::Unknown("c1".to_string()));
+
+        let subs = db.get_all_subs_for_srts(&[id1, id2]).await.unwrap();
+        assert_eq!(subs[&id1], s1);
+        assert_eq!(subs[&id2], s2);
+
+        let views = db.get_media_views_for_srts(&[id1]).await.unwrap();
+        assert_eq!(views[&id1], vec![]);
+
+        assert!(db.get_episodes_by_ids(&[id1, id2 + 100]).await.is_err());
+        assert!(db.get_all_subs_for_srts(&[id2 + 100]).await.is_err());
     }
 
     #[tokio::test]
@@ -462,4 +969,171 @@ mod test {
         assert_eq!(u2_subs.uuid, u2);
         assert_eq!(u2_subs.subs, s2);
     }
+
+    #[tokio::test]
+    async fn subtitle_tracks_by_language() {
+        let db = Database::memory().await.unwrap();
+        let corpus_id = db.add_corpus("media").await.unwrap().id.unwrap();
+        let ch_id = db
+            .define_chapter(corpus_id, "c1", None, None, MediaHash::from_bytes(b"data"))
+            .await
+            .unwrap();
+
+        let s1 = parse_subs(SUB1);
+        let s2 = parse_subs(SUB2);
+        let en = db.add_subtitles(ch_id, &s1).await.unwrap();
+        let es = db
+            .add_subtitle_track(ch_id, Some(Language::Spanish), &s2)
+            .await
+            .unwrap();
+        // untagged subtitles are in the corpus' language
+        let same = db
+            .add_subtitle_track(ch_id, Some(Language::English), &s1)
+            .await
+            .unwrap();
+        assert_eq!(same, en);
+
+        let latest = db.lookup_latest_sub_for_chapter(ch_id).await.unwrap();
+        assert_eq!(latest.unwrap().uuid, en);
+        let spanish = db
+            .lookup_latest_sub_for_chapter_language(ch_id, Language::Spanish)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spanish.uuid, es);
+        assert_eq!(spanish.subs, s2);
+        let french = db
+            .lookup_latest_sub_for_chapter_language(ch_id, Language::French)
+            .await
+            .unwrap();
+        assert_eq!(french, None);
+
+        assert_eq!(
+            db.get_subtitle_languages_for_corpus(corpus_id)
+                .await
+                .unwrap(),
+            vec![Language::English, Language::Spanish]
+        );
+        assert_eq!(db.get_srt_language(es).await.unwrap(), Language::Spanish);
+        assert_eq!(db.get_chapter_id_for_srt(es).await.unwrap(), ch_id);
+        let (srts, content) = db
+            .get_all_subs_for_corpus_language(corpus_id, Language::Spanish)
+            .await
+            .unwrap();
+        assert_eq!(srts, HashSet::from([spanish.id]));
+        assert_eq!(content[0].subtitle.uuid, es);
+
+        // the untagged track keeps the language the corpus had
+        db.set_corpus_language(corpus_id, Language::Spanish)
+            .await
+            .unwrap();
+        assert_eq!(db.get_srt_language(en).await.unwrap(), Language::English);
+        assert_eq!(
+            db.get_subtitle_languages_for_corpus(corpus_id)
+                .await
+                .unwrap(),
+            vec![Language::English, Language::Spanish]
+        );
+    }
+
+    #[tokio::test]
+    async fn revision_history_and_rollback() {
+        let db = Database::memory().await.unwrap();
+        let corpus = db.add_corpus("media").await.unwrap();
+        let ch_id = db
+            .define_chapter(
+                corpus.id.unwrap(),
+                "c1",
+                None,
+                None,
+                MediaHash::from_bytes(b"data"),
+            )
+            .await
+            .unwrap();
+        let (s1, s2) = (parse_subs(SUB1), parse_subs(SUB2));
+        let u1 = db.add_subtitles(ch_id, &s1).await.unwrap();
+        let u2 = db.add_subtitles(ch_id, &s2).await.unwrap();
+        let spanish = db
+            .add_subtitle_track(ch_id, Some(Language::Spanish), &s1)
+            .await
+            .unwrap();
+
+        let index = Uuid::generate();
+        let info = lucille_core::SearchIndexInfo {
+            schema_version: 1,
+            tokenizer: "en_stem".to_string(),
+            max_window: 5,
+            cleanup: vec![],
+        };
+        let srt2 = db.get_srt_id_by_uuid(u2).await.unwrap();
+        db.assoc_index_with_srts(index, HashSet::from([srt2]), &info)
+            .await
+            .unwrap();
+        let spanish_index = Uuid::generate();
+        let spanish_srt = db.get_srt_id_by_uuid(spanish).await.unwrap();
+        db.assoc_index_with_srts(spanish_index, HashSet::from([spanish_srt]), &info)
+            .await
+            .unwrap();
+
+        let revisions = db.get_subtitle_revisions(ch_id).await.unwrap();
+        assert_eq!(
+            revisions.iter().map(|r| r.uuid).collect::<Vec<_>>(),
+            vec![u1, u2, spanish]
+        );
+        assert_eq!(revisions[0].number, 1);
+        assert_eq!(revisions[0].lines, s1.len());
+        assert!(revisions[0].created_at.is_some());
+        assert_eq!(
+            revisions.iter().map(|r| r.current).collect::<Vec<_>>(),
+            vec![false, true, true]
+        );
+        assert!(db.get_stale_search_indexes().await.unwrap().is_empty());
+
+        let restored = db.restore_subtitle_revision(revisions[0].id).await.unwrap();
+        let revisions = db.get_subtitle_revisions(ch_id).await.unwrap();
+        assert_eq!(revisions.len(), 4);
+        assert_eq!(revisions[3].uuid, restored);
+        assert_eq!(revisions[3].restored_from, Some(1));
+        assert_eq!(revisions[3].language, Language::English);
+        assert_eq!(
+            revisions.iter().map(|r| r.current).collect::<Vec<_>>(),
+            vec![false, false, true, true]
+        );
+        let latest = db.lookup_latest_sub_for_chapter(ch_id).await.unwrap();
+        assert_eq!(latest.unwrap().subs, s1);
+        // only indexes of the restored language are stale
+        assert_eq!(db.get_stale_search_indexes().await.unwrap(), vec![index]);
+        assert_eq!(
+            db.get_stale_search_indexes_for_corpus(corpus.id.unwrap())
+                .await
+                .unwrap(),
+            vec![index]
+        );
+        let other = db.add_corpus("other").await.unwrap();
+        assert!(db
+            .get_stale_search_indexes_for_corpus(other.id.unwrap())
+            .await
+            .unwrap()
+            .is_empty());
+
+        // scanning the subtitles which were rolled back keeps the rollback
+        assert_eq!(db.add_subtitles(ch_id, &s2).await.unwrap(), restored);
+        assert_eq!(db.get_subtitle_revisions(ch_id).await.unwrap().len(), 4);
+
+        // the current revision is already restored
+        let same = db.restore_subtitle_revision(revisions[3].id).await.unwrap();
+        assert_eq!(same, restored);
+        assert_eq!(db.get_subtitle_revisions(ch_id).await.unwrap().len(), 4);
+
+        db.update_index_assoc(
+            index,
+            &HashSet::from([revisions[3].id]),
+            &HashSet::from([srt2]),
+            &info,
+            || Ok::<_, DatabaseError>(()),
+        )
+        .await
+        .unwrap();
+        assert!(!db.is_search_index_stale(index).await.unwrap());
+    }
 }
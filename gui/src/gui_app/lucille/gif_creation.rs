@@ -11,15 +11,20 @@ pub enum ClipSource {
     Search { uuid: Uuid, range: (usize, usize) },
 }
 
-#[derive(Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 enum DataFormat {
     Debug,
     Json,
     JsonPretty,
-    #[default]
     Base64,
 }
 
+impl Default for DataFormat {
+    fn default() -> Self {
+        DataFormat::Base64
+    }
+}
+
 impl DataFormat {
     fn display_name(&self) -> &'static str {
         match self {
@@ -59,6 +64,8 @@ impl GifCreationUi {
                 srt_uuid: uuid,
                 sub_range: range.0..range.1,
             }],
+            subtitle_language: None,
+            timing: None,
         })
     }
     pub fn update(&mut self, ctx: &mut (impl LucilleCtx + ErrorPopup)) {
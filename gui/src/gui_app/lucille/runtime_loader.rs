@@ -5,15 +5,19 @@ use egui::RichText;
 
 use crate::gui_app::{error_popup::ErrorUi, ErrorPopup};
 
-#[derive(Default)]
 enum ConfigState {
-    #[default]
     Init,
     Builder(app::app::ConfigBuilder),
     Configured(app::app::LucilleConfig),
     Paths(HashFS, app::app::LucilleConfig),
 }
 
+impl Default for ConfigState {
+    fn default() -> Self {
+        ConfigState::Init
+    }
+}
+
 #[derive(Default)]
 pub struct LucilleConfigLoader {
     manual_loading: bool,
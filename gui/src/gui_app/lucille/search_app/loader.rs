@@ -4,14 +4,18 @@ use app::app::LucilleApp;
 use super::SearchApp;
 use crate::gui_app::{lucille::LucilleCtx, ErrorPopup};
 
-#[derive(Default)]
 pub(crate) enum SearchAppState {
-    #[default]
     Unknown,
     None,
     App(SearchApp),
 }
 
+impl Default for SearchAppState {
+    fn default() -> Self {
+        SearchAppState::Unknown
+    }
+}
+
 async fn load_search(lucille: &LucilleApp) -> anyhow::Result<Option<SearchApp>> {
     log::trace!("looking up most recent search indicies");
     let existing_indexes = lucille
@@ -1,4 +1,11 @@
-use std::fmt::Write;
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
 use app::{
@@ -6,7 +13,11 @@ use app::{
     search_manager::{ClipResult, SearchRequest, SearchResponse, SearchService},
 };
 use egui::RichText;
-use lucille_core::{clean_sub::CleanSubs, uuid::Uuid};
+use lucille_core::{
+    clean_sub::{CleanSubs, SubtitleCleaner},
+    uuid::Uuid,
+};
+use search::Suggestions;
 
 use self::episode_cache::{EpisodeCache, EpisodeData};
 use super::{super::error::ErrorChainLogLine, gif_creation::GifCreationUi, LucilleCtx};
@@ -16,13 +27,23 @@ pub(crate) use loader::{load_last_index, SearchAppState};
 
 const DEFAULT_SEARCH_WIDTH: usize = 5;
 const SEARCH_BUFFER_DEPTH: usize = 32;
+const RESULTS_PAGE_SIZE: usize = 50;
+const MAX_SUGGESTIONS: usize = 5;
+/// Suggestions are only looked up once typing pauses for this long
+const SUGGEST_DEBOUNCE: Duration = Duration::from_millis(200);
 
 type TxSend = tokio::sync::mpsc::Sender<SearchResults>;
 type TxRecv = tokio::sync::mpsc::Receiver<SearchResults>;
+/// Suggestions, and the query they are for
+type SuggestSend = tokio::sync::mpsc::Sender<(String, Suggestions)>;
+type SuggestRecv = tokio::sync::mpsc::Receiver<(String, Suggestions)>;
 
 mod episode_cache;
 
 pub struct SearchResults {
+    query: String,
+    /// These results are the next page of an earlier search for `query`
+    next_page: bool,
     inner: SearchResponse,
     selected: Option<usize>,
     clip: ClipSelection,
@@ -95,14 +116,10 @@ struct PreviewRow<'a> {
 }
 
 impl<'a> PreviewRow<'a> {
-    fn display_text(&self, cache: &EpisodeCache) -> String {
+    fn display_text(&self) -> String {
         let mut text = String::new();
-        let episode = cache.episode(self.clip.srt_id);
-        let episode = episode.value();
 
-        writeln!(&mut text, "{:?} {}", self.clip.score, episode.metadata).unwrap();
-
-        let base = self.clip.offset;
+        writeln!(&mut text, "{:?} {}", self.clip.score, self.clip.metadata).unwrap();
 
         let mut lookback = Lookback::new(2);
 
@@ -110,8 +127,7 @@ impl<'a> PreviewRow<'a> {
         let mut emit = |offset: usize| {
             if emit_max > 0 {
                 emit_max -= 1;
-                let script = CleanSubs(&episode.subs[base + offset..base + offset + 1]);
-                writeln!(&mut text, "{}", script).unwrap();
+                writeln!(&mut text, "{}", self.clip.lines[offset].text).unwrap();
             }
         };
         let mut start_display = false;
@@ -134,7 +150,7 @@ impl<'a> PreviewRow<'a> {
 }
 
 impl SearchResults {
-    fn update_results_scroller(&mut self, cache: &EpisodeCache, ui: &mut egui::Ui) -> bool {
+    fn update_results_scroller(&mut self, ui: &mut egui::Ui) -> bool {
         let text_style = egui::TextStyle::Body;
         let row_height = ui.text_style_height(&text_style) * 7.0;
         let num_rows = self.inner.results.len();
@@ -147,7 +163,7 @@ impl SearchResults {
                     let preview = PreviewRow {
                         clip: &self.inner.results[row],
                     };
-                    let text = preview.display_text(cache);
+                    let text = preview.display_text();
                     if ui.button(text).clicked() {
                         self.selected = Some(row);
                         clicked = true;
@@ -159,6 +175,7 @@ impl SearchResults {
     fn update_results_details(
         &mut self,
         cache: &EpisodeCache,
+        cleaner: &SubtitleCleaner,
         ui: &mut egui::Ui,
         scroll_force: bool,
     ) {
@@ -177,7 +194,7 @@ impl SearchResults {
             .id_source("results_details")
             .show(ui, |ui| {
                 for row in 0..episode.subs.len() {
-                    let cs = CleanSubs(&episode.subs[row..row + 1]);
+                    let cs = CleanSubs(cleaner, &episode.subs[row..row + 1]);
                     let in_search = row < start_id || row >= end_id;
                     let sub_line = SubLine {
                         idx: row,
@@ -195,11 +212,11 @@ impl SearchResults {
                 }
             });
     }
-    fn update(&mut self, cache: &EpisodeCache, ui: &mut egui::Ui) {
+    fn update(&mut self, cache: &EpisodeCache, cleaner: &SubtitleCleaner, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
             ui.columns(2, |columns| {
-                let update = self.update_results_scroller(cache, &mut columns[0]);
-                self.update_results_details(cache, &mut columns[1], update);
+                let update = self.update_results_scroller(&mut columns[0]);
+                self.update_results_details(cache, cleaner, &mut columns[1], update);
             });
         });
     }
@@ -245,6 +262,35 @@ impl SubLine {
 
 pub struct SearchQuery {
     body: String,
+    suggestions: Suggestions,
+}
+
+impl SearchQuery {
+    /// Show the suggestions for the current query, returning the query with
+    /// the suggestion which was clicked applied
+    fn suggestions_ui(&self, ui: &mut egui::Ui) -> Option<String> {
+        let mut chosen = None;
+        let Suggestions {
+            completions,
+            corrections,
+        } = &self.suggestions;
+        ui.horizontal_wrapped(|ui| {
+            for suggestion in completions {
+                if ui.small_button(suggestion.term.as_str()).clicked() {
+                    chosen = Some(suggestion.apply(&self.body));
+                }
+            }
+            if !corrections.is_empty() {
+                ui.label(RichText::new("did you mean:").weak());
+            }
+            for suggestion in corrections {
+                if ui.link(suggestion.term.as_str()).clicked() {
+                    chosen = Some(suggestion.apply(&self.body));
+                }
+            }
+        });
+        chosen
+    }
 }
 
 pub struct SearchApp {
@@ -259,16 +305,24 @@ pub struct SearchApp {
 
     tx: TxSend,
     rx: TxRecv,
+
+    /// Counts suggestion requests, a request is dropped if another
+    /// was made while it waited for typing to pause
+    suggest_requests: Arc<AtomicUsize>,
+    suggest_tx: SuggestSend,
+    suggest_rx: SuggestRecv,
 }
 
 impl SearchApp {
     pub fn new(search_service: SearchService) -> SearchApp {
         let (tx, rx) = tokio::sync::mpsc::channel(SEARCH_BUFFER_DEPTH);
+        let (suggest_tx, suggest_rx) = tokio::sync::mpsc::channel(SEARCH_BUFFER_DEPTH);
         SearchApp {
             search_service: std::sync::Arc::new(search_service),
             search_width: DEFAULT_SEARCH_WIDTH,
             query: SearchQuery {
                 body: "".to_string(),
+                suggestions: Suggestions::default(),
             },
 
             cache: EpisodeCache::default(),
@@ -276,10 +330,18 @@ impl SearchApp {
             tx,
             rx,
             show_gif_creator: false,
+            suggest_requests: Arc::new(AtomicUsize::new(0)),
+            suggest_tx,
+            suggest_rx,
         }
     }
 
-    fn run_query<Ctx: ErrorPopup + LucilleCtx>(&self, ui: &mut egui::Ui, ctx: &mut Ctx) {
+    fn run_query<Ctx: ErrorPopup + LucilleCtx>(
+        &self,
+        ui: &mut egui::Ui,
+        ctx: &mut Ctx,
+        cursor: Option<String>,
+    ) {
         let results_tx = self.tx.clone();
         let service = self.search_service.clone();
         let text = self.query.body.clone();
@@ -290,7 +352,9 @@ impl SearchApp {
             let request = SearchRequest {
                 query: text.as_str(),
                 window: Some(DEFAULT_SEARCH_WIDTH),
-                max_responses: Some(100),
+                max_responses: Some(RESULTS_PAGE_SIZE),
+                cursor,
+                ..Default::default()
             };
             if let Err(e) = search_and_rank(&lucille, &service, &cache, request, results_tx).await {
                 log::error!("{:?}", ErrorChainLogLine::from(e))
@@ -300,13 +364,61 @@ impl SearchApp {
         });
     }
 
+    /// Look up suggestions for the query in the background, once typing
+    /// pauses. Scanning the vocabulary is too slow to do on every keystroke.
+    fn update_suggestions<Ctx: LucilleCtx>(&mut self, ui: &mut egui::Ui, ctx: &mut Ctx) {
+        // the old suggestions would apply to the wrong parts of the new query
+        self.query.suggestions = Suggestions::default();
+
+        let request = self.suggest_requests.fetch_add(1, Ordering::SeqCst) + 1;
+        let requests = self.suggest_requests.clone();
+        let suggest_tx = self.suggest_tx.clone();
+        let service = self.search_service.clone();
+        let text = self.query.body.clone();
+        let egui_ctx = ui.ctx().clone();
+        ctx.rt().spawn(async move {
+            tokio::time::sleep(SUGGEST_DEBOUNCE).await;
+            if requests.load(Ordering::SeqCst) != request {
+                return;
+            }
+            let suggested = tokio::task::spawn_blocking(move || {
+                service
+                    .suggest(&text, Some(MAX_SUGGESTIONS))
+                    .map(|suggestions| (text, suggestions))
+            })
+            .await;
+            match suggested {
+                Ok(Ok(suggested)) => {
+                    if suggest_tx.send(suggested).await.is_ok() {
+                        egui_ctx.request_repaint();
+                    }
+                }
+                Ok(Err(e)) => log::error!("{:?}", ErrorChainLogLine::from(e)),
+                Err(e) => log::error!("suggestion task failed: {}", e),
+            }
+        });
+    }
+
+    fn fetch_latest_suggestions(&mut self) {
+        while let Ok((query, suggestions)) = self.suggest_rx.try_recv() {
+            if query == self.query.body {
+                self.query.suggestions = suggestions;
+            }
+        }
+    }
+
     fn fetch_latest_result(&mut self) {
-        let mut new_results = None;
         while let Ok(r) = self.rx.try_recv() {
-            new_results = Some(r)
-        }
-        if let Some(new_results) = new_results {
-            self.results = Some(new_results)
+            match &mut self.results {
+                Some(current) if r.next_page => {
+                    // a page for a query which has since changed is dropped
+                    if current.query == r.query {
+                        current.inner.results.extend(r.inner.results);
+                        current.inner.next_cursor = r.inner.next_cursor;
+                    }
+                }
+                _ => self.results = Some(r),
+            }
         }
     }
 
@@ -320,9 +432,16 @@ impl SearchApp {
     {
         ui.vertical(|ui| {
             ui.heading("Search");
-            if ui.text_edit_singleline(&mut self.query.body).changed() {
-                self.run_query(ui, app_ctx)
+            let mut changed = ui.text_edit_singleline(&mut self.query.body).changed();
+            if let Some(query) = self.query.suggestions_ui(ui) {
+                self.query.body = query;
+                changed = true;
             }
+            if changed {
+                self.update_suggestions(ui, app_ctx);
+                self.run_query(ui, app_ctx, None)
+            }
+            self.fetch_latest_suggestions();
             self.fetch_latest_result();
             egui::ScrollArea::vertical()
                 .auto_shrink([false, false])
@@ -330,7 +449,7 @@ impl SearchApp {
                 .enable_scrolling(false)
                 .show(ui, |ui| {
                     if let Some(results) = &mut self.results {
-                        results.update(&self.cache, ui)
+                        results.update(&self.cache, self.search_service.subtitle_cleanup(), ui)
                     }
                 });
             ui.with_layout(egui::Layout::bottom_up(egui::Align::BOTTOM), |ui| {
@@ -345,6 +464,16 @@ impl SearchApp {
                     if create.clicked() {
                         self.show_gif_creator = true
                     }
+
+                    let next_cursor = self
+                        .results
+                        .as_ref()
+                        .and_then(|r| r.inner.next_cursor.clone());
+                    let more =
+                        ui.add_enabled(next_cursor.is_some(), egui::Button::new("More results"));
+                    if more.clicked() {
+                        self.run_query(ui, app_ctx, next_cursor)
+                    }
                 });
             });
         });
@@ -377,13 +506,17 @@ async fn search_and_rank<'a>(
     req: SearchRequest<'a>,
     tx: TxSend,
 ) -> anyhow::Result<()> {
+    let query = req.query.to_string();
+    let next_page = req.cursor.is_some();
     let resp = search
         .search_and_rank(req)
         .await
         .context("SearchService search and rank failure")?;
-    let results = fill_cache_with_results(app, resp, cache)
+    let mut results = fill_cache_with_results(app, resp, cache)
         .await
         .context("convert resp to display results")?;
+    results.query = query;
+    results.next_page = next_page;
     tx.send(results)
         .await
         .map_err(|e| anyhow::anyhow!("tokio send failure: {}", e))
@@ -398,12 +531,18 @@ async fn fill_cache_with_results(
 ) -> anyhow::Result<SearchResults> {
     for clip in &resp.results {
         if !cache.contains(clip.srt_id) {
-            let (_, metadata) = app.db.get_episode_by_id(clip.srt_id).await?;
-            let subs = app.db.get_all_subs_for_srt(clip.srt_id).await?;
-            let uuid = app.db.get_srt_uuid_by_id(clip.srt_id).await?;
+            // the details view shows the whole episode, not just the clip,
+            // with the times corrected like the clip's lines
+            let timing = app
+                .db
+                .get_chapter_timings_for_srts(&[clip.srt_id])
+                .await?
+                .remove(&clip.srt_id)
+                .unwrap_or_default();
+            let subs = timing.apply(&app.db.get_all_subs_for_srt(clip.srt_id).await?);
             let e = EpisodeData {
-                uuid,
-                metadata,
+                uuid: clip.srt_uuid,
+                metadata: clip.metadata.clone(),
                 subs,
             };
             cache.insert(clip.srt_id, e);
@@ -411,6 +550,8 @@ async fn fill_cache_with_results(
     }
 
     Ok(SearchResults {
+        query: String::new(),
+        next_page: false,
         inner: resp,
         selected: None,
         clip: ClipSelection::default(),
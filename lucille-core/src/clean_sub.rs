@@ -1,32 +1,339 @@
-use std::fmt;
+//! Cleanup of the text of subtitles, before it is indexed or drawn.
+//!
+//! Subtitles for the deaf and hard of hearing (SDH) describe sounds like
+//! `[DOOR SLAMS]`, label speakers and show music with `♪`, and many files keep
+//! formatting such as `<i>` or `{\an8}`. None of these are words anybody says.
 
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
 use subrip::Subtitle;
 
-pub struct CleanSubs<'a>(pub &'a [Subtitle]);
+/// One step of a [`SubtitleCleaner`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CleanupRule {
+    /// Drop lines with music notes, which are lyrics or describe music
+    MusicLines,
+    /// Remove music notes, keeping the lyrics
+    MusicSymbols,
+    /// Remove HTML style tags such as `<i>` and `</font>`
+    Markup,
+    /// Remove ASS/SSA override codes such as `{\an8}`
+    AssOverrides,
+    /// Remove sound descriptions in brackets or parentheses, `[DOOR SLAMS]` or `(laughs)`
+    SoundDescriptions,
+    /// Remove upper case speaker labels, `MICHAEL: ...`
+    SpeakerLabels,
+    /// Remove the dashes which start each speaker's line
+    LeadingDashes,
+}
+
+impl CleanupRule {
+    pub const ALL: [CleanupRule; 7] = [
+        CleanupRule::MusicLines,
+        CleanupRule::MusicSymbols,
+        CleanupRule::Markup,
+        CleanupRule::AssOverrides,
+        CleanupRule::SoundDescriptions,
+        CleanupRule::SpeakerLabels,
+        CleanupRule::LeadingDashes,
+    ];
+
+    /// The name of the rule in configuration, e.g. `sound-descriptions`
+    pub fn name(self) -> &'static str {
+        match self {
+            CleanupRule::MusicLines => "music-lines",
+            CleanupRule::MusicSymbols => "music-symbols",
+            CleanupRule::Markup => "markup",
+            CleanupRule::AssOverrides => "ass-overrides",
+            CleanupRule::SoundDescriptions => "sound-descriptions",
+            CleanupRule::SpeakerLabels => "speaker-labels",
+            CleanupRule::LeadingDashes => "leading-dashes",
+        }
+    }
+
+    /// Clean a single line on screen, `None` if the whole line is dropped
+    fn apply(self, line: &str) -> Option<String> {
+        Some(match self {
+            CleanupRule::MusicLines => {
+                if line.contains(MUSIC_NOTES) {
+                    return None;
+                }
+                line.to_string()
+            }
+            CleanupRule::MusicSymbols => line.replace(MUSIC_NOTES, ""),
+            CleanupRule::Markup => remove_spans(line, '<', '>', "", |tag| {
+                let tag = tag.strip_prefix('/').unwrap_or(tag);
+                tag.starts_with(|c: char| c.is_ascii_alphabetic())
+            }),
+            CleanupRule::AssOverrides => {
+                remove_spans(line, '{', '}', "", |code| code.starts_with('\\'))
+            }
+            CleanupRule::SoundDescriptions => {
+                // keep the words on either side apart
+                let line = remove_spans(line, '[', ']', " ", |_| true);
+                remove_spans(&line, '(', ')', " ", |_| true)
+            }
+            CleanupRule::SpeakerLabels => match line.split_once(':') {
+                Some((label, rest)) if is_speaker_label(label) => rest.to_string(),
+                _ => line.to_string(),
+            },
+            CleanupRule::LeadingDashes => line.trim_start().trim_start_matches('-').to_string(),
+        })
+    }
+}
+
+const MUSIC_NOTES: &[char] = &['♪', '♫'];
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown subtitle cleanup rule: {0:?}")]
+pub struct UnknownCleanupRule(String);
+
+impl FromStr for CleanupRule {
+    type Err = UnknownCleanupRule;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace('_', "-");
+        CleanupRule::ALL
+            .into_iter()
+            .find(|r| r.name() == name)
+            .ok_or_else(|| UnknownCleanupRule(s.to_string()))
+    }
+}
+
+impl fmt::Display for CleanupRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The rules applied to subtitles before they are searched, or shown as search
+/// results. Lyrics stay searchable, only the music notes are removed.
+pub const DEFAULT_INDEX_RULES: &[CleanupRule] = &[
+    CleanupRule::MusicSymbols,
+    CleanupRule::Markup,
+    CleanupRule::AssOverrides,
+    CleanupRule::SoundDescriptions,
+    CleanupRule::SpeakerLabels,
+    CleanupRule::LeadingDashes,
+];
+
+/// The rules applied to subtitles before they are burned into a clip, dialogue
+/// dashes and speaker labels help tell who is talking, and music notes mark lyrics
+pub const DEFAULT_RENDER_RULES: &[CleanupRule] = &[
+    CleanupRule::Markup,
+    CleanupRule::AssOverrides,
+    CleanupRule::SoundDescriptions,
+];
+
+/// A set of [`CleanupRule`]s, always applied in the order of [`CleanupRule::ALL`]
+/// so that lines are dropped before anything else changes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCleaner {
+    rules: Vec<CleanupRule>,
+}
+
+impl SubtitleCleaner {
+    pub fn new(rules: &[CleanupRule]) -> SubtitleCleaner {
+        let mut rules = rules.to_vec();
+        rules.sort();
+        rules.dedup();
+        SubtitleCleaner { rules }
+    }
+
+    pub fn index_default() -> SubtitleCleaner {
+        SubtitleCleaner::new(DEFAULT_INDEX_RULES)
+    }
+
+    pub fn render_default() -> SubtitleCleaner {
+        SubtitleCleaner::new(DEFAULT_RENDER_RULES)
+    }
+
+    pub fn rules(&self) -> &[CleanupRule] {
+        &self.rules
+    }
+
+    /// The lines of `text` which are left after cleaning, trimmed and with
+    /// runs of whitespace collapsed
+    pub fn clean_lines(&self, text: &str) -> Vec<String> {
+        text.lines()
+            .filter_map(|line| {
+                let line = self
+                    .rules
+                    .iter()
+                    .try_fold(line.to_string(), |line, rule| rule.apply(&line))?;
+                let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+                if line.is_empty() {
+                    None
+                } else {
+                    Some(line)
+                }
+            })
+            .collect()
+    }
+
+    /// The text of a subtitle on a single line, as it is indexed and listed in results
+    pub fn single_line(&self, sub: &Subtitle) -> String {
+        self.clean_lines(&sub.text).join(" ")
+    }
+
+    /// Clean the text of every subtitle, dropping those with nothing left to show
+    pub fn clean_subtitles(&self, subs: &[Subtitle]) -> Vec<Subtitle> {
+        subs.iter()
+            .filter_map(|sub| {
+                let lines = self.clean_lines(&sub.text);
+                if lines.is_empty() {
+                    return None;
+                }
+                let mut cleaned = sub.clone();
+                cleaned.text = lines.iter().map(|l| format!("{}\n", l)).collect();
+                Some(cleaned)
+            })
+            .collect()
+    }
+}
+
+impl Default for SubtitleCleaner {
+    fn default() -> Self {
+        SubtitleCleaner::index_default()
+    }
+}
+
+/// Replace every `open ... close` span whose contents pass `matches`, an
+/// unclosed span is kept
+fn remove_spans(
+    line: &str,
+    open: char,
+    close: char,
+    replacement: &str,
+    matches: impl Fn(&str) -> bool,
+) -> String {
+    let mut cleaned = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(open) {
+        let len = match rest[start..].find(close) {
+            Some(len) => len,
+            None => break,
+        };
+        let inner = &rest[start + open.len_utf8()..start + len];
+        cleaned.push_str(&rest[..start]);
+        if !matches(inner) {
+            cleaned.push_str(&rest[start..start + len + close.len_utf8()]);
+        } else {
+            cleaned.push_str(replacement);
+        }
+        rest = &rest[start + len + close.len_utf8()..];
+    }
+    cleaned.push_str(rest);
+    cleaned
+}
+
+/// `MICHAEL`, `GOB` or `LUCILLE 2`, but not `Note` or `10`
+fn is_speaker_label(label: &str) -> bool {
+    let label = label.trim().trim_start_matches('-').trim();
+    label.chars().count() >= 2
+        && label.chars().any(char::is_alphabetic)
+        && label
+            .chars()
+            .all(|c| c.is_uppercase() || c.is_ascii_digit() || " .'-".contains(c))
+}
+
+/// The text of some subtitles on one line, cleaned by the given cleaner
+pub struct CleanSubs<'a>(pub &'a SubtitleCleaner, pub &'a [Subtitle]);
 
 impl<'a> fmt::Display for CleanSubs<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (idx, sub) in self.0.iter().enumerate() {
+        for (idx, sub) in self.1.iter().enumerate() {
             if idx != 0 {
                 f.write_str(" ")?;
             }
-            write!(f, "{}", CleanSub(sub))?;
+            write!(f, "{}", CleanSub(self.0, sub))?;
         }
         Ok(())
     }
 }
 
-pub struct CleanSub<'a>(pub &'a Subtitle);
+/// The text of a subtitle on one line, cleaned by the given cleaner
+pub struct CleanSub<'a>(pub &'a SubtitleCleaner, pub &'a Subtitle);
 
 impl<'a> fmt::Display for CleanSub<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (idx, line) in self.0.text.lines().enumerate() {
-            if idx != 0 {
-                f.write_str(" ")?;
-            }
-            let text = line.trim().trim_start_matches('-').trim();
-            f.write_str(text)?;
+        f.write_str(&self.0.single_line(self.1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn sub(text: &str) -> Subtitle {
+        Subtitle {
+            idx: 1,
+            start: Duration::from_secs(1),
+            end: Duration::from_secs(2),
+            text: text.to_string(),
         }
-        Ok(())
+    }
+
+    #[test]
+    fn clean_for_index() {
+        let index = SubtitleCleaner::index_default();
+        let clean = |text: &str| index.single_line(&sub(text));
+        assert_eq!(
+            clean("{\\an8}<i>There's always money</i>\n<i>in the banana stand.</i>\n"),
+            "There's always money in the banana stand."
+        );
+        assert_eq!(clean("[DOOR SLAMS]\n- No touching!\n"), "No touching!");
+        assert_eq!(clean("Mar<b>ta</b>![LAUGHS]Gob\n"), "Marta! Gob");
+        assert_eq!(
+            clean("MICHAEL: Her?\n- GOB (ON PHONE): I've made a huge mistake.\n"),
+            "Her? I've made a huge mistake."
+        );
+        assert_eq!(
+            clean("♪ Now the story of a wealthy family ♪\n"),
+            "Now the story of a wealthy family"
+        );
+        // ordinary punctuation is left alone
+        assert_eq!(
+            clean("It's 10:30. Note: <3 {not code}\n"),
+            "It's 10:30. Note: <3 {not code}"
+        );
+    }
+
+    #[test]
+    fn clean_for_render() {
+        let render = SubtitleCleaner::render_default();
+        let subs = [
+            sub("<i>- Hey, Mom.</i>\n- Hi, Michael.\n"),
+            sub("[SIGHS]\n"),
+            sub("♪ Yellow brick road ♪\n"),
+        ];
+        let cleaned = render.clean_subtitles(&subs);
+        assert_eq!(cleaned.len(), 2);
+        assert_eq!(cleaned[0].text, "- Hey, Mom.\n- Hi, Michael.\n");
+        assert_eq!(cleaned[0].start, subs[0].start);
+        assert_eq!(cleaned[1].text, "♪ Yellow brick road ♪\n");
+
+        let no_lyrics = SubtitleCleaner::new(&[CleanupRule::MusicLines]);
+        assert_eq!(no_lyrics.single_line(&subs[2]), "");
+    }
+
+    #[test]
+    fn parse_rules() {
+        assert_eq!(
+            CleanupRule::from_str("sound_descriptions").unwrap(),
+            CleanupRule::SoundDescriptions
+        );
+        assert!(CleanupRule::from_str("profanity").is_err());
+        for rule in CleanupRule::ALL {
+            assert_eq!(CleanupRule::from_str(&rule.to_string()).unwrap(), rule);
+        }
+        let cleaner = SubtitleCleaner::new(&[CleanupRule::Markup, CleanupRule::MusicLines]);
+        assert_eq!(
+            cleaner.rules(),
+            &[CleanupRule::MusicLines, CleanupRule::Markup]
+        );
     }
 }
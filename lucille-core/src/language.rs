@@ -0,0 +1,104 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The language of a corpus, which decides how its subtitles are tokenized for search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+    Italian,
+    Portuguese,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::English,
+        Language::Spanish,
+        Language::French,
+        Language::German,
+        Language::Italian,
+        Language::Portuguese,
+    ];
+
+    /// The ISO 639-1 code for the language
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Italian => "it",
+            Language::Portuguese => "pt",
+        }
+    }
+
+    /// The ISO 639-2 codes for the language, as media containers tag their
+    /// streams. Some languages have both a bibliographic and a terminology code.
+    pub fn iso639_2_codes(self) -> &'static [&'static str] {
+        match self {
+            Language::English => &["eng"],
+            Language::Spanish => &["spa"],
+            Language::French => &["fre", "fra"],
+            Language::German => &["ger", "deu"],
+            Language::Italian => &["ita"],
+            Language::Portuguese => &["por"],
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown language: {0:?}")]
+pub struct UnknownLanguage(String);
+
+/// Accepts the ISO 639-1 or 639-2 code or the english name, e.g. `es`, `spa` or `Spanish`
+impl FromStr for Language {
+    type Err = UnknownLanguage;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        Language::ALL
+            .into_iter()
+            .find(|l| {
+                l.code() == lower
+                    || l.iso639_2_codes().contains(&lower.as_str())
+                    || format!("{:?}", l).to_lowercase() == lower
+            })
+            .ok_or_else(|| UnknownLanguage(s.to_string()))
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::English
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_language() {
+        assert_eq!(Language::from_str("es").unwrap(), Language::Spanish);
+        assert_eq!(Language::from_str("French").unwrap(), Language::French);
+        assert_eq!(Language::from_str(" DE ").unwrap(), Language::German);
+        assert_eq!(Language::from_str("fra").unwrap(), Language::French);
+        assert!(Language::from_str("klingon").is_err());
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for l in Language::ALL {
+            assert_eq!(Language::from_str(&l.to_string()).unwrap(), l);
+        }
+    }
+}
@@ -10,7 +10,11 @@ use self::identifiers::CorpusId;
 pub mod encryption_config;
 pub mod hash;
 pub mod identifiers;
+pub mod language;
 pub mod metadata;
+pub mod revision;
+pub mod subtitle_format;
+pub mod timing;
 
 pub mod base64 {
     use std::fmt;
@@ -99,6 +103,7 @@ pub mod export {
 
     use crate::{
         identifiers::{ChapterId, CorpusId, StorageId},
+        language::Language,
         metadata::{MediaHash, MediaMetadata},
         ContentData,
     };
@@ -107,12 +112,20 @@ pub mod export {
     pub struct CorpusExport {
         pub title: String,
         pub content: Vec<MediaExport>,
+        /// The language of the corpus, left as it is on import when missing
+        #[serde(default)]
+        pub language: Option<Language>,
     }
 
+    /// One subtitle track of a chapter, a chapter with several languages is
+    /// exported once per language
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct MediaExport {
         pub views: ViewOptions,
         pub data: ContentData,
+        /// The language of the subtitles, the corpus' language when missing
+        #[serde(default)]
+        pub language: Option<Language>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -143,7 +156,7 @@ pub mod uuid {
 
     use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
     pub struct Uuid(uuid::Uuid);
 
     impl Uuid {
@@ -249,6 +262,18 @@ pub mod storage {
     }
 }
 
+/// How a search index was built, so stale indexes can be detected and rebuilt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchIndexInfo {
+    pub schema_version: u32,
+    pub tokenizer: String,
+    /// The most lines a clip found in this index may span
+    pub max_window: usize,
+    /// How the subtitles were cleaned before they were indexed
+    #[serde(default)]
+    pub cleanup: Vec<clean_sub::CleanupRule>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentData {
     pub metadata: metadata::MediaMetadata,
@@ -9,20 +9,48 @@ use crate::hash::Sha2Hash;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EpisodeMetadata {
-    pub season: u32,
+    /// The show the episode is from, when the name of its media says so
+    #[serde(default)]
+    pub show: Option<String>,
+    /// `None` for absolute numbering, counted from the first episode of the show
+    pub season: Option<u32>,
     pub episode: u32,
+    /// The episodes after `episode` in a multi-episode file
+    #[serde(default)]
+    pub more_episodes: Vec<u32>,
     pub title: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MediaMetadata {
     Episode(EpisodeMetadata),
+    Movie { title: String, year: u32 },
     Unknown(String),
 }
 
+impl EpisodeMetadata {
+    /// An episode of a season, with no show or more episodes
+    pub fn new<S: Into<String>>(season: u32, episode: u32, title: S) -> EpisodeMetadata {
+        EpisodeMetadata {
+            show: None,
+            season: Some(season),
+            episode,
+            more_episodes: vec![],
+            title: title.into(),
+        }
+    }
+}
+
 impl Display for EpisodeMetadata {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "S{:02} E{:02} {}", self.season, self.episode, self.title)
+        if let Some(season) = self.season {
+            write!(f, "S{:02} ", season)?;
+        }
+        write!(f, "E{:02}", self.episode)?;
+        if let Some(last) = self.more_episodes.last() {
+            write!(f, "-E{:02}", last)?;
+        }
+        write!(f, " {}", self.title)
     }
 }
 
@@ -30,6 +58,7 @@ impl Display for MediaMetadata {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             MediaMetadata::Episode(e) => write!(f, "{e}"),
+            MediaMetadata::Movie { title, year } => write!(f, "{title} ({year})"),
             MediaMetadata::Unknown(s) => write!(f, "{s}"),
         }
     }
@@ -39,6 +68,7 @@ impl MediaMetadata {
     pub fn title(&self) -> String {
         match self {
             MediaMetadata::Episode(e) => e.title.clone(),
+            MediaMetadata::Movie { title, .. } => title.clone(),
             MediaMetadata::Unknown(s) => s.clone(),
         }
     }
@@ -0,0 +1,151 @@
+//! Earlier versions of a chapter's subtitles.
+//!
+//! Adding different subtitles to a chapter keeps the old ones as revisions,
+//! the newest revision of each language is the one searched and rendered.
+
+use serde::{Deserialize, Serialize};
+use subrip::Subtitle;
+
+use crate::{language::Language, uuid::Uuid};
+
+/// One subtitle file of a chapter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtitleRevision {
+    /// For use with local search index
+    pub id: i64,
+    pub uuid: Uuid,
+    /// The position of the revision among all revisions of its chapter, from 1
+    pub number: usize,
+    pub language: Language,
+    /// When the revision was added, as `YYYY-MM-DD HH:MM:SS` in UTC, unknown
+    /// for revisions added before this was recorded
+    pub created_at: Option<String>,
+    /// The number of the revision this one is a copy of, if it was rolled back to
+    pub restored_from: Option<usize>,
+    /// How many subtitles the revision has
+    pub lines: usize,
+    /// The revision is the newest of its language
+    pub current: bool,
+}
+
+/// How one subtitle differs between two revisions
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubtitleChange<'a> {
+    Unchanged(&'a Subtitle),
+    /// The same text is shown at a different time
+    Retimed {
+        old: &'a Subtitle,
+        new: &'a Subtitle,
+    },
+    Removed(&'a Subtitle),
+    Added(&'a Subtitle),
+}
+
+impl<'a> SubtitleChange<'a> {
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, SubtitleChange::Unchanged(_))
+    }
+}
+
+/// Compare two revisions subtitle by subtitle, in order.
+///
+/// Subtitles are matched by their text, with the longest common subsequence,
+/// so changed text is a removal and an addition.
+pub fn diff_subtitles<'a>(old: &'a [Subtitle], new: &'a [Subtitle]) -> Vec<SubtitleChange<'a>> {
+    let same_text = |o: &Subtitle, n: &Subtitle| o.text.trim() == n.text.trim();
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(o, n)| same_text(o, n))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| same_text(o, n))
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j] is the longest common subsequence of old_mid[i..] and new_mid[j..]
+    let mut lcs = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if same_text(&old_mid[i], &new_mid[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let matched = |o: &'a Subtitle, n: &'a Subtitle| {
+        if o.start == n.start && o.end == n.end {
+            SubtitleChange::Unchanged(n)
+        } else {
+            SubtitleChange::Retimed { old: o, new: n }
+        }
+    };
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    changes.extend(old[..prefix].iter().zip(new).map(|(o, n)| matched(o, n)));
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() && j < new_mid.len() {
+        if same_text(&old_mid[i], &new_mid[j]) {
+            changes.push(matched(&old_mid[i], &new_mid[j]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            changes.push(SubtitleChange::Removed(&old_mid[i]));
+            i += 1;
+        } else {
+            changes.push(SubtitleChange::Added(&new_mid[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old_mid[i..].iter().map(SubtitleChange::Removed));
+    changes.extend(new_mid[j..].iter().map(SubtitleChange::Added));
+    changes.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .zip(&new[new.len() - suffix..])
+            .map(|(o, n)| matched(o, n)),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::generate_subtitle;
+
+    #[test]
+    fn diff_revisions() {
+        let old = generate_subtitle(&["zero", "one", "two", "three", "four"]);
+        let mut new = generate_subtitle(&["zero", "one", "deux", "three", "four", "five"]);
+        new[3].start += Duration::from_millis(500);
+
+        let changes = diff_subtitles(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                SubtitleChange::Unchanged(&new[0]),
+                SubtitleChange::Unchanged(&new[1]),
+                SubtitleChange::Removed(&old[2]),
+                SubtitleChange::Added(&new[2]),
+                SubtitleChange::Retimed {
+                    old: &old[3],
+                    new: &new[3]
+                },
+                SubtitleChange::Unchanged(&new[4]),
+                SubtitleChange::Added(&new[5]),
+            ]
+        );
+
+        assert!(diff_subtitles(&old, &old).iter().all(|c| c.is_unchanged()));
+        let removed = diff_subtitles(&old, &[]);
+        assert_eq!(removed.len(), old.len());
+        assert!(matches!(removed[0], SubtitleChange::Removed(_)));
+    }
+}
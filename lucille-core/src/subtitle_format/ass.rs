@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use subrip::Subtitle;
+
+use super::{finish_subtitles, SubtitleParseError};
+
+/// The fields of an event when the `[Events]` section has no `Format:` line
+const DEFAULT_EVENT_FORMAT: &[&str] = &[
+    "layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text",
+];
+
+fn error(line: usize, reason: impl Into<String>) -> SubtitleParseError {
+    SubtitleParseError::Ass {
+        line,
+        reason: reason.into(),
+    }
+}
+
+pub(super) fn parse(input: &str) -> Result<Vec<Subtitle>, SubtitleParseError> {
+    let input = input.trim_start_matches('\u{feff}');
+
+    let mut in_events = false;
+    let mut found_events = false;
+    let mut format = DEFAULT_EVENT_FORMAT
+        .iter()
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>();
+    let mut cues = vec![];
+    for (n, line) in input.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            found_events |= in_events;
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        let (kind, fields) = match line.split_once(':') {
+            Some(split) => split,
+            None => continue,
+        };
+        match kind.trim() {
+            "Format" => {
+                format = fields.split(',').map(|f| f.trim().to_lowercase()).collect();
+                if !format.iter().any(|f| f == "text") {
+                    return Err(error(n, "event format has no Text field"));
+                }
+            }
+            "Dialogue" => {
+                // the text is last, and may itself contain commas
+                let values = fields.splitn(format.len(), ',').collect::<Vec<_>>();
+                if values.len() != format.len() {
+                    return Err(error(
+                        n,
+                        format!("expected {} fields, found {}", format.len(), values.len()),
+                    ));
+                }
+                let field = |name: &str| {
+                    format
+                        .iter()
+                        .position(|f| f == name)
+                        .map(|idx| values[idx])
+                        .ok_or_else(|| error(n, format!("event format has no {} field", name)))
+                };
+                let start = field("start")?;
+                let start = parse_timestamp(start.trim())
+                    .ok_or_else(|| error(n, format!("invalid start time {:?}", start.trim())))?;
+                let end = field("end")?;
+                let end = parse_timestamp(end.trim())
+                    .ok_or_else(|| error(n, format!("invalid end time {:?}", end.trim())))?;
+                let text = strip_overrides(field("text")?);
+                cues.push((start, end, text.lines().map(str::to_string).collect()));
+            }
+            // comments, pictures, sounds and commands are never shown as text
+            _ => {}
+        }
+    }
+    if !found_events {
+        return Err(error(1, "no [Events] section"));
+    }
+
+    // events may be listed in any order, they are shown by time
+    cues.sort_by_key(|(start, end, _): &(Duration, Duration, Vec<String>)| (*start, *end));
+    Ok(finish_subtitles(cues))
+}
+
+/// `h:mm:ss.cc`, with centiseconds
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (hms, fraction) = s.split_once('.')?;
+    let mut parts = hms.split(':');
+    let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || fraction.is_empty() || fraction.len() > 3 {
+        return None;
+    }
+    let number = |s: &str| -> Option<u64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    };
+    // "5" is half a second, like "50"
+    let millis = number(fraction)? * 10u64.pow(3 - fraction.len() as u32);
+    let (hours, minutes, seconds) = (number(hours)?, number(minutes)?, number(seconds)?);
+    if minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+/// Remove override blocks such as `{\i1}` and `{\pos(10,20)}`, and the vector
+/// drawings between `{\p1}` and `{\p0}`. Line breaks `\N` and `\n` become new
+/// lines and hard spaces `\h` become spaces.
+fn strip_overrides(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut drawing = false;
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        if !drawing {
+            stripped.push_str(&rest[..open]);
+        }
+        let close = match rest[open..].find('}') {
+            Some(close) => close,
+            None => {
+                // an unclosed brace is text
+                rest = &rest[open..];
+                break;
+            }
+        };
+        let block = &rest[open + 1..open + close];
+        for tag in block.split('\\').skip(1) {
+            if let Some(scale) = tag
+                .strip_prefix('p')
+                .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+            {
+                drawing = matches!(scale.parse::<u32>(), Ok(s) if s > 0);
+            }
+        }
+        rest = &rest[open + close + 1..];
+    }
+    if !drawing {
+        stripped.push_str(rest);
+    }
+
+    stripped
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ASS: &str = r"[Script Info]
+Title: banana stand
+ScriptType: v4.00+
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize
+Style: Default,Arial,20
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0,0:00:04.50,0:00:06.00,Default,Michael,0,0,0,,{\i1}There's always money{\i0}\Nin the banana stand.
+Comment: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,not shown
+Dialogue: 0,0:00:01.00,0:00:02.25,Default,,0,0,0,,{\an8\pos(10,20)}No touching, please!
+Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,{\p1}m 0 0 l 100 0 100 100{\p0}
+";
+
+    #[test]
+    fn parse_events() {
+        let subs = parse(ASS).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].idx, 1);
+        assert_eq!(subs[0].start, Duration::from_millis(1000));
+        assert_eq!(subs[0].end, Duration::from_millis(2250));
+        assert_eq!(subs[0].text, "No touching, please!\n");
+        assert_eq!(subs[1].start, Duration::from_millis(4500));
+        assert_eq!(subs[1].text, "There's always money\nin the banana stand.\n");
+    }
+
+    #[test]
+    fn parse_ssa_format() {
+        let ssa = "[Events]\nFormat: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: Marked=0,0:00:01.00,0:00:02.00,Default,,0000,0000,0000,,Hello\\hthere\n";
+        let subs = parse(ssa).unwrap();
+        assert_eq!(subs[0].text, "Hello there\n");
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        assert_eq!(
+            parse("[Script Info]\nTitle: nothing\n").unwrap_err(),
+            error(1, "no [Events] section")
+        );
+        assert_eq!(
+            parse("[Events]\nDialogue: 0,0:00:01.00,later,Default,,0,0,0,,Hello\n").unwrap_err(),
+            error(2, "invalid end time \"later\"")
+        );
+        assert_eq!(
+            parse("[Events]\nDialogue: 0,0:00:01.00\n").unwrap_err(),
+            error(2, "expected 10 fields, found 2")
+        );
+    }
+
+    #[test]
+    fn reject_out_of_range_timestamps() {
+        assert_eq!(
+            parse_timestamp("1:59:59.99"),
+            Some(Duration::from_millis(7_199_990))
+        );
+        assert_eq!(parse_timestamp("0:60:00.00"), None);
+        assert_eq!(parse_timestamp("0:00:60.00"), None);
+    }
+}
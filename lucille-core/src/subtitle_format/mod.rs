@@ -0,0 +1,163 @@
+//! Parsers for the subtitle formats found next to media, all producing the
+//! same [`Subtitle`] list as SRT files.
+//!
+//! The text of every parsed subtitle is plain text with one `\n` terminated
+//! line per line on screen, formatting and positioning is dropped.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use subrip::Subtitle;
+
+mod ass;
+mod webvtt;
+
+/// Sidecar extensions in the order they are used, when a media file has more than one
+pub const DEFAULT_SIDECAR_PRIORITY: &[&str] = &["srt", "vtt", "ass", "ssa"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+    /// Advanced SubStation Alpha, and the SubStation Alpha it extends
+    Ass,
+}
+
+impl SubtitleFormat {
+    pub const ALL: [SubtitleFormat; 3] = [
+        SubtitleFormat::Srt,
+        SubtitleFormat::WebVtt,
+        SubtitleFormat::Ass,
+    ];
+
+    /// File extensions of the format, without the dot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            SubtitleFormat::Srt => &["srt"],
+            SubtitleFormat::WebVtt => &["vtt"],
+            SubtitleFormat::Ass => &["ass", "ssa"],
+        }
+    }
+
+    /// The format of files with the extension `ext`, ignoring case
+    pub fn from_extension(ext: &str) -> Option<SubtitleFormat> {
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        SubtitleFormat::ALL
+            .into_iter()
+            .find(|f| f.extensions().contains(&ext.as_str()))
+    }
+
+    pub fn parse(self, text: &str) -> Result<Vec<Subtitle>, SubtitleParseError> {
+        match self {
+            SubtitleFormat::Srt => subrip::parse(text).map_err(SubtitleParseError::Srt),
+            SubtitleFormat::WebVtt => webvtt::parse(text),
+            SubtitleFormat::Ass => ass::parse(text),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown subtitle format: {0:?}")]
+pub struct UnknownSubtitleFormat(String);
+
+/// Accepts any extension of the format, or its name, e.g. `ssa` or `WebVtt`
+impl FromStr for SubtitleFormat {
+    type Err = UnknownSubtitleFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        SubtitleFormat::from_extension(&lower)
+            .or_else(|| {
+                SubtitleFormat::ALL
+                    .into_iter()
+                    .find(|f| format!("{:?}", f).to_lowercase() == lower)
+            })
+            .ok_or_else(|| UnknownSubtitleFormat(s.to_string()))
+    }
+}
+
+impl fmt::Display for SubtitleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extensions()[0])
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubtitleParseError {
+    #[error("invalid SRT: {0:?}")]
+    Srt(subrip::Error),
+    #[error("invalid WebVTT on line {line}: {reason}")]
+    WebVtt { line: usize, reason: String },
+    #[error("invalid ASS/SSA on line {line}: {reason}")]
+    Ass { line: usize, reason: String },
+}
+
+impl PartialEq for SubtitleParseError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Srt(l), Self::Srt(r)) => match (l, r) {
+                (
+                    subrip::Error::ParseError(lsubs, lerr),
+                    subrip::Error::ParseError(rsubs, rerr),
+                ) => lsubs == rsubs && lerr == rerr,
+                _ => false,
+            },
+            (
+                Self::WebVtt { line, reason },
+                Self::WebVtt {
+                    line: r_line,
+                    reason: r_reason,
+                },
+            )
+            | (
+                Self::Ass { line, reason },
+                Self::Ass {
+                    line: r_line,
+                    reason: r_reason,
+                },
+            ) => line == r_line && reason == r_reason,
+            _ => false,
+        }
+    }
+}
+
+/// Number the subtitles in order, and end every line of their text with `\n`
+/// like the SRT parser does
+fn finish_subtitles(cues: Vec<(Duration, Duration, Vec<String>)>) -> Vec<Subtitle> {
+    cues.into_iter()
+        .filter(|(_, _, lines)| lines.iter().any(|l| !l.trim().is_empty()))
+        .enumerate()
+        .map(|(idx, (start, end, lines))| Subtitle {
+            idx: idx as u32 + 1,
+            start,
+            end,
+            text: lines.iter().map(|l| format!("{}\n", l)).collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(
+            SubtitleFormat::from_extension("SSA"),
+            Some(SubtitleFormat::Ass)
+        );
+        assert_eq!(
+            SubtitleFormat::from_extension(".vtt"),
+            Some(SubtitleFormat::WebVtt)
+        );
+        assert_eq!(SubtitleFormat::from_extension("sub"), None);
+        assert_eq!(
+            SubtitleFormat::from_str("webvtt").unwrap(),
+            SubtitleFormat::WebVtt
+        );
+        assert!(SubtitleFormat::from_str("idx").is_err());
+        for ext in DEFAULT_SIDECAR_PRIORITY {
+            assert!(SubtitleFormat::from_extension(ext).is_some());
+        }
+    }
+}
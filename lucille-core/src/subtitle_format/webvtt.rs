@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use subrip::Subtitle;
+
+use super::{finish_subtitles, SubtitleParseError};
+
+fn error(line: usize, reason: impl Into<String>) -> SubtitleParseError {
+    SubtitleParseError::WebVtt {
+        line,
+        reason: reason.into(),
+    }
+}
+
+pub(super) fn parse(input: &str) -> Result<Vec<Subtitle>, SubtitleParseError> {
+    let input = input.trim_start_matches('\u{feff}');
+    // line numbers are 1 based, for error messages
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(n, l)| (n + 1, l))
+        .peekable();
+
+    match lines.next() {
+        Some((_, header))
+            if header == "WEBVTT"
+                || header.starts_with("WEBVTT ")
+                || header.starts_with("WEBVTT\t") => {}
+        _ => return Err(error(1, "missing WEBVTT header")),
+    }
+
+    let mut cues = vec![];
+    loop {
+        // the rest of the current block, the header or a cue, ends at a blank line
+        for (_, line) in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        while matches!(lines.peek(), Some((_, l)) if l.trim().is_empty()) {
+            lines.next();
+        }
+        let (n, first) = match lines.next() {
+            Some(line) => line,
+            None => break,
+        };
+        if ["NOTE", "STYLE", "REGION"].iter().any(|kw| {
+            first == *kw
+                || first.starts_with(&format!("{} ", kw))
+                || first.starts_with(&format!("{}\t", kw))
+        }) {
+            continue;
+        }
+
+        // an optional cue identifier before the timings
+        let (n, timing) = if first.contains("-->") {
+            (n, first)
+        } else {
+            match lines.next() {
+                Some((n, timing)) if timing.contains("-->") => (n, timing),
+                _ => return Err(error(n, "cue has no timings")),
+            }
+        };
+        let (start, end) = parse_timings(timing)
+            .ok_or_else(|| error(n, format!("invalid cue timings {:?}", timing)))?;
+
+        let mut text = vec![];
+        while let Some((_, line)) = lines.next_if(|(_, l)| !l.trim().is_empty()) {
+            text.push(clean_payload(line));
+        }
+        cues.push((start, end, text));
+    }
+
+    Ok(finish_subtitles(cues))
+}
+
+/// `start --> end`, followed by optional cue settings
+fn parse_timings(line: &str) -> Option<(Duration, Duration)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+/// `hh:mm:ss.ttt`, where the hours are optional and may be more than two digits
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (hms, millis) = s.split_once('.')?;
+    if millis.len() != 3 {
+        return None;
+    }
+    let millis = parse_digits(millis)?;
+    let parts = hms.split(':').collect::<Vec<_>>();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (parse_digits(h)?, parse_digits(m)?, parse_digits(s)?),
+        [m, s] => (0, parse_digits(m)?, parse_digits(s)?),
+        _ => return None,
+    };
+    if minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+fn parse_digits(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Remove the markup of a line of cue text: tags such as `<v Speaker>`, `<i>`
+/// and `<00:00:01.000>`, and character references
+fn clean_payload(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_cues() {
+        let vtt = "\u{feff}WEBVTT - the banana stand\nKind: captions\n\nNOTE this is\na comment\n\nSTYLE\n::cue { color: yellow }\n\n1\n00:01.000 --> 00:02.500 align:start\n<v Lucille>I don't understand the question,\n<i>and I won't respond to it.</i>\n\n01:00:03.000 --> 01:00:04.000\nTom &amp; Jerry &lt;3\n\n00:00:05.000 --> 00:00:06.000\n<c.silent></c>\n";
+        let subs = parse(vtt).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].idx, 1);
+        assert_eq!(subs[0].start, Duration::from_millis(1000));
+        assert_eq!(subs[0].end, Duration::from_millis(2500));
+        assert_eq!(
+            subs[0].text,
+            "I don't understand the question,\nand I won't respond to it.\n"
+        );
+        assert_eq!(subs[1].start, Duration::from_secs(3603));
+        assert_eq!(subs[1].text, "Tom & Jerry <3\n");
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        assert_eq!(
+            parse("1\n00:01.000 --> 00:02.000\nhello\n").unwrap_err(),
+            error(1, "missing WEBVTT header")
+        );
+        assert_eq!(
+            parse("WEBVTT\n\n00:01.000 --> 00:02\nhello\n").unwrap_err(),
+            error(3, "invalid cue timings \"00:01.000 --> 00:02\"")
+        );
+        assert_eq!(
+            parse("WEBVTT\n\nintro\nhello\n").unwrap_err(),
+            error(3, "cue has no timings")
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Corrections for subtitles which are out of sync with their media.
+//!
+//! A subtitle file may be shifted by a few seconds, or timed against a release
+//! with a different frame rate, e.g. a 25 fps PAL release of a 23.976 fps film
+//! drifts further out of sync as the episode goes on.
+
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use subrip::Subtitle;
+
+/// A linear correction of subtitle times, `time * scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimingAdjustment {
+    /// Milliseconds added to every time after scaling, negative shows subtitles earlier
+    pub offset_ms: i64,
+    /// Every time is multiplied by this before the offset is added
+    pub scale: f64,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum InvalidTiming {
+    #[error("timing scale must be a positive number, not {0}")]
+    Scale(f64),
+    #[error("invalid frame rate change {0:?}, expected `SUBTITLE_FPS:MEDIA_FPS`")]
+    FrameRates(String),
+}
+
+impl TimingAdjustment {
+    /// Leave every time as it is
+    pub const IDENTITY: TimingAdjustment = TimingAdjustment {
+        offset_ms: 0,
+        scale: 1.0,
+    };
+
+    pub fn new(offset_ms: i64, scale: f64) -> Result<TimingAdjustment, InvalidTiming> {
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(InvalidTiming::Scale(scale));
+        }
+        Ok(TimingAdjustment { offset_ms, scale })
+    }
+
+    /// Show every subtitle `offset_ms` later, or earlier when negative
+    pub fn offset(offset_ms: i64) -> TimingAdjustment {
+        TimingAdjustment {
+            offset_ms,
+            ..TimingAdjustment::IDENTITY
+        }
+    }
+
+    /// Stretch the times of subtitles made for media at `subtitle_fps` to
+    /// media at `media_fps`. The same frames at a slower frame rate are shown
+    /// later, so 25 fps subtitles on 23.976 fps media are scaled up.
+    pub fn frame_rates(
+        subtitle_fps: f64,
+        media_fps: f64,
+    ) -> Result<TimingAdjustment, InvalidTiming> {
+        TimingAdjustment::new(0, subtitle_fps / media_fps)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == TimingAdjustment::IDENTITY
+    }
+
+    /// The corrected time, times before the start of the media become zero
+    pub fn apply_time(&self, time: Duration) -> Duration {
+        let millis = (time.as_secs_f64() * 1000.0 * self.scale).round() as i64 + self.offset_ms;
+        Duration::from_millis(millis.max(0) as u64)
+    }
+
+    /// Correct the times of every subtitle. None are removed, so positions in
+    /// the list still refer to the same lines.
+    pub fn apply(&self, subs: &[Subtitle]) -> Vec<Subtitle> {
+        subs.iter()
+            .map(|sub| {
+                let mut adjusted = sub.clone();
+                adjusted.start = self.apply_time(sub.start);
+                adjusted.end = self.apply_time(sub.end);
+                adjusted
+            })
+            .collect()
+    }
+}
+
+impl Default for TimingAdjustment {
+    fn default() -> Self {
+        TimingAdjustment::IDENTITY
+    }
+}
+
+impl fmt::Display for TimingAdjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "offset {:+.3}s, scale {}",
+            self.offset_ms as f64 / 1000.0,
+            self.scale
+        )
+    }
+}
+
+/// Parse a frame rate change like `25:23.976` into the subtitle and media frame rates
+pub fn parse_frame_rates(s: &str) -> Result<(f64, f64), InvalidTiming> {
+    let invalid = || InvalidTiming::FrameRates(s.to_string());
+    let (subtitle, media) = s.split_once(':').ok_or_else(invalid)?;
+    let fps = |rate: &str| {
+        rate.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|r| r.is_finite() && *r > 0.0)
+            .ok_or_else(invalid)
+    };
+    Ok((fps(subtitle)?, fps(media)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::generate_subtitle;
+
+    #[test]
+    fn adjust_subtitle_times() {
+        let subs = generate_subtitle(&["zero", "one", "two"]);
+
+        let earlier = TimingAdjustment::offset(-2000).apply(&subs);
+        assert_eq!(earlier.len(), subs.len());
+        assert_eq!(earlier[0].start, Duration::ZERO);
+        assert_eq!(earlier[0].end, Duration::ZERO);
+        assert_eq!(earlier[1].start, Duration::from_secs(1));
+        assert_eq!(earlier[2].end, Duration::from_millis(5500));
+        assert_eq!(earlier[2].text, subs[2].text);
+
+        let pal = TimingAdjustment::frame_rates(25.0, 23.976).unwrap();
+        let stretched = pal.apply(&subs);
+        assert_eq!(stretched[2].start, Duration::from_millis(6256));
+
+        assert_eq!(TimingAdjustment::IDENTITY.apply(&subs), subs);
+    }
+
+    #[test]
+    fn reject_invalid_timing() {
+        assert_eq!(
+            TimingAdjustment::new(0, 0.0).unwrap_err(),
+            InvalidTiming::Scale(0.0)
+        );
+        assert!(TimingAdjustment::new(0, f64::NAN).is_err());
+        assert_eq!(parse_frame_rates("25:23.976").unwrap(), (25.0, 23.976));
+        assert!(parse_frame_rates("25").is_err());
+        assert!(parse_frame_rates("25:0").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! A single-file serialization of a search index.
+//!
+//! The bundle starts with [`MAGIC`], the bundle version and the index uuid,
+//! followed by every file of the committed index as a path and its contents.
+//! Strings and byte arrays are prefixed by their little endian `u64` length.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use lucille_core::uuid::Uuid;
+use tantivy::{
+    directory::{Directory, RamDirectory},
+    Index,
+};
+
+use crate::error::TError;
+
+const MAGIC: &[u8] = b"lucille search index\n";
+const BUNDLE_VERSION: u64 = 1;
+const META_FILE: &str = "meta.json";
+
+pub(crate) fn write_bundle(index: &Index, uuid: Uuid) -> Result<Vec<u8>, TError> {
+    let directory = index.directory();
+
+    let mut paths = vec![PathBuf::from(META_FILE)];
+    for segment in index.searchable_segment_metas()? {
+        let mut segment_files = segment.list_files().into_iter().collect::<Vec<_>>();
+        segment_files.sort();
+        for path in segment_files {
+            // not every segment has every component, e.g. deletes
+            if directory
+                .exists(&path)
+                .map_err(tantivy::TantivyError::from)?
+            {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut bundle = MAGIC.to_vec();
+    bundle.extend(BUNDLE_VERSION.to_le_bytes());
+    write_bytes(&mut bundle, uuid.to_string().as_bytes());
+    bundle.extend((paths.len() as u64).to_le_bytes());
+    for path in paths {
+        // read the raw files, including the footers tantivy checks on open
+        let data = directory
+            .atomic_read(&path)
+            .map_err(tantivy::TantivyError::from)?;
+        let name = path
+            .to_str()
+            .ok_or_else(|| TError::InvalidBundle(format!("file name {:?} is not utf-8", path)))?;
+        write_bytes(&mut bundle, name.as_bytes());
+        write_bytes(&mut bundle, &data);
+    }
+    Ok(bundle)
+}
+
+pub(crate) fn read_bundle(bundle: &[u8]) -> Result<(Uuid, Index), TError> {
+    let mut reader = BundleReader { remaining: bundle };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(TError::InvalidBundle(
+            "not a search index bundle".to_string(),
+        ));
+    }
+    let version = reader.read_u64()?;
+    if version != BUNDLE_VERSION {
+        return Err(TError::InvalidBundle(format!(
+            "unsupported bundle version {}",
+            version
+        )));
+    }
+    let uuid = reader.read_str()?;
+    let uuid = Uuid::from_str(uuid)
+        .map_err(|e| TError::InvalidBundle(format!("invalid uuid {:?}: {}", uuid, e)))?;
+
+    let directory = RamDirectory::create();
+    let file_count = reader.read_u64()?;
+    for _ in 0..file_count {
+        let path = reader.read_str()?;
+        let data = reader.read_bytes()?;
+        directory
+            .atomic_write(Path::new(path), data)
+            .map_err(tantivy::TantivyError::from)?;
+    }
+    if !reader.remaining.is_empty() {
+        return Err(TError::InvalidBundle(
+            "unexpected data after the last file".to_string(),
+        ));
+    }
+
+    let index = Index::open(directory)?;
+    Ok((uuid, index))
+}
+
+fn write_bytes(bundle: &mut Vec<u8>, bytes: &[u8]) {
+    bundle.extend((bytes.len() as u64).to_le_bytes());
+    bundle.extend(bytes);
+}
+
+struct BundleReader<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> BundleReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TError> {
+        if self.remaining.len() < len {
+            return Err(TError::InvalidBundle("bundle is truncated".to_string()));
+        }
+        let (taken, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Ok(taken)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, TError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("took 8 bytes")))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], TError> {
+        let len = self.read_u64()?;
+        let len = usize::try_from(len)
+            .map_err(|_| TError::InvalidBundle("bundle is truncated".to_string()))?;
+        self.take(len)
+    }
+
+    fn read_str(&mut self) -> Result<&'a str, TError> {
+        std::str::from_utf8(self.read_bytes()?)
+            .map_err(|_| TError::InvalidBundle("bundle contains invalid utf-8".to_string()))
+    }
+}
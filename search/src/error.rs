@@ -1,6 +1,14 @@
 use tantivy::TantivyError;
 use thiserror::Error;
 
+use crate::query::QueryError;
+
 #[derive(Error, Debug)]
-#[error(transparent)]
-pub struct TError(#[from] TantivyError);
+pub enum TError {
+    #[error(transparent)]
+    Tantivy(#[from] TantivyError),
+    #[error("invalid search index bundle: {0}")]
+    InvalidBundle(String),
+    #[error("invalid query: {0}")]
+    Query(#[from] QueryError),
+}
@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+use tantivy::{tokenizer::TextAnalyzer, SnippetGenerator};
+
+/// Marks the parts of a line of text which matched a query.
+///
+/// Exact terms are found with tantivy's [`SnippetGenerator`], fuzzy terms
+/// are not reported by tantivy so they are matched here by edit distance.
+pub struct Highlighter {
+    snippets: SnippetGenerator,
+    tokenizer: TextAnalyzer,
+    fuzzy_terms: Vec<String>,
+    distance: u8,
+}
+
+impl Highlighter {
+    pub(crate) fn new(
+        mut snippets: SnippetGenerator,
+        tokenizer: TextAnalyzer,
+        fuzzy_terms: Vec<String>,
+        distance: u8,
+    ) -> Highlighter {
+        // a snippet covering the whole line keeps offsets relative to its start
+        snippets.set_max_num_chars(usize::MAX);
+        Highlighter {
+            snippets,
+            tokenizer,
+            fuzzy_terms,
+            distance,
+        }
+    }
+
+    /// Byte ranges of `text` which matched, sorted and non-overlapping
+    pub fn highlight(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges = self.snippets.snippet(text).highlighted().to_vec();
+
+        if !self.fuzzy_terms.is_empty() {
+            let mut token_stream = self.tokenizer.token_stream(text);
+            while let Some(token) = token_stream.next() {
+                let fuzzy_match = self
+                    .fuzzy_terms
+                    .iter()
+                    .any(|term| edit_distance(term, &token.text) <= self.distance as usize);
+                if fuzzy_match {
+                    ranges.push(token.offset_from..token.offset_to);
+                }
+            }
+        }
+
+        ranges.sort_by_key(|r| (r.start, r.end));
+        ranges.dedup_by(|next, prev| {
+            if next.start < prev.end {
+                prev.end = prev.end.max(next.end);
+                true
+            } else {
+                false
+            }
+        });
+        ranges
+    }
+}
+
+/// Levenshtein distance, counting a transposition as a single edit
+/// to agree with tantivy's fuzzy term queries
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("banana", "banana"), 0);
+        assert_eq!(edit_distance("banana", "bananna"), 1);
+        assert_eq!(edit_distance("money", "mnoey"), 1);
+        assert_eq!(edit_distance("money", "monkey"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,166 @@
+use lucille_core::language::Language;
+use tantivy::{
+    tokenizer::{
+        AsciiFoldingFilter, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter,
+        TextAnalyzer,
+    },
+    Index,
+};
+
+/// The name a language's tokenizer is registered under, and recorded in the schema
+pub fn tokenizer_name(language: Language) -> String {
+    format!("lucille_{}", language.code())
+}
+
+/// The language whose tokenizer is registered as `name`
+pub fn tokenizer_language(name: &str) -> Option<Language> {
+    Language::ALL
+        .into_iter()
+        .find(|l| tokenizer_name(*l) == name)
+}
+
+/// The tokenizer of the words of each line as they were written, for suggestions
+pub(crate) const WORDS_TOKENIZER: &str = "lucille_words";
+
+/// Make the tokenizers for every [`Language`] available to `index`
+pub(crate) fn register_tokenizers(index: &Index) {
+    for language in Language::ALL {
+        index
+            .tokenizers()
+            .register(&tokenizer_name(language), analyzer(language));
+    }
+    index
+        .tokenizers()
+        .register(WORDS_TOKENIZER, words_analyzer());
+}
+
+/// Only lowercases, so the terms are words which can be put back in a query
+pub(crate) fn words_analyzer() -> TextAnalyzer {
+    TextAnalyzer::from(SimpleTokenizer)
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+}
+
+/// Stopwords are removed before folding, so they are listed with their accents.
+/// Folding happens before stemming so that lines typed without accents
+/// still stem to the same term.
+fn analyzer(language: Language) -> TextAnalyzer {
+    let mut analyzer = TextAnalyzer::from(SimpleTokenizer)
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser);
+    let stopwords = stopwords(language);
+    if !stopwords.is_empty() {
+        analyzer = analyzer.filter(StopWordFilter::remove(
+            stopwords.iter().map(|w| w.to_string()).collect(),
+        ));
+    }
+    analyzer
+        .filter(AsciiFoldingFilter)
+        .filter(Stemmer::new(stemmer_language(language)))
+}
+
+fn stemmer_language(language: Language) -> tantivy::tokenizer::Language {
+    use tantivy::tokenizer::Language as T;
+    match language {
+        Language::English => T::English,
+        Language::Spanish => T::Spanish,
+        Language::French => T::French,
+        Language::German => T::German,
+        Language::Italian => T::Italian,
+        Language::Portuguese => T::Portuguese,
+    }
+}
+
+/// Words which say nothing about what a line is about, left out when
+/// looking for similar lines. For most languages these are the stopwords,
+/// which are not indexed anyway.
+///
+/// English contractions are split at the apostrophe, so their endings
+/// (`there's`, `don't`, `we'll`) are listed too.
+pub(crate) fn filler_words(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &[
+            "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into",
+            "is", "it", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+            "they", "this", "to", "was", "will", "with", "s", "t", "d", "ll", "m", "re", "ve",
+        ],
+        _ => stopwords(language),
+    }
+}
+
+/// Only the most common function words, subtitles are short and
+/// dropping too much makes quotes hard to find.
+///
+/// English keeps every word, so many of its famous quotes are made of
+/// nothing but function words, e.g. "to be or not to be".
+fn stopwords(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &[],
+        Language::Spanish => &[
+            "a", "al", "de", "del", "el", "en", "es", "la", "las", "lo", "los", "o", "para", "por",
+            "que", "se", "un", "una", "y",
+        ],
+        Language::French => &[
+            "à", "au", "aux", "de", "des", "du", "en", "est", "et", "l", "la", "le", "les", "ou",
+            "que", "un", "une",
+        ],
+        Language::German => &[
+            "der", "die", "das", "dem", "den", "des", "ein", "eine", "einen", "einem", "einer",
+            "und", "oder", "zu", "im", "in", "ist",
+        ],
+        Language::Italian => &[
+            "a", "al", "del", "della", "di", "e", "il", "in", "la", "le", "lo", "gli", "i", "o",
+            "un", "una", "è",
+        ],
+        Language::Portuguese => &[
+            "a", "as", "ao", "da", "das", "de", "do", "dos", "e", "é", "em", "o", "os", "ou", "um",
+            "uma",
+        ],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tokens(language: Language, text: &str) -> Vec<String> {
+        let mut stream = analyzer(language).token_stream(text);
+        let mut tokens = vec![];
+        while let Some(token) = stream.next() {
+            tokens.push(token.text.clone());
+        }
+        tokens
+    }
+
+    #[test]
+    fn tokenizer_names_roundtrip() {
+        for language in Language::ALL {
+            assert_eq!(
+                tokenizer_language(&tokenizer_name(language)),
+                Some(language)
+            );
+        }
+        assert_eq!(tokenizer_language("en_stem"), None);
+    }
+
+    #[test]
+    fn fold_accents_before_stemming() {
+        assert_eq!(
+            tokens(Language::Spanish, "canción"),
+            tokens(Language::Spanish, "cancion")
+        );
+        assert_eq!(
+            tokens(Language::Spanish, "canciones"),
+            tokens(Language::Spanish, "canción")
+        );
+    }
+
+    #[test]
+    fn remove_stopwords() {
+        assert_eq!(tokens(Language::French, "à la plage"), vec!["plag"]);
+        assert_eq!(
+            tokens(Language::English, "the banana"),
+            vec!["the", "banana"]
+        );
+    }
+}
@@ -1,17 +1,39 @@
 use std::{
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap},
+    ops::{Range, RangeInclusive},
     path::Path,
 };
 
-use lucille_core::uuid::Uuid;
-use tantivy::{collector::TopDocs, doc, query::QueryParser, schema::*, Index};
+use lucille_core::{clean_sub::SubtitleCleaner, language::Language, uuid::Uuid, SearchIndexInfo};
+use serde::{Deserialize, Serialize};
+use tantivy::{
+    collector::{Collector, DocSetCollector, SegmentCollector},
+    doc,
+    query::{
+        BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, QueryClone, RangeQuery, TermQuery,
+    },
+    schema::*,
+    store::Compressor,
+    DocAddress, DocId, Index, Score, SegmentOrdinal, SegmentReader, SnippetGenerator,
+};
 
-use self::srt_loader::IndexableEpisode;
+pub use self::srt_loader::IndexableEpisode;
 
+mod bundle;
 pub mod error;
+mod highlight;
+mod language;
+pub mod query;
+mod similar;
 mod srt_loader;
+mod suggest;
 
 use error::TError;
+pub use highlight::Highlighter;
+pub use language::{tokenizer_language, tokenizer_name};
+use query::CompiledQuery;
+pub use query::{parse_query, ParsedQuery, QueryError};
+pub use suggest::{Suggestion, Suggestions};
 
 #[derive(Debug)]
 pub struct SearchIndex {
@@ -28,16 +50,242 @@ impl SearchIndex {
         &self,
         q: &str,
         search_window: usize,
+        mode: QueryMode,
+        filter: &SearchFilter,
+    ) -> Result<HashMap<usize, EpisodeScore>, TError> {
+        self.search_with(q, search_window, mode, filter, false)
+    }
+
+    /// Like [`SearchIndex::search`], but also record how every clip was scored,
+    /// see [`EpisodeScore::explain`]. This is much slower, it is meant for debugging.
+    pub fn explain(
+        &self,
+        q: &str,
+        search_window: usize,
+        mode: QueryMode,
+        filter: &SearchFilter,
+    ) -> Result<HashMap<usize, EpisodeScore>, TError> {
+        self.search_with(q, search_window, mode, filter, true)
+    }
+
+    fn search_with(
+        &self,
+        q: &str,
+        search_window: usize,
+        mode: QueryMode,
+        filter: &SearchFilter,
+        explain: bool,
     ) -> Result<HashMap<usize, EpisodeScore>, TError> {
-        search_impl(&self.inner, q, search_window).map_err(TError::from)
+        let search_window = self.limit_window(search_window)?;
+        search_impl(&self.inner, q, search_window, mode, filter, explain)
+    }
+
+    /// Find clips worded like `text`, typically the lines of another clip.
+    ///
+    /// The most distinctive terms of `text` are searched for, any of them may
+    /// match. Lines of the index which `text` came from will match too.
+    pub fn more_like_this(
+        &self,
+        text: &str,
+        search_window: usize,
+        filter: &SearchFilter,
+    ) -> Result<HashMap<usize, EpisodeScore>, TError> {
+        let search_window = self.limit_window(search_window)?;
+        similar::more_like_this_impl(&self.inner, text, search_window, filter)
+    }
+
+    /// Build a [`Highlighter`] for the results of [`SearchIndex::more_like_this`]
+    pub fn more_like_this_highlighter(&self, text: &str) -> Result<Highlighter, TError> {
+        similar::more_like_this_highlighter(&self.inner, text)
+    }
+
+    /// Clips are never longer than the index was built for
+    fn limit_window(&self, search_window: usize) -> Result<usize, TError> {
+        Ok(match self.info()? {
+            Some(info) => search_window.min(info.max_window),
+            None => search_window,
+        })
+    }
+
+    /// How this index was built, `None` if it predates schema versioning
+    pub fn info(&self) -> Result<Option<SearchIndexInfo>, TError> {
+        let payload = match self.inner.load_metas()?.payload {
+            Some(payload) => payload,
+            None => return Ok(None),
+        };
+        match serde_json::from_str(&payload) {
+            Ok(info) => Ok(Some(info)),
+            Err(e) => {
+                log::warn!("search index {} has invalid metadata: {}", self.uuid, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Build a [`Highlighter`] which marks the terms of `q` in lines of text
+    pub fn highlighter(&self, q: &str, mode: QueryMode) -> Result<Highlighter, TError> {
+        highlighter_impl(&self.inner, q, mode)
+    }
+
+    /// Suggest up to `limit` completions of the last word of `q`, and
+    /// up to `limit` corrections for each misspelled word of `q`.
+    ///
+    /// Suggestions are terms of the `body` field, so completions are only
+    /// offered while the last word is being typed, i.e. `q` does not end in a separator.
+    pub fn suggest(&self, q: &str, limit: usize) -> Result<Suggestions, TError> {
+        let schema = self.inner.schema();
+        let body = get_field(&schema, SchemaField::Body);
+        let words = get_field(&schema, SchemaField::Words);
+        suggest::suggest_impl(&self.inner, body, words, q, limit).map_err(TError::from)
     }
 
     pub fn open_in_dir<P: AsRef<Path>>(uuid: Uuid, dir: P) -> Result<SearchIndex, TError> {
-        let index = Index::open_in_dir(dir.as_ref())?;
+        let mut index = Index::open_in_dir(dir.as_ref())?;
+        prepare_index(&mut index);
         Ok(SearchIndex { inner: index, uuid })
     }
+
+    /// Serialize the committed state of this index, wherever it is stored,
+    /// into a single bundle which [`SearchIndex::from_bundle`] can load
+    pub fn to_bundle(&self) -> Result<Vec<u8>, TError> {
+        bundle::write_bundle(&self.inner, self.uuid)
+    }
+
+    /// Load an index bundle into memory, no filesystem access is needed
+    pub fn from_bundle(bundle: &[u8]) -> Result<SearchIndex, TError> {
+        let (uuid, mut index) = bundle::read_bundle(bundle)?;
+        prepare_index(&mut index);
+        Ok(SearchIndex { inner: index, uuid })
+    }
+
+    /// Modify the index in place, instead of creating a new one.
+    ///
+    /// All documents for the srt ids in `remove` are deleted, then every
+    /// episode in `add` is indexed, recording the given `max_window`.
+    pub fn update<I: Into<IndexableEpisode>>(
+        &self,
+        add: impl Iterator<Item = I>,
+        remove: &[i64],
+        max_window: usize,
+    ) -> Result<(), TError> {
+        self.prepare_update(add, remove, max_window)?.commit()
+    }
+
+    /// Like [`SearchIndex::update`], but nothing changes until the returned
+    /// update is committed, so it can be committed along with other changes.
+    ///
+    /// `add` must be cleaned the way the index was built, see [`SearchIndexInfo::cleanup`].
+    pub fn prepare_update<I: Into<IndexableEpisode>>(
+        &self,
+        add: impl Iterator<Item = I>,
+        remove: &[i64],
+        max_window: usize,
+    ) -> Result<PreparedUpdate, TError> {
+        let ieps = add.map(|e| e.into()).collect::<Vec<_>>();
+        let cleanup = match self.info()? {
+            Some(info) => SubtitleCleaner::new(&info.cleanup),
+            None => SubtitleCleaner::index_default(),
+        };
+        update_index_impl(&self.inner, ieps.as_slice(), remove, max_window, &cleanup)
+            .map_err(TError::from)
+    }
+}
+
+/// Changes to a search index which are not searchable until committed,
+/// see [`SearchIndex::prepare_update`]. Dropping it discards the changes.
+pub struct PreparedUpdate {
+    index_writer: tantivy::IndexWriter,
+    info: SearchIndexInfo,
 }
 
+impl PreparedUpdate {
+    pub fn commit(mut self) -> Result<(), TError> {
+        commit_with_info(&mut self.index_writer, &self.info).map_err(TError::from)
+    }
+}
+
+/// How the terms of a query should be matched against the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Only match the (stemmed) terms as written
+    Exact,
+    /// Also match terms within `distance` edits of the query terms.
+    ///
+    /// Exact matches are always ranked above fuzzy ones.
+    Fuzzy { distance: u8 },
+}
+
+impl Default for QueryMode {
+    fn default() -> Self {
+        QueryMode::Exact
+    }
+}
+
+/// Bump this whenever `create_schema`, the tokenizers or [`SearchIndexInfo`]
+/// change, existing indexes must then be rebuilt
+pub const SCHEMA_VERSION: u32 = 6;
+
+/// The [`SearchIndexInfo`] for an index built now with `max_window` for
+/// `language`, of subtitles cleaned by `cleanup`
+pub fn index_info(
+    max_window: usize,
+    language: Language,
+    cleanup: &SubtitleCleaner,
+) -> SearchIndexInfo {
+    SearchIndexInfo {
+        schema_version: SCHEMA_VERSION,
+        tokenizer: tokenizer_name(language),
+        max_window,
+        cleanup: cleanup.rules().to_vec(),
+    }
+}
+
+/// Explain why an index built with `info` can not be used by this version, if it can't
+pub fn outdated_reason(info: Option<&SearchIndexInfo>) -> Option<String> {
+    let info = match info {
+        Some(info) => info,
+        None => return Some("index was built before schema versioning".to_string()),
+    };
+    if info.schema_version != SCHEMA_VERSION {
+        Some(format!(
+            "index has schema version {}, expected {}",
+            info.schema_version, SCHEMA_VERSION
+        ))
+    } else if tokenizer_language(&info.tokenizer).is_none() {
+        Some(format!("index uses unknown tokenizer {:?}", info.tokenizer))
+    } else {
+        None
+    }
+}
+
+/// Restrict a search to part of the index.
+///
+/// Every filter which is set must match, an empty filter matches everything.
+/// Documents with unknown metadata never match a season or episode filter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    pub seasons: Option<RangeInclusive<u32>>,
+    pub episodes: Option<RangeInclusive<u32>>,
+    /// Only match documents from one of these corpus ids
+    pub corpus: Vec<i64>,
+}
+
+impl SearchFilter {
+    pub fn is_empty(&self) -> bool {
+        self.seasons.is_none() && self.episodes.is_none() && self.corpus.is_empty()
+    }
+}
+
+/// The largest edit distance tantivy will build an automaton for
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// Terms shorter than this are never fuzzed, they would match nearly everything
+const MIN_FUZZY_TERM_LEN: usize = 3;
+
+/// Fuzzy term matches have a constant score of 1.0, scale them down
+/// so they do not drown out the BM25 scores of exact matches.
+const FUZZY_SCORE_WEIGHT: f32 = 0.75;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RankScore(pub f32);
 
@@ -55,147 +303,794 @@ impl Ord for RankScore {
     }
 }
 
+/// Index `eps`, whose subtitles were cleaned by `cleanup`
 pub fn build_index<P: AsRef<Path>, I: Into<IndexableEpisode>>(
     uuid: Uuid,
     path: P,
     eps: impl Iterator<Item = I>,
     max_window: usize,
+    language: Language,
+    cleanup: &SubtitleCleaner,
 ) -> Result<SearchIndex, TError> {
     let ieps = eps.map(|e| e.into()).collect::<Vec<_>>();
-    let index = build_index_impl(path, ieps.as_slice(), max_window)?;
+    let mut index = Index::create_in_dir(path.as_ref(), create_schema(language))?;
+    prepare_index(&mut index);
+    let index = build_index_impl(index, ieps.as_slice(), max_window, language, cleanup)?;
     Ok(SearchIndex { uuid, inner: index })
 }
 
-fn build_index_impl<P: AsRef<Path>>(
-    path: P,
+/// Like [`build_index`], but the index only lives in memory.
+///
+/// See [`SearchIndex::to_bundle`] to keep it around.
+pub fn build_index_in_ram<I: Into<IndexableEpisode>>(
+    uuid: Uuid,
+    eps: impl Iterator<Item = I>,
+    max_window: usize,
+    language: Language,
+    cleanup: &SubtitleCleaner,
+) -> Result<SearchIndex, TError> {
+    let ieps = eps.map(|e| e.into()).collect::<Vec<_>>();
+    let mut index = Index::create_in_ram(create_schema(language));
+    prepare_index(&mut index);
+    let index = build_index_impl(index, ieps.as_slice(), max_window, language, cleanup)?;
+    Ok(SearchIndex { uuid, inner: index })
+}
+
+/// Register our tokenizers, and write new doc stores with snappy.
+///
+/// tantivy 0.16's lz4 doc store compression writes past the end of its
+/// buffer. Doc stores record their compressor, so segments already written
+/// with lz4 are still read and are recompressed when merged.
+fn prepare_index(index: &mut Index) {
+    language::register_tokenizers(index);
+    index.settings_mut().docstore_compression = Compressor::Snappy;
+}
+
+fn build_index_impl(
+    index: tantivy::Index,
     eps: &[IndexableEpisode],
     max_window: usize,
+    language: Language,
+    cleanup: &SubtitleCleaner,
 ) -> tantivy::Result<tantivy::Index> {
-    let index_path = path.as_ref();
+    let schema = index.schema();
 
-    let schema = create_schema();
+    // # Indexing documents
+    let mut index_writer = index.writer(50_000_000)?;
 
-    let title = get_field(&schema, SchemaField::Title);
-    let body = get_field(&schema, SchemaField::Body);
+    for episode_data in eps.iter() {
+        add_episode(&index_writer, &schema, episode_data);
+    }
+    commit_with_info(
+        &mut index_writer,
+        &index_info(max_window, language, cleanup),
+    )?;
+    Ok(index)
+}
+
+fn update_index_impl(
+    index: &Index,
+    eps: &[IndexableEpisode],
+    remove: &[i64],
+    max_window: usize,
+    cleanup: &SubtitleCleaner,
+) -> tantivy::Result<PreparedUpdate> {
+    let schema = index.schema();
     let episode = get_field(&schema, SchemaField::Episode);
-    let clip_start = get_field(&schema, SchemaField::ClipStart);
-    let clip_end = get_field(&schema, SchemaField::ClipEnd);
 
-    // # Indexing documents
-    let index = Index::create_in_dir(index_path, schema)?;
+    if !schema.get_field_entry(episode).is_indexed() {
+        return Err(tantivy::TantivyError::SchemaError(
+            "index was created before incremental updates were supported, it must be rebuilt"
+                .to_string(),
+        ));
+    }
 
-    let mut index_writer = index.writer(50_000_000)?;
+    let index_writer = index.writer(50_000_000)?;
 
+    for srt_id in remove {
+        index_writer.delete_term(Term::from_field_i64(episode, *srt_id));
+    }
     for episode_data in eps.iter() {
-        for clip in episode_data.slices(max_window) {
-            index_writer.add_document(doc!(
-                title => clip.title,
-                body => clip.text,
-                episode => episode_data.srt_id,
-                clip_start => clip.start as u64,
-                clip_end => clip.end as u64,
-            ));
+        // never index the same srt twice
+        index_writer.delete_term(Term::from_field_i64(episode, episode_data.srt_id));
+        add_episode(&index_writer, &schema, episode_data);
+    }
+    let language = index_language(&schema).ok_or_else(|| {
+        tantivy::TantivyError::SchemaError(
+            "index uses an unknown tokenizer, it must be rebuilt".to_string(),
+        )
+    })?;
+    Ok(PreparedUpdate {
+        index_writer,
+        info: index_info(max_window, language, cleanup),
+    })
+}
+
+/// Commit, recording how the index was built in the index metadata
+fn commit_with_info(
+    index_writer: &mut tantivy::IndexWriter,
+    info: &SearchIndexInfo,
+) -> tantivy::Result<()> {
+    let payload = serde_json::to_string(info).expect("unable to serialize JSON");
+    let mut prepared = index_writer.prepare_commit()?;
+    prepared.set_payload(&payload);
+    prepared.commit()?;
+    Ok(())
+}
+
+/// Each line of the episode is its own document, clips spanning several
+/// lines are put together at query time.
+///
+/// A document also has the text of its line and the next one as its span,
+/// so that a phrase can cross from one line into the next, and the unstemmed
+/// words of its line to make suggestions from.
+fn add_episode(
+    index_writer: &tantivy::IndexWriter,
+    schema: &Schema,
+    episode_data: &IndexableEpisode,
+) {
+    let body = get_field(schema, SchemaField::Body);
+    let span = get_field(schema, SchemaField::Span);
+    let words = get_field(schema, SchemaField::Words);
+    let episode = get_field(schema, SchemaField::Episode);
+    let line = get_field(schema, SchemaField::Line);
+    let line_count = get_field(schema, SchemaField::LineCount);
+    let season = get_field(schema, SchemaField::Season);
+    let episode_number = get_field(schema, SchemaField::EpisodeNumber);
+    let corpus = get_field(schema, SchemaField::Corpus);
+
+    for (idx, text) in episode_data.lines.iter().enumerate() {
+        let mut doc = doc!(
+            body => text.as_str(),
+            words => text.as_str(),
+            episode => episode_data.srt_id,
+            line => idx as u64,
+            line_count => episode_data.lines.len() as u64,
+        );
+        if let Some(next) = episode_data.lines.get(idx + 1) {
+            doc.add_text(span, format!("{}\n{}", text, next));
+        }
+        if let Some(s) = episode_data.season {
+            doc.add_u64(season, s as u64);
         }
+        if let Some(e) = episode_data.episode {
+            doc.add_u64(episode_number, e as u64);
+        }
+        if let Some(c) = episode_data.corpus_id {
+            doc.add_i64(corpus, c);
+        }
+        index_writer.add_document(doc);
     }
-    index_writer.commit()?;
-    Ok(index)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum SchemaField {
-    Title,
     Body,
+    Span,
+    Words,
     Episode,
-    ClipStart,
-    ClipEnd,
+    Line,
+    LineCount,
+    Season,
+    EpisodeNumber,
+    Corpus,
 }
 
 impl SchemaField {
     fn as_str(self) -> &'static str {
         match self {
-            SchemaField::Title => "title",
             SchemaField::Body => "body",
+            SchemaField::Span => "span",
+            SchemaField::Words => "words",
             SchemaField::Episode => "episode",
-            SchemaField::ClipStart => "clip_start",
-            SchemaField::ClipEnd => "clip_end",
+            SchemaField::Line => "line",
+            SchemaField::LineCount => "line_count",
+            SchemaField::Season => "season",
+            SchemaField::EpisodeNumber => "episode_number",
+            SchemaField::Corpus => "corpus",
         }
     }
 }
 
-fn create_schema() -> Schema {
+fn create_schema(language: Language) -> Schema {
     let mut schema_builder = Schema::builder();
 
     let text_options = TextOptions::default().set_indexing_options(
         TextFieldIndexing::default()
-            .set_tokenizer("en_stem")
-            .set_index_option(IndexRecordOption::Basic),
+            .set_tokenizer(&tokenizer_name(language))
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
     );
 
-    schema_builder.add_text_field(SchemaField::Title.as_str(), text_options.clone());
-    schema_builder.add_text_field(SchemaField::Body.as_str(), text_options);
-    schema_builder.add_i64_field(SchemaField::Episode.as_str(), STORED);
-    schema_builder.add_u64_field(SchemaField::ClipStart.as_str(), STORED);
-    schema_builder.add_u64_field(SchemaField::ClipEnd.as_str(), STORED);
+    schema_builder.add_text_field(SchemaField::Body.as_str(), text_options.clone());
+    schema_builder.add_text_field(SchemaField::Span.as_str(), text_options);
+    let words_options = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(language::WORDS_TOKENIZER)
+            .set_index_option(IndexRecordOption::Basic),
+    );
+    schema_builder.add_text_field(SchemaField::Words.as_str(), words_options);
+    schema_builder.add_i64_field(SchemaField::Episode.as_str(), INDEXED | STORED);
+    schema_builder.add_u64_field(SchemaField::Line.as_str(), STORED);
+    schema_builder.add_u64_field(SchemaField::LineCount.as_str(), STORED);
+    schema_builder.add_u64_field(SchemaField::Season.as_str(), INDEXED);
+    schema_builder.add_u64_field(SchemaField::EpisodeNumber.as_str(), INDEXED);
+    schema_builder.add_i64_field(SchemaField::Corpus.as_str(), INDEXED);
     schema_builder.build()
 }
 
+/// The language an index was built for, read from the tokenizer of its body field
+fn index_language(schema: &Schema) -> Option<Language> {
+    let body = get_field(schema, SchemaField::Body);
+    match schema.get_field_entry(body).field_type() {
+        FieldType::Str(options) => options
+            .get_indexing_options()
+            .and_then(|indexing| tokenizer_language(indexing.tokenizer())),
+        _ => None,
+    }
+}
+
 fn get_field(schema: &Schema, field: SchemaField) -> Field {
     schema
         .get_field(field.as_str())
         .expect("field in enum was not in schema")
 }
 
+/// A query for each word of the query long enough to be fuzzed, matching
+/// only lines which the exact query did not
+fn fuzzy_term_queries(body: Field, query: &CompiledQuery, distance: u8) -> Vec<TermScoreQuery> {
+    let distance = distance.min(MAX_FUZZY_DISTANCE);
+
+    query
+        .fuzzy_words
+        .iter()
+        .map(|(group, text)| {
+            let term = Term::from_field_text(body, text);
+            let fuzzy: Box<dyn Query> = Box::new(FuzzyTermQuery::new(term, distance, true));
+            // Anything the exact query found is scored by the exact pass
+            let clauses = vec![
+                (Occur::Must, fuzzy),
+                (Occur::MustNot, query.matches.box_clone()),
+            ];
+            let query: Box<dyn Query> = Box::new(BoostQuery::new(
+                Box::new(BooleanQuery::new(clauses)),
+                FUZZY_SCORE_WEIGHT,
+            ));
+            TermScoreQuery {
+                text: text.clone(),
+                group: *group,
+                query,
+                crossing: None,
+            }
+        })
+        .collect()
+}
+
+/// Scores the lines matching one term of the search
+pub(crate) struct TermScoreQuery {
+    /// The term as it was indexed
+    pub text: String,
+    /// The clause of the query the term is from
+    pub group: usize,
+    pub query: Box<dyn Query>,
+    /// Scores the term where its phrase crosses from a line into the next,
+    /// matched against the span of the first line
+    pub crossing: Option<Box<dyn Query>>,
+}
+
+/// Build the clauses for `filter`, they are boosted to zero
+/// so that filtering never changes the score of a match.
+fn filter_clauses(
+    schema: &Schema,
+    filter: &SearchFilter,
+) -> tantivy::Result<Vec<(Occur, Box<dyn Query>)>> {
+    if filter.is_empty() {
+        return Ok(vec![]);
+    }
+
+    if schema.get_field(SchemaField::Corpus.as_str()).is_none() {
+        return Err(tantivy::TantivyError::SchemaError(
+            "index was created before filters were supported, it must be rebuilt".to_string(),
+        ));
+    }
+
+    let season = get_field(schema, SchemaField::Season);
+    let episode_number = get_field(schema, SchemaField::EpisodeNumber);
+    let corpus = get_field(schema, SchemaField::Corpus);
+
+    let mut filters: Vec<Box<dyn Query>> = vec![];
+    if let Some(seasons) = &filter.seasons {
+        let range = *seasons.start() as u64..(*seasons.end() as u64 + 1);
+        filters.push(Box::new(RangeQuery::new_u64(season, range)));
+    }
+    if let Some(episodes) = &filter.episodes {
+        let range = *episodes.start() as u64..(*episodes.end() as u64 + 1);
+        filters.push(Box::new(RangeQuery::new_u64(episode_number, range)));
+    }
+    if !filter.corpus.is_empty() {
+        let any_corpus = filter
+            .corpus
+            .iter()
+            .map(|id| {
+                let term = Term::from_field_i64(corpus, *id);
+                let query: Box<dyn Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                (Occur::Should, query)
+            })
+            .collect::<Vec<_>>();
+        filters.push(Box::new(BooleanQuery::new(any_corpus)));
+    }
+
+    Ok(filters
+        .into_iter()
+        .map(|f| {
+            let query: Box<dyn Query> = Box::new(BoostQuery::new(f, 0.0));
+            (Occur::Must, query)
+        })
+        .collect())
+}
+
+/// Require `query` to match, and every clause in `filters`
+fn apply_filters(query: Box<dyn Query>, filters: &[(Occur, Box<dyn Query>)]) -> Box<dyn Query> {
+    if filters.is_empty() {
+        return query;
+    }
+    let mut clauses = vec![(Occur::Must, query)];
+    clauses.extend(filters.iter().map(|(occur, q)| (*occur, q.box_clone())));
+    Box::new(BooleanQuery::new(clauses))
+}
+
+fn highlighter_impl(index: &Index, q: &str, mode: QueryMode) -> Result<Highlighter, TError> {
+    let schema = index.schema();
+    let body = get_field(&schema, SchemaField::Body);
+
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let query = query::compile_query(index, &query::parse_query(q)?, MIN_FUZZY_TERM_LEN)?;
+
+    let snippets = SnippetGenerator::create(&searcher, query.matches.as_ref(), body)?;
+    let tokenizer = index.tokenizer_for_field(body)?;
+    let (fuzzy_terms, distance) = match mode {
+        QueryMode::Exact => (vec![], 0),
+        QueryMode::Fuzzy { distance } => (
+            query.fuzzy_words.into_iter().map(|(_, t)| t).collect(),
+            distance.min(MAX_FUZZY_DISTANCE),
+        ),
+    };
+    Ok(Highlighter::new(snippets, tokenizer, fuzzy_terms, distance))
+}
+
 fn search_impl(
     index: &Index,
     q: &str,
     search_window: usize,
-) -> tantivy::Result<HashMap<usize, EpisodeScore>> {
-    let read_schema = create_schema();
+    mode: QueryMode,
+    filter: &SearchFilter,
+    explain: bool,
+) -> Result<HashMap<usize, EpisodeScore>, TError> {
+    let read_schema = index.schema();
 
     let body = get_field(&read_schema, SchemaField::Body);
-    let episode = get_field(&read_schema, SchemaField::Episode);
-    let clip_start = get_field(&read_schema, SchemaField::ClipStart);
-    let clip_end = get_field(&read_schema, SchemaField::ClipEnd);
 
     let reader = index.reader()?;
     let searcher = reader.searcher();
-    let query_parser = QueryParser::for_index(index, vec![body]);
-    let query = query_parser.parse_query(q)?;
-
-    let top_docs = searcher.search(&query, &TopDocs::with_limit(10000))?;
+    let query = query::compile_query(index, &query::parse_query(q)?, MIN_FUZZY_TERM_LEN)?;
+    let mut filters = filter_clauses(&read_schema, filter)?;
+    filters.extend(
+        query
+            .restrictions
+            .iter()
+            .map(|(occur, q)| (*occur, q.box_clone())),
+    );
 
     let mut scores = HashMap::new();
+    if query.groups == 0 {
+        return Ok(scores);
+    }
+
+    let exact = collect_line_scores(
+        &searcher,
+        &read_schema,
+        &query.term_queries,
+        &filters,
+        query.groups,
+        explain,
+    )?;
+    let fuzzy = match mode {
+        QueryMode::Exact => None,
+        QueryMode::Fuzzy { distance } => Some(collect_line_scores(
+            &searcher,
+            &read_schema,
+            &fuzzy_term_queries(body, &query, distance),
+            &filters,
+            query.groups,
+            explain,
+        )?),
+    };
+
+    let excluded = collect_excluded_lines(&searcher, &read_schema, &query)?;
+
+    let passes = std::iter::once(&exact).chain(&fuzzy).collect::<Vec<_>>();
+    score_windows(
+        &exact,
+        &passes,
+        &excluded,
+        search_window,
+        MatchKind::Exact,
+        &mut scores,
+    );
+    if let Some(fuzzy) = &fuzzy {
+        score_windows(
+            fuzzy,
+            &passes,
+            &excluded,
+            search_window,
+            MatchKind::Fuzzy,
+            &mut scores,
+        );
+    }
+
+    Ok(scores)
+}
+
+/// The lines of one episode which matched
+#[derive(Default)]
+struct EpisodeLines {
+    line_count: usize,
+    /// The score of each term query, by line number
+    matches: BTreeMap<usize, Vec<f32>>,
+    /// tantivy's explanation of each term score, by line number and term,
+    /// only when explaining
+    explanations: HashMap<(usize, usize), serde_json::Value>,
+}
 
-    for (score, doc_address) in top_docs {
+/// Matching lines by srt id, and the terms which were scored
+struct LineScores {
+    terms: Vec<String>,
+    /// The clause of the query each term is from
+    groups: Vec<usize>,
+    /// The number of clauses in the query
+    group_count: usize,
+    episodes: HashMap<i64, EpisodeLines>,
+    explain: bool,
+}
+
+fn collect_line_scores(
+    searcher: &tantivy::Searcher,
+    schema: &Schema,
+    term_queries: &[TermScoreQuery],
+    filters: &[(Occur, Box<dyn Query>)],
+    group_count: usize,
+    explain: bool,
+) -> tantivy::Result<LineScores> {
+    let episode = get_field(schema, SchemaField::Episode);
+    let line = get_field(schema, SchemaField::Line);
+    let line_count = get_field(schema, SchemaField::LineCount);
+
+    // the srt id, line number and line count of a document
+    let doc_line = |doc_address: DocAddress| -> tantivy::Result<(i64, usize, usize)> {
         let doc = searcher.doc(doc_address)?;
         let en = doc
             .get_first(episode)
             .unwrap()
             .i64_value()
-            .expect("no ep number") as usize;
-        let cs = doc
-            .get_first(clip_start)
+            .expect("no ep number");
+        let ln = doc
+            .get_first(line)
             .unwrap()
             .u64_value()
-            .expect("no clip start") as usize;
-        let ce = doc
-            .get_first(clip_end)
+            .expect("no line number") as usize;
+        let count = doc
+            .get_first(line_count)
             .unwrap()
             .u64_value()
-            .expect("no clip end") as usize;
+            .expect("no line count") as usize;
+        Ok((en, ln, count))
+    };
+
+    let mut lines: HashMap<i64, EpisodeLines> = HashMap::new();
+    let record = |lines: &mut HashMap<i64, EpisodeLines>,
+                  (en, ln, count): (i64, usize, usize),
+                  term_idx: usize,
+                  score: f32,
+                  explanation: Option<serde_json::Value>| {
+        let episode_lines = lines.entry(en).or_default();
+        episode_lines.line_count = count;
+        episode_lines
+            .matches
+            .entry(ln)
+            .or_insert_with(|| vec![0.0; term_queries.len()])[term_idx] = score;
+        if let Some(explanation) = explanation {
+            episode_lines
+                .explanations
+                .insert((ln, term_idx), explanation);
+        }
+    };
+    let explanation = |query: &dyn Query, doc_address| -> tantivy::Result<_> {
+        Ok(if explain {
+            let explanation = query.explain(searcher, doc_address)?;
+            Some(serde_json::to_value(&explanation).expect("unable to serialize JSON"))
+        } else {
+            None
+        })
+    };
+
+    for (term_idx, term_query) in term_queries.iter().enumerate() {
+        let query = apply_filters(term_query.query.box_clone(), filters);
+        for (score, doc_address) in searcher.search(query.as_ref(), &AllScoredDocs)? {
+            let doc = doc_line(doc_address)?;
+            let explanation = explanation(term_query.query.as_ref(), doc_address)?;
+            record(&mut lines, doc, term_idx, score, explanation);
+        }
+
+        let crossing = match &term_query.crossing {
+            Some(crossing) => crossing,
+            None => continue,
+        };
+        // a span also matches a phrase within either of its lines, those
+        // were already scored above
+        let within_line = |en: i64, ln: usize| {
+            let term_scores = lines
+                .get(&en)
+                .and_then(|episode_lines| episode_lines.matches.get(&ln));
+            matches!(term_scores, Some(term_scores) if term_scores[term_idx] > 0.0)
+        };
+        let query = apply_filters(crossing.box_clone(), filters);
+        let mut crossings = vec![];
+        for (score, doc_address) in searcher.search(query.as_ref(), &AllScoredDocs)? {
+            let (en, ln, count) = doc_line(doc_address)?;
+            if within_line(en, ln) || within_line(en, ln + 1) {
+                continue;
+            }
+            let explanation = explanation(crossing.as_ref(), doc_address)?;
+            crossings.push(((en, ln, count), score, explanation));
+        }
+        for ((en, ln, count), score, explanation) in crossings {
+            record(
+                &mut lines,
+                (en, ln, count),
+                term_idx,
+                score,
+                explanation.clone(),
+            );
+            record(
+                &mut lines,
+                (en, ln + 1, count),
+                term_idx,
+                score,
+                explanation,
+            );
+        }
+    }
+
+    Ok(LineScores {
+        terms: term_queries.iter().map(|t| t.text.clone()).collect(),
+        groups: term_queries.iter().map(|t| t.group).collect(),
+        group_count,
+        episodes: lines,
+        explain,
+    })
+}
+
+/// The lines of one episode which no clip may include
+#[derive(Default)]
+struct ExcludedLines {
+    /// Lines containing an excluded word or phrase
+    lines: BTreeSet<usize>,
+    /// Lines where an excluded phrase runs on into the next line
+    spans: BTreeSet<usize>,
+}
+
+impl ExcludedLines {
+    fn excludes(&self, range: Range<usize>) -> bool {
+        let spans = range.start..range.end.saturating_sub(1).max(range.start);
+        self.lines.range(range).next().is_some() || self.spans.range(spans).next().is_some()
+    }
+}
 
-        if ce - cs > search_window {
-            continue;
+/// The lines excluded by `query`, by srt id
+fn collect_excluded_lines(
+    searcher: &tantivy::Searcher,
+    schema: &Schema,
+    query: &CompiledQuery,
+) -> tantivy::Result<HashMap<i64, ExcludedLines>> {
+    let episode = get_field(schema, SchemaField::Episode);
+    let line = get_field(schema, SchemaField::Line);
+
+    let mut excluded: HashMap<i64, ExcludedLines> = HashMap::new();
+    let queries = query
+        .exclusions
+        .iter()
+        .map(|q| (q, false))
+        .chain(query.crossing_exclusions.iter().map(|q| (q, true)));
+    for (exclusion, crossing) in queries {
+        for doc_address in searcher.search(exclusion.as_ref(), &DocSetCollector)? {
+            let doc = searcher.doc(doc_address)?;
+            let en = doc
+                .get_first(episode)
+                .unwrap()
+                .i64_value()
+                .expect("no ep number");
+            let ln = doc
+                .get_first(line)
+                .unwrap()
+                .u64_value()
+                .expect("no line number") as usize;
+            let episode_lines = excluded.entry(en).or_default();
+            if crossing {
+                episode_lines.spans.insert(ln);
+            } else {
+                episode_lines.lines.insert(ln);
+            }
         }
+    }
+    Ok(excluded)
+}
+
+/// Collects every matching document and its score, unlike `TopDocs`
+/// nothing is left out however many documents match
+struct AllScoredDocs;
+
+impl Collector for AllScoredDocs {
+    type Fruit = Vec<(Score, DocAddress)>;
+    type Child = AllScoredSegmentDocs;
 
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        _segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(AllScoredSegmentDocs {
+            segment_local_id,
+            docs: vec![],
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(Score, DocAddress)>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        Ok(segment_fruits.into_iter().flatten().collect())
+    }
+}
+
+struct AllScoredSegmentDocs {
+    segment_local_id: SegmentOrdinal,
+    docs: Vec<(Score, DocAddress)>,
+}
+
+impl SegmentCollector for AllScoredSegmentDocs {
+    type Fruit = Vec<(Score, DocAddress)>;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        self.docs
+            .push((score, DocAddress::new(self.segment_local_id, doc)));
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.docs
+    }
+}
+
+/// Score every clip of up to `search_window` lines around the matching lines.
+///
+/// A clip scores the best match of each term within it, so clips where several
+/// terms are close together outrank clips containing only one of them. Longer
+/// clips are discounted the way BM25 discounts long documents, taking a single
+/// line as the average length. Every line accumulates the scores of all the
+/// clips it is part of.
+///
+/// Only clips where every required clause of the query is matched, by either pass of
+/// `passes`, and which include none of the `excluded` lines, are scored.
+fn score_windows(
+    lines: &LineScores,
+    passes: &[&LineScores],
+    excluded: &HashMap<i64, ExcludedLines>,
+    search_window: usize,
+    kind: MatchKind,
+    scores: &mut HashMap<usize, EpisodeScore>,
+) {
+    for (&srt_id, episode_lines) in &lines.episodes {
+        let matches = &episode_lines.matches;
+        let mut windows = BTreeSet::new();
+        for &line in matches.keys() {
+            for len in 1..=search_window.min(episode_lines.line_count) {
+                let last_start = line.min(episode_lines.line_count - len);
+                for start in line.saturating_sub(len - 1)..=last_start {
+                    windows.insert((start, len));
+                }
+            }
+        }
+
+        let en = srt_id as usize;
         let e_score = scores.entry(en).or_insert_with(|| EpisodeScore {
             inner: vec![],
+            exact: vec![],
             episode: en,
+            windows: vec![],
         });
-        e_score.add(cs, ce, score)
+        let episode_excluded = excluded.get(&srt_id);
+        for (start, len) in windows {
+            if !covers_all_groups(passes, srt_id, start..start + len) {
+                continue;
+            }
+            if matches!(episode_excluded, Some(e) if e.excludes(start..start + len)) {
+                continue;
+            }
+            // the best score of each term, and the line it is on
+            let mut best = vec![(0.0f32, start); lines.terms.len()];
+            for (&line, term_scores) in matches.range(start..start + len) {
+                for (b, s) in best.iter_mut().zip(term_scores) {
+                    if *s > b.0 {
+                        *b = (*s, line);
+                    }
+                }
+            }
+            let norm = window_norm(len);
+            let score = best.iter().map(|(s, _)| s).sum::<f32>() * norm;
+            e_score.add(start, start + len, score, kind);
+
+            if lines.explain {
+                let terms = best
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (s, _))| *s > 0.0)
+                    .map(|(term_idx, &(score, line))| TermExplanation {
+                        term: lines.terms[term_idx].clone(),
+                        line,
+                        score,
+                        explanation: episode_lines.explanations.get(&(line, term_idx)).cloned(),
+                    })
+                    .collect();
+                e_score.windows.push(WindowExplanation {
+                    start,
+                    len,
+                    exact: kind == MatchKind::Exact,
+                    terms,
+                    norm,
+                    score,
+                });
+            }
+        }
     }
+}
 
-    Ok(scores)
+/// Does some line in `range` of the episode match a term of every clause which must match
+fn covers_all_groups(passes: &[&LineScores], srt_id: i64, range: Range<usize>) -> bool {
+    let group_count = passes.first().map_or(0, |p| p.group_count);
+    // every term is from the one group, and the window has a match
+    let single_group = passes.iter().all(|p| p.groups.iter().all(|g| *g == 0));
+    if group_count == 0 || (group_count == 1 && single_group) {
+        return true;
+    }
+    let mut covered = vec![false; group_count];
+    for pass in passes {
+        let episode_lines = match pass.episodes.get(&srt_id) {
+            Some(episode_lines) => episode_lines,
+            None => continue,
+        };
+        for term_scores in episode_lines.matches.range(range.clone()).map(|(_, s)| s) {
+            for (term_idx, score) in term_scores.iter().enumerate() {
+                // terms of clauses which are not required are in no group
+                match covered.get_mut(pass.groups[term_idx]) {
+                    Some(covered) if *score > 0.0 => *covered = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+    covered.into_iter().all(|c| c)
+}
+
+/// BM25's length normalization of a single occurrence of a term,
+/// in a document `len` times the average length
+fn window_norm(len: usize) -> f32 {
+    const K1: f32 = 1.2;
+    const B: f32 = 0.75;
+    (K1 + 1.0) / (1.0 + K1 * (1.0 - B + B * len as f32))
 }
 
 pub fn rank(scores: &HashMap<usize, EpisodeScore>) -> Vec<RankedMatch> {
@@ -204,6 +1099,7 @@ pub fn rank(scores: &HashMap<usize, EpisodeScore>) -> Vec<RankedMatch> {
         .map(|es| {
             let matches = ClipMatches {
                 data: es.inner.as_slice(),
+                exact: es.exact.as_slice(),
                 cursor: 0,
             };
             (es.episode, matches)
@@ -212,28 +1108,109 @@ pub fn rank(scores: &HashMap<usize, EpisodeScore>) -> Vec<RankedMatch> {
             let ep = episode;
             matches.map(move |clip| {
                 let score = *clip.scores.iter().max().unwrap();
-                RankedMatch { score, ep, clip }
+                RankedMatch {
+                    exact: clip.exact,
+                    score,
+                    ep,
+                    clip,
+                }
             })
         })
         .collect::<BinaryHeap<RankedMatch>>();
     ranked.into_sorted_vec()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchKind {
+    Exact,
+    Fuzzy,
+}
+
 pub struct EpisodeScore {
     episode: usize,
     inner: Vec<RankScore>,
+    /// Did an exact (non-fuzzy) match contribute to this line
+    exact: Vec<bool>,
+    /// Every window which was scored, only when explaining
+    windows: Vec<WindowExplanation>,
+}
+
+/// Why a clip scored what it did, see [`SearchIndex::explain`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipExplanation {
+    /// The windows overlapping the clip, each adds its score to all of its lines
+    pub windows: Vec<WindowExplanation>,
+    /// The total score of each line of the clip, summed over every window it is part of
+    pub line_totals: Vec<f32>,
+    /// Lines scoring at most this are not part of any clip
+    pub min_score: f32,
+}
+
+/// The score of a window of lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowExplanation {
+    /// The first line of the window
+    pub start: usize,
+    /// The number of lines in the window
+    pub len: usize,
+    /// Was this scored by the exact pass, rather than the fuzzy one
+    pub exact: bool,
+    /// The best match of each term within the window
+    pub terms: Vec<TermExplanation>,
+    /// The length normalization applied to the sum of the term scores
+    pub norm: f32,
+    pub score: f32,
+}
+
+/// The best match of a term within a window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TermExplanation {
+    /// The term as it was indexed
+    pub term: String,
+    /// The line of the match
+    pub line: usize,
+    pub score: f32,
+    /// tantivy's explanation of `score`
+    pub explanation: Option<serde_json::Value>,
 }
 
 impl EpisodeScore {
-    fn add(&mut self, start: usize, end: usize, score: f32) {
+    /// Explain the score of `clip`, which must come from this episode.
+    ///
+    /// `None` unless the search was run with [`SearchIndex::explain`].
+    pub fn explain(&self, clip: &ClipMatch) -> Option<ClipExplanation> {
+        if self.windows.is_empty() {
+            return None;
+        }
+        let clip_lines = clip.index..clip.index + clip.scores.len();
+        let windows = self
+            .windows
+            .iter()
+            .filter(|w| w.start < clip_lines.end && clip_lines.start < w.start + w.len)
+            .cloned()
+            .collect();
+        Some(ClipExplanation {
+            windows,
+            line_totals: clip.scores.iter().map(|s| s.0).collect(),
+            min_score: MIN_SCORE,
+        })
+    }
+
+    fn add(&mut self, start: usize, end: usize, score: f32, kind: MatchKind) {
         if self.inner.len() <= end {
             let extend = 1 + end - self.inner.len();
             self.inner
-                .extend(std::iter::repeat(RankScore(0.0)).take(extend))
+                .extend(std::iter::repeat(RankScore(0.0)).take(extend));
+            self.exact.resize(self.inner.len(), false);
         }
         for s in self.inner.as_mut_slice()[start..end].iter_mut() {
             s.0 += score
         }
+        if kind == MatchKind::Exact {
+            for e in self.exact.as_mut_slice()[start..end].iter_mut() {
+                *e = true
+            }
+        }
     }
 }
 
@@ -241,11 +1218,14 @@ const MIN_SCORE: f32 = 0.5f32;
 
 struct ClipMatches<'a> {
     data: &'a [RankScore],
+    exact: &'a [bool],
     cursor: usize,
 }
 
 #[derive(PartialEq, Eq)]
 pub struct RankedMatch<'a> {
+    /// Exact matches always outrank fuzzy matches, regardless of score
+    pub exact: bool,
     pub score: RankScore,
     pub ep: usize,
     pub clip: ClipMatch<'a>,
@@ -253,13 +1233,13 @@ pub struct RankedMatch<'a> {
 
 impl<'a> PartialOrd for RankedMatch<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.score.partial_cmp(&other.score)
+        Some(self.cmp(other))
     }
 }
 
 impl<'a> Ord for RankedMatch<'a> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.score.cmp(&other.score)
+        (self.exact, self.score).cmp(&(other.exact, other.score))
     }
 }
 
@@ -267,6 +1247,8 @@ impl<'a> Ord for RankedMatch<'a> {
 pub struct ClipMatch<'a> {
     pub index: usize,
     pub scores: &'a [RankScore],
+    /// At least one line of the clip was an exact match
+    pub exact: bool,
 }
 
 impl<'a> Iterator for ClipMatches<'a> {
@@ -284,9 +1266,883 @@ impl<'a> Iterator for ClipMatches<'a> {
             Some(ClipMatch {
                 index,
                 scores: &self.data[index..self.cursor],
+                exact: self.exact[index..self.cursor].iter().any(|e| *e),
             })
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use lucille_core::{
+        identifiers::CorpusId,
+        metadata::{EpisodeMetadata, MediaHash, MediaMetadata},
+        test_util::generate_subtitle,
+        ContentData, LucilleSub,
+    };
+
+    use super::*;
+
+    fn episode(srt_id: i64, lines: &[&str]) -> ContentData {
+        ContentData {
+            metadata: MediaMetadata::Unknown(format!("episode {}", srt_id)),
+            hash: MediaHash::from_bytes(format!("episode {}", srt_id).as_bytes()),
+            subtitle: LucilleSub {
+                id: srt_id,
+                uuid: Uuid::generate(),
+                subs: generate_subtitle(lines),
+            },
+        }
+    }
+
+    fn season_episode(
+        srt_id: i64,
+        season: u32,
+        episode_number: u32,
+        lines: &[&str],
+    ) -> ContentData {
+        ContentData {
+            metadata: MediaMetadata::Episode(EpisodeMetadata::new(
+                season,
+                episode_number,
+                format!("episode {}", srt_id),
+            )),
+            ..episode(srt_id, lines)
+        }
+    }
+
+    /// Episodes 1 and 2 are in corpus 1, seasons 1 and 2; episode 3 is in corpus 2
+    fn filter_index(dir: &Path) -> SearchIndex {
+        let eps = vec![
+            (
+                CorpusId::new(1),
+                season_episode(1, 1, 4, &["there's always money"]),
+            ),
+            (
+                CorpusId::new(1),
+                season_episode(2, 2, 1, &["money in the banana stand"]),
+            ),
+            (CorpusId::new(2), episode(3, &["here's some money"])),
+            (CorpusId::new(2), episode(4, &["no touching"; 10])),
+        ];
+        build_index(
+            Uuid::generate(),
+            dir,
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap()
+    }
+
+    fn test_index(dir: &Path) -> SearchIndex {
+        let eps = vec![
+            episode(1, &["I just blue myself", "there's always money"]),
+            episode(2, &["no touching", "in the banana stand"]),
+            episode(3, &["here's some money", "go see a star war"]),
+        ];
+        build_index(
+            Uuid::generate(),
+            dir,
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap()
+    }
+
+    fn ranked_episodes(scores: &HashMap<usize, EpisodeScore>) -> Vec<(usize, bool)> {
+        rank(scores)
+            .into_iter()
+            .rev()
+            .map(|rm| (rm.ep, rm.exact))
+            .collect()
+    }
+
+    #[test]
+    fn update_replaces_episode() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let scores = index
+            .search("banana", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(2, true)]);
+
+        let replacement = episode(4, &["no touching", "in the orange stand"]);
+        index.update(std::iter::once(replacement), &[2], 2).unwrap();
+
+        let scores = index
+            .search("banana", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert!(ranked_episodes(&scores).is_empty());
+        let scores = index
+            .search("orange", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(4, true)]);
+    }
+
+    #[test]
+    fn update_does_not_duplicate_episode() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let again = episode(2, &["no touching", "in the banana stand"]);
+        index.update(std::iter::once(again), &[], 2).unwrap();
+
+        let dir2 = tempfile::tempdir().unwrap();
+        let fresh = test_index(dir2.path());
+        let num_docs = |i: &SearchIndex| i.inner.reader().unwrap().searcher().num_docs();
+        assert_eq!(num_docs(&index), num_docs(&fresh));
+    }
+
+    #[test]
+    fn one_document_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let num_docs = index.inner.reader().unwrap().searcher().num_docs();
+        assert_eq!(num_docs, 6);
+    }
+
+    #[test]
+    fn adjacent_terms_outrank_scattered_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut scattered = vec!["no touching"; 8];
+        scattered[0] = "there's always money";
+        scattered[7] = "in the banana stand";
+        let mut adjacent = vec!["no touching"; 8];
+        adjacent[3] = "there's always money";
+        adjacent[4] = "in the banana stand";
+        let eps = vec![episode(1, &scattered), episode(2, &adjacent)];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            3,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        let scores = index
+            .search(
+                "money banana",
+                3,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        let ranked = rank(&scores);
+        let best = ranked.last().unwrap();
+        assert_eq!(best.ep, 2);
+        // the clip covers both lines
+        assert!(best.clip.index <= 3);
+        assert!(best.clip.index + best.clip.scores.len() >= 5);
+    }
+
+    #[test]
+    fn search_window_is_limited_by_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut lines = vec!["no touching"; 9];
+        lines[4] = "there's always money";
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            vec![episode(1, &lines)].into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+        let scores = index
+            .search("money", 5, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        let ranked = rank(&scores);
+        assert!(!ranked.is_empty());
+        for clip in ranked {
+            // windows of two lines reach one line either side of the match
+            assert!(clip.clip.index >= 3);
+            assert!(clip.clip.index + clip.clip.scores.len() <= 6);
+        }
+    }
+
+    #[test]
+    fn exact_search_misses_typo() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let scores = index
+            .search("bananna", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert!(ranked_episodes(&scores).is_empty());
+    }
+
+    #[test]
+    fn fuzzy_search_finds_typo() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let scores = index
+            .search(
+                "bananna",
+                2,
+                QueryMode::Fuzzy { distance: 1 },
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(2, false)]);
+    }
+
+    #[test]
+    fn exact_matches_rank_above_fuzzy() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        // "monkey" is one edit from "money", "star" is exact
+        let scores = index
+            .search(
+                "monkey star",
+                2,
+                QueryMode::Fuzzy { distance: 1 },
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        let ranked = ranked_episodes(&scores);
+        assert_eq!(ranked.first(), Some(&(3, true)));
+        assert!(ranked.contains(&(1, false)));
+    }
+
+    #[test]
+    fn filter_by_season() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+        let filter = SearchFilter {
+            seasons: Some(2..=2),
+            ..Default::default()
+        };
+        let scores = index.search("money", 2, QueryMode::Exact, &filter).unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(2, true)]);
+    }
+
+    #[test]
+    fn filter_by_episode_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+        let filter = SearchFilter {
+            episodes: Some(3..=5),
+            ..Default::default()
+        };
+        let scores = index.search("money", 2, QueryMode::Exact, &filter).unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(1, true)]);
+    }
+
+    #[test]
+    fn filter_by_corpus() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+        let filter = SearchFilter {
+            corpus: vec![2],
+            ..Default::default()
+        };
+        let scores = index.search("money", 2, QueryMode::Exact, &filter).unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(3, true)]);
+    }
+
+    #[test]
+    fn filter_applies_to_fuzzy_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+        let filter = SearchFilter {
+            seasons: Some(1..=1),
+            ..Default::default()
+        };
+        let scores = index
+            .search("monkey", 2, QueryMode::Fuzzy { distance: 1 }, &filter)
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(1, false)]);
+    }
+
+    #[test]
+    fn filter_does_not_change_scores() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+        let score_of = |filter: &SearchFilter| {
+            let scores = index.search("money", 2, QueryMode::Exact, filter).unwrap();
+            scores[&1].inner.clone()
+        };
+        let filter = SearchFilter {
+            corpus: vec![1],
+            ..Default::default()
+        };
+        assert_eq!(score_of(&SearchFilter::default()), score_of(&filter));
+    }
+
+    #[test]
+    fn highlight_exact_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let highlighter = index
+            .highlighter("banana stands", QueryMode::Exact)
+            .unwrap();
+        let text = "in the banana stand.";
+        let marked = highlighter
+            .highlight(text)
+            .into_iter()
+            .map(|r| &text[r])
+            .collect::<Vec<_>>();
+        assert_eq!(marked, vec!["banana", "stand"]);
+    }
+
+    #[test]
+    fn highlight_fuzzy_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let text = "in the banana stand";
+
+        let exact = index.highlighter("bananna", QueryMode::Exact).unwrap();
+        assert!(exact.highlight(text).is_empty());
+
+        let fuzzy = index
+            .highlighter("bananna", QueryMode::Fuzzy { distance: 1 })
+            .unwrap();
+        let marked = fuzzy
+            .highlight(text)
+            .into_iter()
+            .map(|r| &text[r])
+            .collect::<Vec<_>>();
+        assert_eq!(marked, vec!["banana"]);
+    }
+
+    #[test]
+    fn index_records_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let info = index.info().unwrap();
+        assert_eq!(
+            info,
+            Some(index_info(
+                2,
+                Language::English,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+        assert_eq!(outdated_reason(info.as_ref()), None);
+
+        index
+            .update(std::iter::empty::<ContentData>(), &[1], 2)
+            .unwrap();
+        assert_eq!(
+            index.info().unwrap(),
+            Some(index_info(
+                2,
+                Language::English,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+    }
+
+    #[test]
+    fn search_with_corpus_language() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            episode(1, &["una canción para ti"; 6]),
+            episode(2, &["el plátano"; 6]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::Spanish,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+        assert_eq!(
+            index.info().unwrap(),
+            Some(index_info(
+                2,
+                Language::Spanish,
+                &SubtitleCleaner::index_default()
+            ))
+        );
+
+        let scores = index
+            .search("canciones", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(1, true)]);
+        let scores = index
+            .search("platano", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(2, true)]);
+
+        // reopening the index must find the same tokenizer
+        let reopened = SearchIndex::open_in_dir(index.uuid, dir.path()).unwrap();
+        let scores = reopened
+            .search("cancion", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores), vec![(1, true)]);
+    }
+
+    #[test]
+    fn in_memory_index_and_bundle() {
+        let eps = vec![
+            episode(1, &["I just blue myself", "there's always money"]),
+            episode(2, &["no touching", "in the banana stand"]),
+        ];
+        let index = build_index_in_ram(
+            Uuid::generate(),
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+        let search = |index: &SearchIndex| {
+            let scores = index
+                .search("money", 2, QueryMode::Exact, &SearchFilter::default())
+                .unwrap();
+            ranked_episodes(&scores)
+        };
+        assert_eq!(search(&index), vec![(1, true)]);
+
+        let loaded = SearchIndex::from_bundle(&index.to_bundle().unwrap()).unwrap();
+        assert_eq!(loaded.uuid, index.uuid);
+        assert_eq!(loaded.info().unwrap(), index.info().unwrap());
+        assert_eq!(search(&loaded), vec![(1, true)]);
+
+        // indexes on disk bundle the same way, including updates
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        index
+            .update(
+                vec![episode(4, &["money money money"])].into_iter(),
+                &[1],
+                2,
+            )
+            .unwrap();
+        let loaded = SearchIndex::from_bundle(&index.to_bundle().unwrap()).unwrap();
+        assert_eq!(search(&loaded), search(&index));
+        assert_eq!(search(&loaded).len(), 2);
+
+        let bundle = index.to_bundle().unwrap();
+        assert!(SearchIndex::from_bundle(&bundle[..bundle.len() - 1]).is_err());
+        assert!(SearchIndex::from_bundle(b"not a bundle").is_err());
+    }
+
+    #[test]
+    fn explain_clip_scores() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+        let filter = SearchFilter::default();
+        let mode = QueryMode::Fuzzy { distance: 1 };
+
+        let scores = index.search("blue money", 2, mode, &filter).unwrap();
+        let rm = &rank(&scores)[0];
+        assert_eq!(scores[&rm.ep].explain(&rm.clip), None);
+
+        let scores = index.explain("blue money", 2, mode, &filter).unwrap();
+        let ranked = rank(&scores);
+        let rm = ranked.last().unwrap();
+        assert_eq!(rm.ep, 1);
+        let explanation = scores[&rm.ep].explain(&rm.clip).unwrap();
+        assert_eq!(explanation.min_score, MIN_SCORE);
+        assert_eq!(explanation.line_totals.len(), rm.clip.scores.len());
+
+        // the window of both lines has both terms
+        let both = explanation
+            .windows
+            .iter()
+            .find(|w| w.start == 0 && w.len == 2 && w.exact)
+            .unwrap();
+        let terms = both.terms.iter().map(|t| (t.term.as_str(), t.line));
+        assert_eq!(terms.collect::<Vec<_>>(), vec![("blue", 0), ("money", 1)]);
+        let term_total = both.terms.iter().map(|t| t.score).sum::<f32>();
+        assert!((both.score - term_total * both.norm).abs() < 1e-5);
+        for term in &both.terms {
+            let value = term.explanation.as_ref().unwrap()["value"]
+                .as_f64()
+                .unwrap();
+            assert!((value as f32 - term.score).abs() < 1e-5);
+        }
+
+        // every line totals the windows it is part of
+        for (offset, total) in explanation.line_totals.iter().enumerate() {
+            let line = rm.clip.index + offset;
+            let sum = explanation
+                .windows
+                .iter()
+                .filter(|w| (w.start..w.start + w.len).contains(&line))
+                .map(|w| w.score)
+                .sum::<f32>();
+            assert!((total - sum).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn outdated_index_info() {
+        assert!(outdated_reason(None).is_some());
+        let old = SearchIndexInfo {
+            schema_version: SCHEMA_VERSION - 1,
+            ..index_info(5, Language::English, &SubtitleCleaner::index_default())
+        };
+        assert!(outdated_reason(Some(&old)).is_some());
+        let other_tokenizer = SearchIndexInfo {
+            tokenizer: "default".to_string(),
+            ..index_info(5, Language::English, &SubtitleCleaner::index_default())
+        };
+        assert!(outdated_reason(Some(&other_tokenizer)).is_some());
+        let spanish = index_info(5, Language::Spanish, &SubtitleCleaner::index_default());
+        assert_eq!(outdated_reason(Some(&spanish)), None);
+    }
+
+    fn matched_episodes(index: &SearchIndex, q: &str) -> Vec<usize> {
+        let scores = index
+            .search(q, 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        let mut episodes = ranked_episodes(&scores)
+            .into_iter()
+            .map(|(ep, _)| ep)
+            .collect::<Vec<_>>();
+        episodes.sort();
+        episodes.dedup();
+        episodes
+    }
+
+    #[test]
+    fn query_phrases_and_exclusions() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            episode(1, &["the banana stand", "no touching"]),
+            episode(2, &["stand by the banana", "touching"]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        assert_eq!(matched_episodes(&index, "banana stand"), vec![1, 2]);
+        assert_eq!(matched_episodes(&index, r#""banana stand""#), vec![1]);
+        assert_eq!(matched_episodes(&index, "banana -touching"), vec![1, 2]);
+        assert!(matched_episodes(&index, "stand -banana").is_empty());
+        // exclusions remove clips, not whole episodes
+        assert_eq!(matched_episodes(&index, "touching -banana"), vec![1, 2]);
+    }
+
+    #[test]
+    fn exclusions_remove_clips() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            season_episode(1, 2, 1, &["no touching", "lucille", "no touching"]),
+            season_episode(2, 2, 2, &["no touching lucille", "I said"]),
+            season_episode(3, 1, 1, &["no touching", "I said no"]),
+            season_episode(4, 1, 3, &["no", "touching lucille"]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            3,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        let scores = index
+            .search(
+                r#""no touching" -"lucille" season:2"#,
+                3,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        let clips = rank(&scores)
+            .into_iter()
+            .map(|rm| (rm.ep, rm.clip.index, rm.clip.scores.len()))
+            .collect::<Vec<_>>();
+        // no clip reaches across the line with "lucille" in it
+        assert_eq!(clips.len(), 2, "{:?}", clips);
+        assert!(clips.contains(&(1, 0, 1)));
+        assert!(clips.contains(&(1, 2, 1)));
+
+        // a phrase across lines excludes the clips with both of them
+        let scores = index
+            .search(
+                r#"lucille -"no touching""#,
+                3,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        let clips = rank(&scores)
+            .into_iter()
+            .filter(|rm| rm.ep == 4)
+            .map(|rm| (rm.clip.index, rm.clip.scores.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(clips, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn quote_of_common_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            episode(1, &["to be or not to be", "that is the question"]),
+            episode(2, &["no touching", "in the banana stand"]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        assert_eq!(matched_episodes(&index, r#""to be or not to be""#), vec![1]);
+        assert_eq!(matched_episodes(&index, "to be or not to be"), vec![1]);
+    }
+
+    #[test]
+    fn phrase_across_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            episode(1, &["I said no", "touching!", "in the banana stand"]),
+            episode(2, &["no way", "stop touching"]),
+            episode(3, &["there's always money", "no touching"]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        assert_eq!(matched_episodes(&index, r#""no touching""#), vec![1, 3]);
+        let scores = index
+            .search(
+                r#""no touching""#,
+                2,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .unwrap();
+        let ranked = rank(&scores);
+        let crossing = ranked.iter().find(|rm| rm.ep == 1).unwrap();
+        // the clip has both halves of the phrase
+        assert_eq!(crossing.clip.index, 0);
+        assert!(crossing.clip.scores.len() >= 2);
+    }
+
+    /// The best clip of each episode, best first, as ranked when every
+    /// window of up to `max_window` lines was its own document
+    fn windowed_ranking(eps: &[ContentData], q: &str, max_window: usize) -> Vec<(usize, usize)> {
+        let mut schema_builder = Schema::builder();
+        let text_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer("en_stem")
+                .set_index_option(IndexRecordOption::Basic),
+        );
+        let body = schema_builder.add_text_field("body", text_options);
+        let episode = schema_builder.add_i64_field("episode", STORED);
+        let clip_start = schema_builder.add_u64_field("clip_start", STORED);
+        let clip_end = schema_builder.add_u64_field("clip_end", STORED);
+        let mut index = Index::create_in_ram(schema_builder.build());
+        prepare_index(&mut index);
+
+        let mut index_writer = index.writer(50_000_000).unwrap();
+        for ep in eps {
+            let lines = ep
+                .subtitle
+                .subs
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<Vec<_>>();
+            for window in 0..max_window {
+                for start in 0..lines.len() - window {
+                    let end = start + window + 1;
+                    index_writer.add_document(doc!(
+                        body => lines[start..end].join(" "),
+                        episode => ep.subtitle.id,
+                        clip_start => start as u64,
+                        clip_end => end as u64,
+                    ));
+                }
+            }
+        }
+        index_writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let query = tantivy::query::QueryParser::for_index(&index, vec![body])
+            .parse_query(q)
+            .unwrap();
+        let mut scores = HashMap::new();
+        for (score, doc_address) in searcher
+            .search(&query, &tantivy::collector::TopDocs::with_limit(10000))
+            .unwrap()
+        {
+            let doc = searcher.doc(doc_address).unwrap();
+            let en = doc.get_first(episode).unwrap().i64_value().unwrap() as usize;
+            let cs = doc.get_first(clip_start).unwrap().u64_value().unwrap() as usize;
+            let ce = doc.get_first(clip_end).unwrap().u64_value().unwrap() as usize;
+            scores
+                .entry(en)
+                .or_insert_with(|| EpisodeScore {
+                    episode: en,
+                    inner: vec![],
+                    exact: vec![],
+                    windows: vec![],
+                })
+                .add(cs, ce, score, MatchKind::Exact);
+        }
+        best_clips(&scores)
+    }
+
+    fn best_clips(scores: &HashMap<usize, EpisodeScore>) -> Vec<(usize, usize)> {
+        let mut best = vec![];
+        for rm in rank(scores).into_iter().rev() {
+            if best.iter().all(|(ep, _)| *ep != rm.ep) {
+                best.push((rm.ep, rm.clip.index));
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn ranking_matches_windowed_documents() {
+        let eps = vec![
+            episode(
+                1,
+                &[
+                    "I just blue myself",
+                    "there's always money",
+                    "in the banana stand",
+                    "no touching, please",
+                ],
+            ),
+            episode(
+                2,
+                &[
+                    "no touching",
+                    "money money money",
+                    "I've made a huge mistake",
+                    "I said no touching",
+                ],
+            ),
+            episode(
+                3,
+                &[
+                    "here's some more money",
+                    "go see a star war",
+                    "the banana stand burned down",
+                    "there was money in there",
+                ],
+            ),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.clone().into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        for q in ["money", "touching", "banana", "mistake", "banana stand"] {
+            let scores = index
+                .search(q, 2, QueryMode::Exact, &SearchFilter::default())
+                .unwrap();
+            assert_eq!(best_clips(&scores), windowed_ranking(&eps, q, 2), "{}", q);
+        }
+    }
+
+    #[test]
+    fn query_or_and_required() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+
+        assert_eq!(matched_episodes(&index, "banana OR star"), vec![2, 3]);
+        // words are alternatives, clips with more of them rank higher
+        assert_eq!(matched_episodes(&index, "banana star"), vec![2, 3]);
+        let scores = index
+            .search("money star", 2, QueryMode::Exact, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(ranked_episodes(&scores).first(), Some(&(3, true)));
+        assert_eq!(matched_episodes(&index, "money star"), vec![1, 3]);
+        // every required clause must match within the same clip
+        assert_eq!(matched_episodes(&index, "+money +star"), vec![3]);
+        assert_eq!(matched_episodes(&index, "+money star"), vec![1, 3]);
+        assert!(matched_episodes(&index, "+banana +money").is_empty());
+    }
+
+    #[test]
+    fn query_field_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = filter_index(dir.path());
+
+        assert_eq!(matched_episodes(&index, "money season:2"), vec![2]);
+        assert_eq!(matched_episodes(&index, "money s:1-2 ep:4"), vec![1]);
+        assert_eq!(matched_episodes(&index, "money -season:1"), vec![2, 3]);
+    }
+
+    #[test]
+    fn reject_phrase_of_stopwords() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = vec![
+            episode(1, &["el plátano de la casa", "hola"]),
+            episode(2, &["una canción para ti"; 4]),
+        ];
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::Spanish,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        let err = index
+            .search(
+                r#"platano "de la""#,
+                2,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .err()
+            .unwrap();
+        assert!(
+            matches!(&err, TError::Query(QueryError::OnlyStopwords { text }) if text == "de la"),
+            "{}",
+            err
+        );
+        // a single stopword is left out
+        assert_eq!(matched_episodes(&index, "de platano"), vec![1]);
+    }
+
+    #[test]
+    fn query_errors_are_descriptive() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = test_index(dir.path());
+
+        let err = index
+            .search(
+                "money season:two",
+                2,
+                QueryMode::Exact,
+                &SearchFilter::default(),
+            )
+            .err()
+            .unwrap();
+        assert!(matches!(err, TError::Query(_)));
+        assert!(err.to_string().contains("season:two"), "{}", err);
+    }
+}
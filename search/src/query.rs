@@ -0,0 +1,592 @@
+//! The search query language.
+//!
+//! A query is a list of clauses separated by whitespace:
+//!
+//! - `word` matches lines containing the (stemmed) word
+//! - `"some words"` matches lines containing the words next to each other,
+//!   a phrase may run on from the end of one line into the next
+//! - `season:2`, `episode:1-5` keep only matches from those seasons or episodes,
+//!   `s:` and `ep:` (or `e:`) are short for `season:` and `episode:`
+//! - `a OR b` matches either clause, `OR` must be upper case
+//! - `+word` or `+"some words"` must be in every clip
+//! - `-word` or `-"some words"` excludes the clips which contain it,
+//!   `-season:2` excludes the episodes of season 2
+//!
+//! A clip matches if it contains any of the words and phrases searched for,
+//! clips with more of them rank higher. If some are marked with `+`, a clip
+//! must contain all of those, though not necessarily on the same line.
+//!
+//! The parser is forgiving: an unterminated quote runs to the end of the query,
+//! a stray `-` or `OR` is ignored, and an unknown `field:` is searched for as
+//! plain text. Only filters with invalid values, phrases made of nothing
+//! but stopwords, and queries which can't match anything, are errors.
+
+use std::ops::{Range, RangeInclusive};
+
+use tantivy::{
+    query::{BooleanQuery, BoostQuery, Occur, PhraseQuery, Query, RangeQuery, TermQuery},
+    schema::{Field, IndexRecordOption, Schema},
+    Index, Term,
+};
+use thiserror::Error;
+
+use crate::{error::TError, get_field, SchemaField, TermScoreQuery};
+
+/// A query which can not be searched for
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QueryError {
+    #[error("`{text}` is not a valid {field} filter, use a number like `{field}:2` or a range like `{field}:1-3`")]
+    InvalidFilter { field: &'static str, text: String },
+    #[error(
+        "`{text}` joins a filter and words with OR, filters can only be joined to other filters"
+    )]
+    MixedOr { text: String },
+    #[error("the query only excludes or filters clips, add something to search for")]
+    NothingToMatch,
+    #[error("\"{text}\" is only made of words too common to search for, add some other words")]
+    OnlyStopwords { text: String },
+}
+
+/// A query, as a list of clauses which must all match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedQuery {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    /// Clips matching any of the alternatives are excluded, instead of searched for
+    pub negated: bool,
+    /// Every clip must match one of the alternatives
+    pub required: bool,
+    /// Alternatives joined by `OR`, any one of them may match
+    pub alternatives: Vec<Atom>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Atom {
+    pub kind: AtomKind,
+    /// The byte range of the atom in the query, without quotes or a leading `-`
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtomKind {
+    Word(String),
+    Phrase(String),
+    Filter(QueryFilter),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryFilter {
+    Season(RangeInclusive<u32>),
+    Episode(RangeInclusive<u32>),
+}
+
+impl ParsedQuery {
+    /// The words and phrases of the query, searched for or excluded
+    pub fn text_atoms(&self) -> impl Iterator<Item = &Atom> {
+        self.clauses
+            .iter()
+            .flat_map(|c| c.alternatives.iter())
+            .filter(|a| !matches!(a.kind, AtomKind::Filter(_)))
+    }
+}
+
+enum Token {
+    Item {
+        negated: bool,
+        required: bool,
+        atom: Atom,
+    },
+    Or,
+}
+
+/// Parse a query, see the [module documentation](self) for the grammar
+pub fn parse_query(q: &str) -> Result<ParsedQuery, QueryError> {
+    let mut clauses: Vec<Clause> = Vec::new();
+    let mut pending_or = false;
+    for token in tokenize(q)? {
+        match token {
+            Token::Or => {
+                // only join clauses which are searched for
+                pending_or = matches!(clauses.last(), Some(c) if !c.negated);
+            }
+            Token::Item {
+                negated,
+                required,
+                atom,
+            } => {
+                match clauses.last_mut() {
+                    Some(last) if pending_or && !negated => last.alternatives.push(atom),
+                    _ => clauses.push(Clause {
+                        negated,
+                        required,
+                        alternatives: vec![atom],
+                    }),
+                }
+                pending_or = false;
+            }
+        }
+    }
+
+    for clause in &clauses {
+        let filters = clause
+            .alternatives
+            .iter()
+            .filter(|a| matches!(a.kind, AtomKind::Filter(_)))
+            .count();
+        if filters != 0 && filters != clause.alternatives.len() {
+            let start = clause.alternatives.first().unwrap().range.start;
+            let end = clause.alternatives.last().unwrap().range.end;
+            return Err(QueryError::MixedOr {
+                text: q[start..end].to_string(),
+            });
+        }
+    }
+
+    Ok(ParsedQuery { clauses })
+}
+
+fn tokenize(q: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = q.char_indices().peekable();
+    while let Some(&(_, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let negated = c == '-';
+        let required = c == '+';
+        if negated || required {
+            chars.next();
+        }
+        let (start, quoted) = match chars.peek() {
+            Some(&(idx, '"')) => {
+                chars.next();
+                (idx + 1, true)
+            }
+            Some(&(idx, c)) if !c.is_whitespace() => (idx, false),
+            // a `-` or `+` on its own
+            _ => continue,
+        };
+
+        let mut end = q.len();
+        while let Some(&(idx, c)) = chars.peek() {
+            if quoted && c == '"' {
+                end = idx;
+                chars.next();
+                break;
+            }
+            if !quoted && (c.is_whitespace() || c == '"') {
+                end = idx;
+                break;
+            }
+            chars.next();
+        }
+        let text = &q[start..end];
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let kind = if quoted {
+            AtomKind::Phrase(text.to_string())
+        } else if text == "OR" && !negated && !required {
+            tokens.push(Token::Or);
+            continue;
+        } else {
+            match parse_filter(text)? {
+                Some(filter) => AtomKind::Filter(filter),
+                None => AtomKind::Word(text.to_string()),
+            }
+        };
+        tokens.push(Token::Item {
+            negated,
+            required,
+            atom: Atom {
+                kind,
+                range: start..end,
+            },
+        });
+    }
+    Ok(tokens)
+}
+
+/// `None` if `text` is not a filter at all
+fn parse_filter(text: &str) -> Result<Option<QueryFilter>, QueryError> {
+    let (field, value) = match text.split_once(':') {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    let (field, filter): (_, fn(RangeInclusive<u32>) -> QueryFilter) =
+        match field.to_lowercase().as_str() {
+            "season" | "s" => ("season", QueryFilter::Season),
+            "episode" | "ep" | "e" => ("episode", QueryFilter::Episode),
+            _ => return Ok(None),
+        };
+    let invalid = || QueryError::InvalidFilter {
+        field,
+        text: text.to_string(),
+    };
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    let start = start.trim().parse::<u32>().map_err(|_| invalid())?;
+    let end = end.trim().parse::<u32>().map_err(|_| invalid())?;
+    if start > end {
+        return Err(invalid());
+    }
+    Ok(Some(filter(start..=end)))
+}
+
+/// A parsed query translated into tantivy queries for one index
+pub(crate) struct CompiledQuery {
+    /// The number of clauses which must all match within a clip.
+    ///
+    /// Each required clause is a group of its own, the other clauses share the
+    /// group after them, which only has to match if nothing is required.
+    /// Zero if there is nothing to search for.
+    pub groups: usize,
+    /// A query for every term of every clause searched for
+    pub term_queries: Vec<TermScoreQuery>,
+    /// The words which may be matched fuzzily, and their clause
+    pub fuzzy_words: Vec<(usize, String)>,
+    /// Matches the lines matching any clause
+    pub matches: Box<dyn Query>,
+    /// Filters from the query, every line must satisfy them all
+    pub restrictions: Vec<(Occur, Box<dyn Query>)>,
+    /// Matches the lines containing an excluded word or phrase, no clip may include one
+    pub exclusions: Vec<Box<dyn Query>>,
+    /// Matches the spans where an excluded phrase runs from a line into the
+    /// next, no clip may include both lines
+    pub crossing_exclusions: Vec<Box<dyn Query>>,
+}
+
+/// The group of the clauses which are not required, until the number of
+/// required clauses is known
+const OPTIONAL_GROUP: usize = usize::MAX;
+
+pub(crate) fn compile_query(
+    index: &Index,
+    parsed: &ParsedQuery,
+    min_fuzzy_len: usize,
+) -> Result<CompiledQuery, TError> {
+    let schema = index.schema();
+    let body = get_field(&schema, SchemaField::Body);
+    let span = get_field(&schema, SchemaField::Span);
+    let tokenizer = index.tokenizer_for_field(body)?;
+    let tokens = |text: &str| {
+        let mut terms = Vec::new();
+        let mut token_stream = tokenizer.token_stream(text);
+        while let Some(token) = token_stream.next() {
+            terms.push((token.position, token.text.clone()));
+        }
+        terms
+    };
+
+    let mut compiled = CompiledQuery {
+        groups: 0,
+        term_queries: vec![],
+        fuzzy_words: vec![],
+        matches: Box::new(BooleanQuery::new(vec![])),
+        restrictions: vec![],
+        exclusions: vec![],
+        crossing_exclusions: vec![],
+    };
+    let mut matches: Vec<(Occur, Box<dyn Query>)> = vec![];
+    let mut optional = false;
+    for clause in &parsed.clauses {
+        let group = if clause.required {
+            compiled.groups
+        } else {
+            OPTIONAL_GROUP
+        };
+        let mut atom_queries = vec![];
+        for atom in &clause.alternatives {
+            let text = match &atom.kind {
+                AtomKind::Word(text) | AtomKind::Phrase(text) => text,
+                AtomKind::Filter(filter) => {
+                    atom_queries.push(filter_query(&schema, filter));
+                    continue;
+                }
+            };
+            let terms = tokens(text);
+            let atom_query = match (text_query(body, &terms), &atom.kind) {
+                (Some(query), _) => query,
+                (None, AtomKind::Phrase(text)) => {
+                    return Err(QueryError::OnlyStopwords { text: text.clone() }.into())
+                }
+                // a stopword is left out, like it was when indexed
+                (None, _) => continue,
+            };
+            if clause.negated {
+                if terms.len() > 1 {
+                    compiled
+                        .crossing_exclusions
+                        .push(text_query(span, &terms).unwrap());
+                }
+                compiled.exclusions.push(atom_query);
+                continue;
+            }
+            for (_, text) in &terms {
+                let term_query: Box<dyn Query> = Box::new(TermQuery::new(
+                    Term::from_field_text(body, text),
+                    IndexRecordOption::WithFreqs,
+                ));
+                let (query, crossing) = if terms.len() == 1 {
+                    (term_query, None)
+                } else {
+                    // each term only scores where the whole phrase matched
+                    let phrase = BoostQuery::new(atom_query.box_clone(), 0.0);
+                    let query = BooleanQuery::new(vec![
+                        (Occur::Must, term_query),
+                        (Occur::Must, Box::new(phrase)),
+                    ]);
+                    let crossing_term = TermQuery::new(
+                        Term::from_field_text(span, text),
+                        IndexRecordOption::WithFreqs,
+                    );
+                    let crossing_phrase = BoostQuery::new(text_query(span, &terms).unwrap(), 0.0);
+                    let crossing: Box<dyn Query> = Box::new(BooleanQuery::new(vec![
+                        (Occur::Must, Box::new(crossing_term)),
+                        (Occur::Must, Box::new(crossing_phrase)),
+                    ]));
+                    (Box::new(query) as Box<dyn Query>, Some(crossing))
+                };
+                compiled.term_queries.push(TermScoreQuery {
+                    text: text.clone(),
+                    group,
+                    query,
+                    crossing,
+                });
+            }
+            if let AtomKind::Word(_) = atom.kind {
+                compiled.fuzzy_words.extend(
+                    terms
+                        .iter()
+                        .filter(|(_, t)| t.chars().count() >= min_fuzzy_len)
+                        .map(|(_, t)| (group, t.clone())),
+                );
+            }
+            atom_queries.push(atom_query);
+        }
+
+        if atom_queries.is_empty() {
+            continue;
+        }
+        let is_filter = matches!(clause.alternatives[0].kind, AtomKind::Filter(_));
+        let any: Box<dyn Query> = if atom_queries.len() == 1 {
+            atom_queries.pop().unwrap()
+        } else {
+            Box::new(BooleanQuery::new(
+                atom_queries
+                    .into_iter()
+                    .map(|q| (Occur::Should, q))
+                    .collect(),
+            ))
+        };
+        if clause.negated {
+            compiled.restrictions.push((Occur::MustNot, any));
+        } else if is_filter {
+            let any: Box<dyn Query> = Box::new(BoostQuery::new(any, 0.0));
+            compiled.restrictions.push((Occur::Must, any));
+        } else {
+            matches.push((Occur::Should, any));
+            if clause.required {
+                compiled.groups += 1;
+            } else {
+                optional = true;
+            }
+        }
+    }
+
+    let optional_group = compiled.groups;
+    for term_query in &mut compiled.term_queries {
+        if term_query.group == OPTIONAL_GROUP {
+            term_query.group = optional_group;
+        }
+    }
+    for (group, _) in &mut compiled.fuzzy_words {
+        if *group == OPTIONAL_GROUP {
+            *group = optional_group;
+        }
+    }
+    if compiled.groups == 0 && optional {
+        compiled.groups = 1;
+    }
+
+    let excludes = !compiled.restrictions.is_empty() || !compiled.exclusions.is_empty();
+    if compiled.groups == 0 && excludes {
+        return Err(QueryError::NothingToMatch.into());
+    }
+    compiled.matches = Box::new(BooleanQuery::new(matches));
+    Ok(compiled)
+}
+
+/// A term query for a single term, or a phrase query for several
+fn text_query(field: Field, terms: &[(usize, String)]) -> Option<Box<dyn Query>> {
+    match terms {
+        [] => None,
+        [(_, text)] => Some(Box::new(TermQuery::new(
+            Term::from_field_text(field, text),
+            IndexRecordOption::WithFreqs,
+        ))),
+        _ => Some(Box::new(PhraseQuery::new_with_offset(
+            terms
+                .iter()
+                .map(|(position, text)| (*position, Term::from_field_text(field, text)))
+                .collect(),
+        ))),
+    }
+}
+
+fn filter_query(schema: &Schema, filter: &QueryFilter) -> Box<dyn Query> {
+    let (field, range) = match filter {
+        QueryFilter::Season(range) => (SchemaField::Season, range),
+        QueryFilter::Episode(range) => (SchemaField::EpisodeNumber, range),
+    };
+    let range = *range.start() as u64..(*range.end() as u64 + 1);
+    Box::new(RangeQuery::new_u64(get_field(schema, field), range))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn word(text: &str, range: Range<usize>) -> Atom {
+        Atom {
+            kind: AtomKind::Word(text.to_string()),
+            range,
+        }
+    }
+
+    fn clause(negated: bool, alternatives: Vec<Atom>) -> Clause {
+        Clause {
+            negated,
+            required: false,
+            alternatives,
+        }
+    }
+
+    #[test]
+    fn parse_phrases_exclusions_and_filters() {
+        let q = r#""no touching" -"lucille" season:2 ep:1-3"#;
+        let parsed = parse_query(q).unwrap();
+        assert_eq!(
+            parsed.clauses,
+            vec![
+                clause(
+                    false,
+                    vec![Atom {
+                        kind: AtomKind::Phrase("no touching".to_string()),
+                        range: 1..12,
+                    }]
+                ),
+                clause(
+                    true,
+                    vec![Atom {
+                        kind: AtomKind::Phrase("lucille".to_string()),
+                        range: 16..23,
+                    }]
+                ),
+                clause(
+                    false,
+                    vec![Atom {
+                        kind: AtomKind::Filter(QueryFilter::Season(2..=2)),
+                        range: 25..33,
+                    }]
+                ),
+                clause(
+                    false,
+                    vec![Atom {
+                        kind: AtomKind::Filter(QueryFilter::Episode(1..=3)),
+                        range: 34..40,
+                    }]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_or() {
+        let parsed = parse_query("money OR banana stand").unwrap();
+        assert_eq!(
+            parsed.clauses,
+            vec![
+                clause(false, vec![word("money", 0..5), word("banana", 9..15)]),
+                clause(false, vec![word("stand", 16..21)]),
+            ]
+        );
+
+        // exclusions are never alternatives
+        let parsed = parse_query("money OR -banana").unwrap();
+        assert_eq!(
+            parsed.clauses,
+            vec![
+                clause(false, vec![word("money", 0..5)]),
+                clause(true, vec![word("banana", 10..16)]),
+            ]
+        );
+
+        let parsed = parse_query("season:1 OR s:3").unwrap();
+        assert_eq!(parsed.clauses.len(), 1);
+        assert!(matches!(
+            parse_query("money OR season:1"),
+            Err(QueryError::MixedOr { text }) if text == "money OR season:1"
+        ));
+    }
+
+    #[test]
+    fn parse_required() {
+        let parsed = parse_query(r#"+money +"banana stand" OR star always"#).unwrap();
+        let required = parsed
+            .clauses
+            .iter()
+            .map(|c| (c.required, c.alternatives.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(required, vec![(true, 1), (true, 2), (false, 1)]);
+        assert_eq!(
+            parse_query("+ money").unwrap().clauses,
+            vec![clause(false, vec![word("money", 2..7)])]
+        );
+    }
+
+    #[test]
+    fn parse_forgivingly() {
+        // unterminated quote
+        let parsed = parse_query(r#"always "banana stand"#).unwrap();
+        assert_eq!(
+            parsed.clauses[1].alternatives[0].kind,
+            AtomKind::Phrase("banana stand".to_string())
+        );
+        // stray `-`, `OR` and empty quotes
+        let parsed = parse_query(r#"OR - money OR "" -"#).unwrap();
+        assert_eq!(
+            parsed.clauses,
+            vec![clause(false, vec![word("money", 5..10)])]
+        );
+        // lower case or, and unknown fields, are words
+        let parsed = parse_query("money or 10:30").unwrap();
+        assert_eq!(
+            parsed.clauses,
+            vec![
+                clause(false, vec![word("money", 0..5)]),
+                clause(false, vec![word("or", 6..8)]),
+                clause(false, vec![word("10:30", 9..14)]),
+            ]
+        );
+        assert_eq!(parse_query("  ").unwrap(), ParsedQuery::default());
+    }
+
+    #[test]
+    fn reject_invalid_filters() {
+        for q in ["season:two", "episode:", "s:3-1", "ep:1-"] {
+            let err = parse_query(q).unwrap_err();
+            assert!(matches!(err, QueryError::InvalidFilter { .. }), "{}", q);
+        }
+        let err = parse_query("money season:x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`season:x` is not a valid season filter, use a number like `season:2` or a range like `season:1-3`"
+        );
+    }
+}
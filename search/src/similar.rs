@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tantivy::{
+    query::{BooleanQuery, Occur, Query, TermQuery},
+    schema::{Field, IndexRecordOption},
+    Index, Searcher, SnippetGenerator, Term,
+};
+
+use crate::{
+    collect_line_scores, error::TError, filter_clauses, get_field, index_language,
+    language::filler_words, score_windows, EpisodeScore, Highlighter, MatchKind, SchemaField,
+    SearchFilter, TermScoreQuery,
+};
+
+/// At most this many of the most distinctive terms of the text are searched for
+const MAX_SIMILAR_TERMS: usize = 12;
+
+/// The terms of `text` which best tell it apart from the rest of the index,
+/// most distinctive first.
+///
+/// Terms are weighted by TF-IDF: how often they occur in `text`, and how rare
+/// they are in the index. Terms which are not indexed anywhere can't match.
+fn similar_terms(
+    searcher: &Searcher,
+    index: &Index,
+    body: Field,
+    text: &str,
+) -> tantivy::Result<Vec<Term>> {
+    let tokenizer = index.tokenizer_for_field(body)?;
+    let filler = index_language(&index.schema()).map_or(&[][..], filler_words);
+    let mut term_freqs = BTreeMap::<String, usize>::new();
+    let mut token_stream = tokenizer.token_stream(text);
+    while let Some(token) = token_stream.next() {
+        let word = text[token.offset_from..token.offset_to].to_lowercase();
+        if filler.contains(&word.as_str()) {
+            continue;
+        }
+        *term_freqs.entry(token.text.clone()).or_default() += 1;
+    }
+
+    let line_count = searcher.num_docs() as f32;
+    let mut weighted = vec![];
+    for (text, freq) in term_freqs {
+        let term = Term::from_field_text(body, &text);
+        let doc_freq = searcher.doc_freq(&term)?;
+        if doc_freq == 0 {
+            continue;
+        }
+        // BM25's idf, which stays positive for very common terms
+        let idf = (1.0 + (line_count - doc_freq as f32 + 0.5) / (doc_freq as f32 + 0.5)).ln();
+        weighted.push((freq as f32 * idf, term));
+    }
+    weighted.sort_by(|(a, a_term), (b, b_term)| {
+        b.partial_cmp(a)
+            .expect("similar term weight is a number")
+            .then_with(|| a_term.text().cmp(b_term.text()))
+    });
+    Ok(weighted
+        .into_iter()
+        .take(MAX_SIMILAR_TERMS)
+        .map(|(_, term)| term)
+        .collect())
+}
+
+pub(crate) fn more_like_this_impl(
+    index: &Index,
+    text: &str,
+    search_window: usize,
+    filter: &SearchFilter,
+) -> Result<HashMap<usize, EpisodeScore>, TError> {
+    let schema = index.schema();
+    let body = get_field(&schema, SchemaField::Body);
+
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let filters = filter_clauses(&schema, filter)?;
+
+    // any of the terms may match, clips with more of them close together rank higher
+    let term_queries = similar_terms(&searcher, index, body, text)?
+        .into_iter()
+        .map(|term| TermScoreQuery {
+            text: term.text().to_string(),
+            group: 0,
+            query: Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)),
+            crossing: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut scores = HashMap::new();
+    if term_queries.is_empty() {
+        return Ok(scores);
+    }
+    let lines = collect_line_scores(&searcher, &schema, &term_queries, &filters, 1, false)?;
+    score_windows(
+        &lines,
+        &[&lines],
+        &HashMap::new(),
+        search_window,
+        MatchKind::Exact,
+        &mut scores,
+    );
+    Ok(scores)
+}
+
+pub(crate) fn more_like_this_highlighter(index: &Index, text: &str) -> Result<Highlighter, TError> {
+    let body = get_field(&index.schema(), SchemaField::Body);
+
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let clauses = similar_terms(&searcher, index, body, text)?
+        .into_iter()
+        .map(|term| {
+            let query: Box<dyn Query> =
+                Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+            (Occur::Should, query)
+        })
+        .collect::<Vec<_>>();
+    let query = BooleanQuery::new(clauses);
+
+    let snippets = SnippetGenerator::create(&searcher, &query, body)?;
+    let tokenizer = index.tokenizer_for_field(body)?;
+    Ok(Highlighter::new(snippets, tokenizer, vec![], 0))
+}
+
+#[cfg(test)]
+mod test {
+    use lucille_core::{
+        clean_sub::SubtitleCleaner,
+        language::Language,
+        metadata::{MediaHash, MediaMetadata},
+        test_util::generate_subtitle,
+        uuid::Uuid,
+        ContentData, LucilleSub,
+    };
+
+    use crate::{build_index, rank, SearchFilter};
+
+    #[test]
+    fn find_similar_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let eps = [
+            &["there's always money in the banana stand", "no touching"][..],
+            &["I just blue myself", "burn the banana stand down"][..],
+            &["there's always money", "here's some money"][..],
+            &["a lot of lines", "nothing in common"][..],
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, lines)| ContentData {
+            metadata: MediaMetadata::Unknown(format!("episode {}", i)),
+            hash: MediaHash::from_bytes(format!("episode {}", i).as_bytes()),
+            subtitle: LucilleSub {
+                id: i as i64 + 1,
+                uuid: Uuid::generate(),
+                subs: generate_subtitle(lines),
+            },
+        })
+        .collect::<Vec<_>>();
+        let index = build_index(
+            Uuid::generate(),
+            dir.path(),
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap();
+
+        let text = "there's always money in the banana stand";
+        let scores = index
+            .more_like_this(text, 1, &SearchFilter::default())
+            .unwrap();
+        let ranked = rank(&scores)
+            .into_iter()
+            .rev()
+            .map(|rm| (rm.ep, rm.clip.index))
+            .collect::<Vec<_>>();
+        // the lines themselves, then lines sharing the rarest terms
+        assert_eq!(ranked[0], (1, 0));
+        assert!(ranked.contains(&(2, 1)));
+        assert!(ranked.contains(&(3, 0)));
+        assert!(!ranked.iter().any(|(ep, _)| *ep == 4));
+
+        let highlighter = index.more_like_this_highlighter(text).unwrap();
+        assert_eq!(
+            highlighter.highlight("burn the banana stand down"),
+            vec![9..15, 16..21]
+        );
+    }
+}
@@ -1,73 +1,58 @@
-use lucille_core::{ContentData, Subtitle};
-
-pub fn generate_multi_window(
-    size: usize,
-    max_window: usize,
-) -> impl Iterator<Item = (usize, usize)> {
-    (0..max_window).flat_map(move |window| (0..(size - window)).map(move |s| (s, s + window + 1)))
-}
+use lucille_core::{
+    clean_sub::SubtitleCleaner, identifiers::CorpusId, metadata::MediaMetadata, ContentData,
+};
 
 pub struct IndexableEpisode {
-    pub title: String,
     pub srt_id: i64,
-    pub script: String,
-    pub subs: Vec<Subtitle>,
-    pub index: Vec<usize>,
-}
-
-pub struct Clip<'a> {
-    pub title: &'a str,
-    pub text: &'a str,
-    pub start: usize,
-    pub end: usize,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    pub corpus_id: Option<i64>,
+    /// The text of each subtitle, indexed as one document per line
+    pub lines: Vec<String>,
 }
 
-impl From<ContentData> for IndexableEpisode {
-    fn from(c: ContentData) -> Self {
+impl IndexableEpisode {
+    /// Index the subtitles of an episode as they are left by `cleaner`
+    pub fn new(corpus_id: Option<CorpusId>, c: ContentData, cleaner: &SubtitleCleaner) -> Self {
         let ContentData {
             subtitle: subs,
             metadata,
             hash: _,
         } = c;
-        let mut script = String::new();
-        let mut index = vec![0];
 
-        for sub in &subs.subs {
-            for line in sub.text.lines() {
-                let text = line.trim().trim_start_matches('-').trim();
-                script.push(' ');
-                script.push_str(text);
-            }
-            index.push(script.len())
-        }
+        // every subtitle stays a line, even if nothing is left of it, so
+        // that line numbers match the subtitle file
+        let lines = subs
+            .subs
+            .iter()
+            .map(|sub| cleaner.single_line(sub))
+            .collect();
+
+        let (season, episode) = match &metadata {
+            MediaMetadata::Episode(e) => (e.season, Some(e.episode)),
+            MediaMetadata::Movie { .. } | MediaMetadata::Unknown(_) => (None, None),
+        };
 
         IndexableEpisode {
-            title: metadata.title(),
             srt_id: subs.id,
-            script,
-            subs: subs.subs,
-            index,
+            season,
+            episode,
+            corpus_id: corpus_id.map(|c| c.get()),
+            lines,
         }
     }
 }
 
-impl IndexableEpisode {
-    pub fn extract_window(&self, start: usize, end: usize) -> &str {
-        let start_byte = self.index[start];
-        let end_byte = if end < self.index.len() {
-            self.index[end]
-        } else {
-            self.script.len()
-        };
-        &self.script[start_byte..end_byte]
+/// Index an episode cleaned with the default index rules
+impl From<ContentData> for IndexableEpisode {
+    fn from(c: ContentData) -> Self {
+        IndexableEpisode::new(None, c, &SubtitleCleaner::index_default())
     }
+}
 
-    pub fn slices(&self, max_window: usize) -> impl Iterator<Item = Clip<'_>> + '_ {
-        generate_multi_window(self.subs.len(), max_window).map(move |(start, end)| Clip {
-            title: self.title.as_str(),
-            text: self.extract_window(start, end),
-            start,
-            end,
-        })
+/// Index an episode along with the corpus it belongs to
+impl From<(CorpusId, ContentData)> for IndexableEpisode {
+    fn from((corpus_id, c): (CorpusId, ContentData)) -> Self {
+        IndexableEpisode::new(Some(corpus_id), c, &SubtitleCleaner::index_default())
     }
 }
@@ -0,0 +1,314 @@
+use std::{collections::HashMap, ops::Range};
+
+use serde::{Deserialize, Serialize};
+use tantivy::{schema::Field, Index, Searcher, Term};
+
+use crate::{highlight::edit_distance, language::words_analyzer, query::parse_query};
+
+/// A replacement for part of a query, taken from the indexed vocabulary.
+///
+/// Terms are words as they are written in the subtitles, lowercased.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    /// The byte range of the query to replace
+    pub range: Range<usize>,
+    pub term: String,
+}
+
+impl Suggestion {
+    /// The query with this suggestion applied
+    pub fn apply(&self, query: &str) -> String {
+        let mut applied = String::with_capacity(query.len() + self.term.len());
+        applied.push_str(&query[..self.range.start]);
+        applied.push_str(&self.term);
+        applied.push_str(&query[self.range.end..]);
+        applied
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestions {
+    /// Ways to finish the word being typed at the end of the query, most common first
+    pub completions: Vec<Suggestion>,
+    /// "Did you mean" spellings for words which are not in the index, closest first
+    pub corrections: Vec<Suggestion>,
+}
+
+/// Completions are only offered once this much of a word has been typed
+const MIN_PREFIX_LEN: usize = 2;
+
+/// `body` is searched to tell whether a word needs correcting, the
+/// suggestions are taken from the unstemmed `words`
+pub(crate) fn suggest_impl(
+    index: &Index,
+    body: Field,
+    words: Field,
+    q: &str,
+    limit: usize,
+) -> tantivy::Result<Suggestions> {
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+
+    // the last word is still being typed unless the query ends in a separator
+    let typing = matches!(q.chars().next_back(), Some(c) if c.is_alphanumeric());
+
+    let mut completions = vec![];
+    if typing {
+        let mut prefix = None;
+        let mut token_stream = words_analyzer().token_stream(q);
+        while let Some(token) = token_stream.next() {
+            prefix = Some((token.text.clone(), token.offset_from..token.offset_to));
+        }
+        if let Some((prefix, range)) = prefix.filter(|(p, _)| p.chars().count() >= MIN_PREFIX_LEN) {
+            completions = complete(&searcher, words, &prefix)?
+                .into_iter()
+                .take(limit)
+                .map(|term| Suggestion {
+                    range: range.clone(),
+                    term,
+                })
+                .collect();
+        }
+    }
+
+    // only the words and phrases of the query are corrected, not its syntax
+    let spans: Vec<Range<usize>> = match parse_query(q) {
+        Ok(parsed) => parsed.text_atoms().map(|a| a.range.clone()).collect(),
+        Err(_) => std::iter::once(0..q.len()).collect(),
+    };
+    let tokenizer = index.tokenizer_for_field(body)?;
+    let mut tokens = vec![];
+    for span in spans {
+        let mut token_stream = words_analyzer().token_stream(&q[span.clone()]);
+        while let Some(token) = token_stream.next() {
+            let range = span.start + token.offset_from..span.start + token.offset_to;
+            tokens.push((token.text.clone(), range));
+        }
+    }
+
+    let mut corrections = vec![];
+    for (text, range) in tokens {
+        if typing && range.end == q.len() {
+            continue;
+        }
+        let length = text.chars().count();
+        if length < crate::MIN_FUZZY_TERM_LEN {
+            continue;
+        }
+        // the word is searched for as it is indexed, which may be another
+        // form of the same word, or nothing at all for a stopword
+        let mut indexed = vec![];
+        let mut token_stream = tokenizer.token_stream(&text);
+        while let Some(token) = token_stream.next() {
+            indexed.push(Term::from_field_text(body, &token.text));
+        }
+        let mut found = indexed.is_empty();
+        for term in &indexed {
+            found |= searcher.doc_freq(term)? > 0;
+        }
+        if found {
+            continue;
+        }
+        let distance = if length < 6 {
+            1
+        } else {
+            crate::MAX_FUZZY_DISTANCE as usize
+        };
+        for term in closest_terms(&searcher, words, &text, distance)?
+            .into_iter()
+            .take(limit)
+        {
+            corrections.push(Suggestion {
+                range: range.clone(),
+                term,
+            });
+        }
+    }
+
+    Ok(Suggestions {
+        completions,
+        corrections,
+    })
+}
+
+/// The number of lines each indexed term of `field` occurs in, across all segments,
+/// for the terms accepted by `keep`. The dictionary scan starts at `start`,
+/// and stops at the first term for which `cont` is false.
+fn scan_terms(
+    searcher: &Searcher,
+    field: Field,
+    start: &str,
+    cont: impl Fn(&str) -> bool,
+    keep: impl Fn(&str) -> bool,
+) -> tantivy::Result<HashMap<String, u32>> {
+    let mut doc_freqs = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let mut stream = inverted_index.terms().range().ge(start).into_stream()?;
+        while let Some((key, info)) = stream.next() {
+            let term = match std::str::from_utf8(key) {
+                Ok(term) => term,
+                Err(_) => continue,
+            };
+            if !cont(term) {
+                break;
+            }
+            if keep(term) {
+                *doc_freqs.entry(term.to_string()).or_default() += info.doc_freq;
+            }
+        }
+    }
+    Ok(doc_freqs)
+}
+
+/// Indexed terms which start with `prefix`, most frequent first
+fn complete(searcher: &Searcher, field: Field, prefix: &str) -> tantivy::Result<Vec<String>> {
+    let doc_freqs = scan_terms(searcher, field, prefix, |t| t.starts_with(prefix), |_| true)?;
+    let mut terms = doc_freqs.into_iter().collect::<Vec<_>>();
+    terms.sort_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then_with(|| a.cmp(b)));
+    Ok(terms.into_iter().map(|(term, _)| term).collect())
+}
+
+/// Indexed terms within `distance` edits of `word`, closest then most frequent first
+fn closest_terms(
+    searcher: &Searcher,
+    field: Field,
+    word: &str,
+    distance: usize,
+) -> tantivy::Result<Vec<String>> {
+    let length = word.chars().count();
+    let doc_freqs = scan_terms(
+        searcher,
+        field,
+        "",
+        |_| true,
+        |t| {
+            let t_length = t.chars().count();
+            t_length + distance >= length
+                && t_length <= length + distance
+                && edit_distance(word, t) <= distance
+        },
+    )?;
+    let mut terms = doc_freqs
+        .into_iter()
+        .map(|(term, freq)| (edit_distance(word, &term), freq, term))
+        .collect::<Vec<_>>();
+    terms.sort_by(|(a_dist, a_freq, a), (b_dist, b_freq, b)| {
+        a_dist
+            .cmp(b_dist)
+            .then_with(|| b_freq.cmp(a_freq))
+            .then_with(|| a.cmp(b))
+    });
+    Ok(terms.into_iter().map(|(_, _, term)| term).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use lucille_core::{
+        clean_sub::SubtitleCleaner,
+        language::Language,
+        metadata::{MediaHash, MediaMetadata},
+        test_util::generate_subtitle,
+        uuid::Uuid,
+        ContentData, LucilleSub,
+    };
+
+    use crate::{build_index, SearchIndex};
+
+    fn suggest_index(dir: &std::path::Path) -> SearchIndex {
+        let eps = [
+            &["there's always money", "in the banana stand"][..],
+            &["money money money", "no touching"][..],
+            &["I'm a monster", "bands"][..],
+            &["what a story", "stories"][..],
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, lines)| ContentData {
+            metadata: MediaMetadata::Unknown(format!("episode {}", i)),
+            hash: MediaHash::from_bytes(format!("episode {}", i).as_bytes()),
+            subtitle: LucilleSub {
+                id: i as i64,
+                uuid: Uuid::generate(),
+                subs: generate_subtitle(lines),
+            },
+        })
+        .collect::<Vec<_>>();
+        build_index(
+            Uuid::generate(),
+            dir,
+            eps.into_iter(),
+            2,
+            Language::English,
+            &SubtitleCleaner::index_default(),
+        )
+        .unwrap()
+    }
+
+    fn terms(suggestions: &[super::Suggestion]) -> Vec<&str> {
+        suggestions.iter().map(|s| s.term.as_str()).collect()
+    }
+
+    #[test]
+    fn complete_last_word() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = suggest_index(dir.path());
+
+        let suggestions = index.suggest("always Mon", 5).unwrap();
+        // money is in more lines than monster
+        assert_eq!(terms(&suggestions.completions), vec!["money", "monster"]);
+        assert!(suggestions.corrections.is_empty());
+        let completion = &suggestions.completions[0];
+        assert_eq!(completion.range, 7..10);
+        assert_eq!(completion.apply("always Mon"), "always money");
+
+        let suggestions = index.suggest("ban", 1).unwrap();
+        assert_eq!(terms(&suggestions.completions), vec!["banana"]);
+
+        // words are suggested as they were written, not stemmed
+        let suggestions = index.suggest("sto", 5).unwrap();
+        assert_eq!(terms(&suggestions.completions), vec!["stories", "story"]);
+
+        // nothing is being typed after a separator, or after a single letter
+        assert!(index
+            .suggest("always mon ", 5)
+            .unwrap()
+            .completions
+            .is_empty());
+        assert!(index.suggest("always m", 5).unwrap().completions.is_empty());
+    }
+
+    #[test]
+    fn correct_misspelled_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = suggest_index(dir.path());
+
+        let suggestions = index.suggest("monye in the bananna stand", 5).unwrap();
+        assert_eq!(terms(&suggestions.corrections), vec!["money", "banana"]);
+        assert_eq!(suggestions.corrections[0].range, 0..5);
+        assert_eq!(
+            suggestions.corrections[1].apply("monye in the bananna stand"),
+            "monye in the banana stand"
+        );
+
+        // the word being typed is completed, not corrected
+        let suggestions = index.suggest("money monye", 5).unwrap();
+        assert!(suggestions.corrections.is_empty());
+
+        // nor is the syntax of the query
+        let suggestions = index
+            .suggest("season:2 monye OR \"bananna stand\" -touchng ", 5)
+            .unwrap();
+        assert_eq!(
+            terms(&suggestions.corrections),
+            vec!["money", "banana", "touching"]
+        );
+
+        // words in the index need no correction, in any form
+        assert!(index
+            .suggest("banana stands story ", 5)
+            .unwrap()
+            .corrections
+            .is_empty());
+    }
+}